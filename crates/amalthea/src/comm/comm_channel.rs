@@ -26,6 +26,9 @@ pub enum Comm {
     /// A dynamic (resizable) plot.
     Plot,
 
+    /// The history of the plots of the graphics device.
+    PlotHistory,
+
     /// A data viewer.
     DataViewer,
 
@@ -503,7 +503,11 @@ pub struct SupportedFeatures {
 	pub set_sort_columns: SetSortColumnsFeatures,
 
 	/// Support for 'export_data_selection' RPC and its features
-	pub export_data_selection: ExportDataSelectionFeatures
+	pub export_data_selection: ExportDataSelectionFeatures,
+
+	/// Support for 'set_cell_value' and 'undo_cell_edit' RPCs. May be omitted
+	/// by backends that don't support cell edits.
+	pub set_cell_value: Option<SetCellValueFeatures>
 }
 
 /// Feature flags for 'search_schema' RPC
@@ -559,6 +563,13 @@ pub struct ExportDataSelectionFeatures {
 	pub supported_formats: Vec<ExportFormat>
 }
 
+/// Feature flags for 'set_cell_value' RPC
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SetCellValueFeatures {
+	/// The support status for this RPC method
+	pub support_status: SupportStatus
+}
+
 /// Feature flags for 'set_sort_columns' RPC
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct SetSortColumnsFeatures {
@@ -855,7 +866,23 @@ pub enum ExportFormat {
 
 	#[serde(rename = "html")]
 	#[strum(to_string = "html")]
-	Html
+	Html,
+
+	#[serde(rename = "markdown")]
+	#[strum(to_string = "markdown")]
+	Markdown,
+
+	#[serde(rename = "json_records")]
+	#[strum(to_string = "json_records")]
+	JsonRecords,
+
+	#[serde(rename = "json_columns")]
+	#[strum(to_string = "json_columns")]
+	JsonColumns,
+
+	#[serde(rename = "parquet")]
+	#[strum(to_string = "parquet")]
+	Parquet
 }
 
 /// Possible values for SupportStatus
@@ -992,6 +1019,21 @@ pub struct ExportDataSelectionParams {
 	pub format: ExportFormat,
 }
 
+/// Parameters for the SetCellValue method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct SetCellValueParams {
+	/// Row index of the cell in the current view (after sorting and
+	/// filtering)
+	pub row_index: i64,
+
+	/// Column index of the cell in the current view (after column filtering)
+	pub column_index: i64,
+
+	/// The new value, formatted as a string. It is coerced to the type of the
+	/// column.
+	pub value: String,
+}
+
 /// Parameters for the SetColumnFilters method.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct SetColumnFiltersParams {
@@ -1061,6 +1103,19 @@ pub enum DataExplorerBackendRequest {
 	#[serde(rename = "export_data_selection")]
 	ExportDataSelection(ExportDataSelectionParams),
 
+	/// Set the value of a single cell
+	///
+	/// Set the value of a single cell, coerced to the type of its column, and
+	/// write the modified table back to the variable it was viewed from
+	#[serde(rename = "set_cell_value")]
+	SetCellValue(SetCellValueParams),
+
+	/// Undo the last cell edit
+	///
+	/// Restore the table as it was before the last cell edit
+	#[serde(rename = "undo_cell_edit")]
+	UndoCellEdit,
+
 	/// Set column filters to select subset of table columns
 	///
 	/// Set or clear column filters on table, replacing any previous filters
@@ -1114,6 +1169,12 @@ pub enum DataExplorerBackendReply {
 	/// Exported result
 	ExportDataSelectionReply(ExportedData),
 
+	/// Reply for the set_cell_value method (no result)
+	SetCellValueReply(),
+
+	/// Reply for the undo_cell_edit method (no result)
+	UndoCellEditReply(),
+
 	/// Reply for the set_column_filters method (no result)
 	SetColumnFiltersReply(),
 
@@ -19,6 +19,8 @@ pub mod event;
 pub mod help_comm;
 #[rustfmt::skip]
 pub mod plot_comm;
+#[rustfmt::skip]
+pub mod plot_history_comm;
 pub mod server_comm;
 #[rustfmt::skip]
 pub mod ui_comm;
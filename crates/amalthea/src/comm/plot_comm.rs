@@ -21,7 +21,62 @@ pub struct PlotResult {
 	pub mime_type: String
 }
 
-/// Possible values for Format in Render
+/// A plot recorded in the plot history
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PlotRecord {
+	/// The ID of the plot
+	pub id: String,
+
+	/// The code that created the plot
+	pub code: String,
+
+	/// The execution count of the request that created the plot
+	pub execution_count: i64,
+
+	/// The time the plot was created, in milliseconds since the epoch
+	pub created: i64,
+
+	/// The location of the code that created the plot, if it was sourced from
+	/// a file
+	pub source: Option<PlotSource>,
+
+	/// The graphics device the plot was drawn on
+	pub device: Option<PlotDevice>
+}
+
+/// The location of the code that created a plot in a source file
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PlotSource {
+	/// The path of the source file
+	pub file: String,
+
+	/// The first line of the code, 1-based
+	pub first_line: i64,
+
+	/// The last line of the code, 1-based
+	pub last_line: i64,
+
+	/// The code that created the plot
+	pub code: String
+}
+
+/// The parameters of the graphics device a plot was drawn on
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PlotDevice {
+	/// The name of the device
+	pub name: String,
+
+	/// The width of the device, in inches
+	pub width: f64,
+
+	/// The height of the device, in inches
+	pub height: f64,
+
+	/// The resolution of the device, in dots per inch
+	pub res: Option<f64>
+}
+
+/// Possible values for RenderFormat
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, strum_macros::Display)]
 pub enum RenderFormat {
 	#[serde(rename = "png")]
@@ -57,6 +112,54 @@ pub struct RenderParams {
 	pub format: RenderFormat,
 }
 
+/// Parameters for the Export method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportParams {
+	/// The path of the file to write the plot to
+	pub path: String,
+
+	/// The requested plot height, in pixels
+	pub height: i64,
+
+	/// The requested plot width, in pixels
+	pub width: i64,
+
+	/// The pixel ratio of the display device
+	pub pixel_ratio: f64,
+
+	/// The requested plot format
+	pub format: RenderFormat,
+}
+
+/// Parameters for the Locate method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LocateParams {
+	/// The horizontal position of the click, in pixels from the left of the
+	/// plot
+	pub x: f64,
+
+	/// The vertical position of the click, in pixels from the top of the plot
+	pub y: f64,
+
+	/// The width of the clicked plot, in pixels
+	pub width: i64,
+
+	/// The height of the clicked plot, in pixels
+	pub height: i64,
+}
+
+/// Parameters for the Locator method.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LocatorParams {
+	/// The width at which the plot should be rendered while locating, in
+	/// pixels
+	pub width: i64,
+
+	/// The height at which the plot should be rendered while locating, in
+	/// pixels
+	pub height: i64,
+}
+
 /**
  * Backend RPC request types for the plot comm
  */
@@ -70,6 +173,40 @@ pub enum PlotBackendRequest {
 	#[serde(rename = "render")]
 	Render(RenderParams),
 
+	/// Get the record of a plot
+	///
+	/// Returns the code that created the plot, where it was sourced from and
+	/// the device it was drawn on.
+	#[serde(rename = "get_record")]
+	GetRecord,
+
+	/// Delete a plot
+	///
+	/// Removes the plot from the plot history and deletes its snapshot. The
+	/// plot comm is closed once the request has been replied to.
+	#[serde(rename = "delete_plot")]
+	DeletePlot,
+
+	/// Export a plot
+	///
+	/// Renders the plot at a given size and format, and writes it to a file.
+	#[serde(rename = "export")]
+	Export(ExportParams),
+
+	/// Locate a point on a plot
+	///
+	/// Sends the position of a click on the plot while `locator()` or
+	/// `identify()` is waiting for one.
+	#[serde(rename = "locate")]
+	Locate(LocateParams),
+
+	/// Cancel locating points
+	///
+	/// Stops waiting for clicks on the plot, as when the user presses Escape
+	/// in a graphics device.
+	#[serde(rename = "cancel_locate")]
+	CancelLocate,
+
 }
 
 /**
@@ -81,6 +218,21 @@ pub enum PlotBackendReply {
 	/// A rendered plot
 	RenderReply(PlotResult),
 
+	/// The record of the plot
+	GetRecordReply(PlotRecord),
+
+	/// Reply for the delete_plot method (no result)
+	DeletePlotReply(),
+
+	/// Reply for the export method (no result)
+	ExportReply(),
+
+	/// Reply for the locate method (no result)
+	LocateReply(),
+
+	/// Reply for the cancel_locate method (no result)
+	CancelLocateReply(),
+
 }
 
 /**
@@ -111,5 +263,13 @@ pub enum PlotFrontendEvent {
 	#[serde(rename = "show")]
 	Show,
 
+	/// Start waiting for clicks on the plot
+	#[serde(rename = "locator")]
+	Locator(LocatorParams),
+
+	/// Stop waiting for clicks on the plot
+	#[serde(rename = "locator_done")]
+	LocatorDone,
+
 }
 
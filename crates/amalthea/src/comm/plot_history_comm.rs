@@ -0,0 +1,73 @@
+// @generated
+
+/*---------------------------------------------------------------------------------------------
+ *  Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+ *--------------------------------------------------------------------------------------------*/
+
+//
+// AUTO-GENERATED from plot_history.json; do not edit.
+//
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::plot_comm::PlotRecord;
+
+/**
+ * Backend RPC request types for the plot_history comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "params")]
+pub enum PlotHistoryBackendRequest {
+	/// List the recorded plots
+	///
+	/// Returns the plots of the plot history, oldest first.
+	#[serde(rename = "list_plots")]
+	ListPlots,
+
+	/// Clear the plot history
+	///
+	/// Deletes all the recorded plots and closes their comms.
+	#[serde(rename = "clear_plots")]
+	ClearPlots,
+
+}
+
+/**
+ * Backend RPC Reply types for the plot_history comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "result")]
+pub enum PlotHistoryBackendReply {
+	/// The recorded plots
+	ListPlotsReply(Vec<PlotRecord>),
+
+	/// Reply for the clear_plots method (no result)
+	ClearPlotsReply(),
+
+}
+
+/**
+ * Frontend RPC request types for the plot_history comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "params")]
+pub enum PlotHistoryFrontendRequest {
+}
+
+/**
+ * Frontend RPC Reply types for the plot_history comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "result")]
+pub enum PlotHistoryFrontendReply {
+}
+
+/**
+ * Frontend events for the plot_history comm
+ */
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "method", content = "params")]
+pub enum PlotHistoryFrontendEvent {
+}
+
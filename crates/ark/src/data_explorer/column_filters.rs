@@ -0,0 +1,218 @@
+//
+// column_filters.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use amalthea::comm::data_explorer_comm::ColumnDisplayType;
+use amalthea::comm::data_explorer_comm::ColumnFilter;
+use amalthea::comm::data_explorer_comm::ColumnFilterParams;
+use amalthea::comm::data_explorer_comm::ColumnFilterType;
+use amalthea::comm::data_explorer_comm::ColumnSchema;
+use amalthea::comm::data_explorer_comm::FilterTextSearch;
+use amalthea::comm::data_explorer_comm::TextSearchType;
+use anyhow::anyhow;
+use regex::Regex;
+use regex::RegexBuilder;
+
+/// Returns the columns that match all of the column filters, in their
+/// original order. If no filters are supplied, all columns match.
+///
+/// - `columns`: The schemas of the columns to filter.
+/// - `filters`: The column filters to apply.
+pub fn filter_columns<'a>(
+    columns: &'a [ColumnSchema],
+    filters: &[ColumnFilter],
+) -> anyhow::Result<Vec<&'a ColumnSchema>> {
+    // Build the matchers upfront so that regexes are only compiled once
+    let matchers = filters
+        .iter()
+        .map(ColumnMatcher::new)
+        .collect::<anyhow::Result<Vec<ColumnMatcher>>>()?;
+
+    Ok(columns
+        .iter()
+        .filter(|column| matchers.iter().all(|matcher| matcher.matches(column)))
+        .collect())
+}
+
+enum ColumnMatcher {
+    /// Matches the column name against a pattern
+    Name(Regex),
+
+    /// Matches columns with one of the display types
+    DisplayTypes(Vec<ColumnDisplayType>),
+}
+
+impl ColumnMatcher {
+    fn new(filter: &ColumnFilter) -> anyhow::Result<Self> {
+        match (&filter.filter_type, &filter.params) {
+            (ColumnFilterType::TextSearch, ColumnFilterParams::TextSearch(params)) => {
+                Ok(Self::Name(text_search_regex(params)?))
+            },
+            (ColumnFilterType::MatchDataTypes, ColumnFilterParams::MatchDataTypes(params)) => {
+                Ok(Self::DisplayTypes(params.display_types.clone()))
+            },
+            (filter_type, _) => Err(anyhow!(
+                "Invalid parameters for column filter of type '{filter_type}'"
+            )),
+        }
+    }
+
+    fn matches(&self, column: &ColumnSchema) -> bool {
+        match self {
+            Self::Name(pattern) => pattern.is_match(&column.column_name),
+            Self::DisplayTypes(types) => types.contains(&column.type_display),
+        }
+    }
+}
+
+fn text_search_regex(params: &FilterTextSearch) -> anyhow::Result<Regex> {
+    let pattern = match params.search_type {
+        TextSearchType::Contains => regex::escape(&params.term),
+        TextSearchType::StartsWith => format!("^{}", regex::escape(&params.term)),
+        TextSearchType::EndsWith => format!("{}$", regex::escape(&params.term)),
+        TextSearchType::RegexMatch => params.term.clone(),
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!params.case_sensitive)
+        .build()
+        .map_err(|err| anyhow!("Invalid search pattern '{}': {err}", params.term))
+}
+
+#[cfg(test)]
+mod tests {
+    use amalthea::comm::data_explorer_comm::FilterMatchDataTypes;
+
+    use super::*;
+
+    fn column(name: &str, index: i64, type_display: ColumnDisplayType) -> ColumnSchema {
+        ColumnSchema {
+            column_name: name.to_string(),
+            column_index: index,
+            type_name: String::from("unknown"),
+            type_display,
+            description: None,
+            children: None,
+            precision: None,
+            scale: None,
+            timezone: None,
+            type_size: None,
+        }
+    }
+
+    fn columns() -> Vec<ColumnSchema> {
+        vec![
+            column("gene_id", 0, ColumnDisplayType::String),
+            column("Gene_Expr_1", 1, ColumnDisplayType::Number),
+            column("gene_expr_2", 2, ColumnDisplayType::Number),
+            column("is_control", 3, ColumnDisplayType::Boolean),
+        ]
+    }
+
+    fn text_search(search_type: TextSearchType, term: &str, case_sensitive: bool) -> ColumnFilter {
+        ColumnFilter {
+            filter_type: ColumnFilterType::TextSearch,
+            params: ColumnFilterParams::TextSearch(FilterTextSearch {
+                search_type,
+                term: term.to_string(),
+                case_sensitive,
+            }),
+        }
+    }
+
+    fn match_types(display_types: Vec<ColumnDisplayType>) -> ColumnFilter {
+        ColumnFilter {
+            filter_type: ColumnFilterType::MatchDataTypes,
+            params: ColumnFilterParams::MatchDataTypes(FilterMatchDataTypes { display_types }),
+        }
+    }
+
+    fn matching_indices(filters: Vec<ColumnFilter>) -> Vec<i64> {
+        let columns = columns();
+        filter_columns(&columns, &filters)
+            .unwrap()
+            .into_iter()
+            .map(|column| column.column_index)
+            .collect()
+    }
+
+    #[test]
+    fn test_no_filters() {
+        assert_eq!(matching_indices(vec![]), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_text_search() {
+        assert_eq!(
+            matching_indices(vec![text_search(TextSearchType::Contains, "expr", false)]),
+            vec![1, 2]
+        );
+        assert_eq!(
+            matching_indices(vec![text_search(TextSearchType::Contains, "expr", true)]),
+            vec![2]
+        );
+        assert_eq!(
+            matching_indices(vec![text_search(TextSearchType::StartsWith, "gene", false)]),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            matching_indices(vec![text_search(TextSearchType::EndsWith, "_1", false)]),
+            vec![1]
+        );
+        assert_eq!(
+            matching_indices(vec![text_search(
+                TextSearchType::RegexMatch,
+                "_[0-9]$",
+                false
+            )]),
+            vec![1, 2]
+        );
+
+        // Regex characters are escaped for non-regex searches
+        assert_eq!(
+            matching_indices(vec![text_search(TextSearchType::Contains, ".", false)]),
+            Vec::<i64>::new()
+        );
+    }
+
+    #[test]
+    fn test_match_data_types() {
+        assert_eq!(
+            matching_indices(vec![match_types(vec![
+                ColumnDisplayType::String,
+                ColumnDisplayType::Boolean
+            ])]),
+            vec![0, 3]
+        );
+    }
+
+    #[test]
+    fn test_combined_filters() {
+        assert_eq!(
+            matching_indices(vec![
+                text_search(TextSearchType::StartsWith, "gene", false),
+                match_types(vec![ColumnDisplayType::Number]),
+            ]),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn test_invalid_filters() {
+        let columns = columns();
+
+        let filters = vec![text_search(TextSearchType::RegexMatch, "(", false)];
+        assert!(filter_columns(&columns, &filters).is_err());
+
+        let filters = vec![ColumnFilter {
+            filter_type: ColumnFilterType::TextSearch,
+            params: ColumnFilterParams::MatchDataTypes(FilterMatchDataTypes {
+                display_types: vec![],
+            }),
+        }];
+        assert!(filter_columns(&columns, &filters).is_err());
+    }
+}
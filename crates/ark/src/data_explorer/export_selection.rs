@@ -13,10 +13,17 @@ use amalthea::comm::data_explorer_comm::ExportFormat;
 use amalthea::comm::data_explorer_comm::Selection;
 use amalthea::comm::data_explorer_comm::TableSelection;
 use amalthea::comm::data_explorer_comm::TableSelectionKind;
+use anyhow::bail;
+use base64::engine::general_purpose;
+use base64::Engine;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
 use libr::SEXP;
+use libr::*;
+use serde_json::Map;
+use serde_json::Number;
+use serde_json::Value;
 
 use crate::data_explorer::utils::tbl_subset_with_view_indices;
 use crate::modules::ARK_ENVS;
@@ -27,19 +34,26 @@ use crate::modules::ARK_ENVS;
 // - data: The data frame full data frame to export
 // - view_indices: The order of rows, and maybe filtered rows from the data frame to be selected.
 //   Must be applied before the selection rules if selection affects rows.
+// - column_view_indices: The columns that remain after applying column filters, if any.
+//   Must be applied before the selection rules if selection affects columns.
 // - selection: The selected region of the data frame
-// - format: The format to export the data frame to (csv, tsv and html are currently supported).
+// - format: The format to export the data frame to. Parquet files are returned base64-encoded.
 pub fn export_selection(
     data: SEXP,
     view_indices: &Option<Vec<i32>>,
+    column_view_indices: &Option<Vec<i64>>,
     selection: TableSelection,
     format: ExportFormat,
 ) -> anyhow::Result<String> {
-    let region = get_selection(data, view_indices, selection.clone())?;
+    let region = get_selection(data, view_indices, column_view_indices, selection.clone())?;
     let format_string = match format {
         ExportFormat::Csv => "csv",
         ExportFormat::Tsv => "tsv",
         ExportFormat::Html => "html",
+        ExportFormat::Markdown => "markdown",
+        ExportFormat::Parquet => return export_parquet(region),
+        ExportFormat::JsonRecords => return export_json(region, JsonLayout::Records),
+        ExportFormat::JsonColumns => return export_json(region, JsonLayout::Columns),
     };
     let include_header = match selection.kind {
         TableSelectionKind::SingleCell => false,
@@ -57,9 +71,94 @@ pub fn export_selection(
         .try_into()?)
 }
 
+enum JsonLayout {
+    /// An array with an object per row, e.g. `[{"a": 1, "b": "x"}]`
+    Records,
+
+    /// An object with an array per column, e.g. `{"a": [1], "b": ["x"]}`
+    Columns,
+}
+
+// Parquet is a binary format, so the written file is sent base64-encoded
+fn export_parquet(region: RObject) -> anyhow::Result<String> {
+    let bytes: Vec<u8> = RFunction::from("export_selection")
+        .param("x", region)
+        .param("format", "parquet")
+        .call_in(ARK_ENVS.positron_ns)?
+        .try_into()?;
+    Ok(general_purpose::STANDARD.encode(bytes))
+}
+
+fn export_json(region: RObject, layout: JsonLayout) -> anyhow::Result<String> {
+    // Matrices are converted to a list of named columns on the R side
+    let columns = RFunction::from("export_json_columns")
+        .add(region)
+        .call_in(ARK_ENVS.positron_ns)?;
+
+    let names: Vec<String> = match columns.attr("names") {
+        Some(names) => names.try_into()?,
+        None => vec![],
+    };
+
+    let mut cells = Vec::<Vec<Value>>::with_capacity(names.len());
+    for i in 0..names.len() {
+        cells.push(json_cells(columns.vector_elt(i as isize)?)?);
+    }
+
+    let value = match layout {
+        JsonLayout::Records => {
+            let num_rows = cells.first().map_or(0, |column| column.len());
+            let records = (0..num_rows)
+                .map(|row| {
+                    let record = names
+                        .iter()
+                        .zip(cells.iter())
+                        .map(|(name, column)| (name.clone(), column[row].clone()))
+                        .collect::<Map<String, Value>>();
+                    Value::Object(record)
+                })
+                .collect();
+            Value::Array(records)
+        },
+        JsonLayout::Columns => Value::Object(
+            names
+                .into_iter()
+                .zip(cells.into_iter())
+                .map(|(name, column)| (name, Value::Array(column)))
+                .collect(),
+        ),
+    };
+
+    Ok(serde_json::to_string(&value)?)
+}
+
+// Converts an atomic column to JSON values, with missing values as `null`
+fn json_cells(column: RObject) -> anyhow::Result<Vec<Value>> {
+    let n = column.length();
+    let mut cells = Vec::<Value>::with_capacity(n as usize);
+
+    for i in 0..n {
+        let cell = match column.kind() {
+            LGLSXP => column.get_bool(i)?.map(Value::Bool),
+            INTSXP => column.get_i32(i)?.map(Value::from),
+            // Non finite numbers don't have a JSON representation
+            REALSXP => column
+                .get_f64(i)?
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+            STRSXP => column.get_string(i)?.map(Value::String),
+            kind => bail!("Can't export columns of type {kind} to JSON"),
+        };
+        cells.push(cell.unwrap_or(Value::Null));
+    }
+
+    Ok(cells)
+}
+
 fn get_selection(
     data: SEXP,
     view_indices: &Option<Vec<i32>>,
+    column_view_indices: &Option<Vec<i64>>,
     selection: TableSelection,
 ) -> anyhow::Result<RObject> {
     let (i, j) = match selection.kind {
@@ -106,7 +205,7 @@ fn get_selection(
         },
     };
 
-    tbl_subset_with_view_indices(data, view_indices, i, j)
+    tbl_subset_with_view_indices(data, view_indices, column_view_indices, i, j)
 }
 
 #[cfg(test)]
@@ -130,7 +229,7 @@ mod tests {
         selection: TableSelection,
         format: ExportFormat,
     ) -> String {
-        export_selection(data.sexp, &None, selection, format).unwrap()
+        export_selection(data.sexp, &None, &None, selection, format).unwrap()
     }
 
     fn export_selection_helper_with_view_indices(
@@ -138,7 +237,14 @@ mod tests {
         view_indices: Vec<i32>,
         selection: TableSelection,
     ) -> String {
-        export_selection(data.sexp, &Some(view_indices), selection, ExportFormat::Csv).unwrap()
+        export_selection(
+            data.sexp,
+            &Some(view_indices),
+            &None,
+            selection,
+            ExportFormat::Csv,
+        )
+        .unwrap()
     }
 
     fn small_test_data() -> RObject {
@@ -417,4 +523,201 @@ mod tests {
             );
         })
     }
+
+    #[test]
+    fn test_markdown_format() {
+        r_test(|| {
+            let data = small_test_data();
+
+            let row_range_selection = |i1, i2| TableSelection {
+                kind: TableSelectionKind::RowRange,
+                selection: Selection::IndexRange(DataSelectionRange {
+                    first_index: i1,
+                    last_index: i2,
+                }),
+            };
+
+            // Numeric columns are right aligned and NA's are empty cells
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data.clone(),
+                    row_range_selection(0, 2),
+                    ExportFormat::Markdown
+                ),
+                "| a | b | c |\n| ---: | ---: | --- |\n| 1 | 4 | a |\n| 2 | 5 | b |\n| 3 |  | c |"
+                    .to_string()
+            );
+
+            // Single cells don't have a header
+            let single_cell_selection = TableSelection {
+                kind: TableSelectionKind::SingleCell,
+                selection: Selection::SingleCell(DataSelectionSingleCell {
+                    row_index: 0,
+                    column_index: 2,
+                }),
+            };
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data.clone(),
+                    single_cell_selection,
+                    ExportFormat::Markdown
+                ),
+                "| a |".to_string()
+            );
+
+            // Pipes are escaped
+            let data = r_parse_eval0(r#"data.frame(x = "a|b")"#, R_ENVS.global).unwrap();
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data,
+                    row_range_selection(0, 0),
+                    ExportFormat::Markdown
+                ),
+                "| x |\n| --- |\n| a\\|b |".to_string()
+            );
+        })
+    }
+
+    #[test]
+    fn test_json_formats() {
+        r_test(|| {
+            let data = small_test_data();
+
+            let row_range_selection = |i1, i2| TableSelection {
+                kind: TableSelectionKind::RowRange,
+                selection: Selection::IndexRange(DataSelectionRange {
+                    first_index: i1,
+                    last_index: i2,
+                }),
+            };
+
+            // NA's are exported as nulls
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data.clone(),
+                    row_range_selection(1, 2),
+                    ExportFormat::JsonRecords
+                ),
+                r#"[{"a":2,"b":5.0,"c":"b"},{"a":3,"b":null,"c":"c"}]"#.to_string()
+            );
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data.clone(),
+                    row_range_selection(1, 2),
+                    ExportFormat::JsonColumns
+                ),
+                r#"{"a":[2,3],"b":[5.0,null],"c":["b","c"]}"#.to_string()
+            );
+
+            // Factors and dates are exported as strings
+            let data = r_parse_eval0(
+                r#"data.frame(f = factor("x"), d = as.Date("2024-01-02"), l = NA)"#,
+                R_ENVS.global,
+            )
+            .unwrap();
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data,
+                    row_range_selection(0, 0),
+                    ExportFormat::JsonRecords
+                ),
+                r#"[{"f":"x","d":"2024-01-02","l":null}]"#.to_string()
+            );
+        })
+    }
+
+    #[test]
+    fn test_matrix_formats() {
+        r_test(|| {
+            let data = r_parse_eval0(
+                "matrix(c(1, 2, NA, 4), nrow = 2, dimnames = list(NULL, c('a', 'b')))",
+                R_ENVS.global,
+            )
+            .unwrap();
+
+            let row_range_selection = TableSelection {
+                kind: TableSelectionKind::RowRange,
+                selection: Selection::IndexRange(DataSelectionRange {
+                    first_index: 0,
+                    last_index: 1,
+                }),
+            };
+
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data.clone(),
+                    row_range_selection.clone(),
+                    ExportFormat::Markdown
+                ),
+                "| a | b |\n| ---: | ---: |\n| 1 |  |\n| 2 | 4 |".to_string()
+            );
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data.clone(),
+                    row_range_selection.clone(),
+                    ExportFormat::JsonRecords
+                ),
+                r#"[{"a":1.0,"b":null},{"a":2.0,"b":4.0}]"#.to_string()
+            );
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data.clone(),
+                    row_range_selection.clone(),
+                    ExportFormat::JsonColumns
+                ),
+                r#"{"a":[1.0,2.0],"b":[null,4.0]}"#.to_string()
+            );
+
+            // Unnamed columns are named like `write.table()` names them
+            let data = r_parse_eval0("matrix(1:2, nrow = 1)", R_ENVS.global).unwrap();
+            let row_range_selection = TableSelection {
+                kind: TableSelectionKind::RowRange,
+                selection: Selection::IndexRange(DataSelectionRange {
+                    first_index: 0,
+                    last_index: 0,
+                }),
+            };
+            assert_eq!(
+                export_selection_helper_with_format(
+                    data,
+                    row_range_selection,
+                    ExportFormat::JsonRecords
+                ),
+                r#"[{"V1":1,"V2":2}]"#.to_string()
+            );
+        })
+    }
+
+    #[test]
+    fn test_parquet_format() {
+        r_test(|| {
+            let has_arrow: bool =
+                r_parse_eval0(r#".ps.is_installed("arrow")"#, ARK_ENVS.positron_ns)
+                    .unwrap()
+                    .try_into()
+                    .unwrap();
+            if !has_arrow {
+                return;
+            }
+
+            let row_range_selection = TableSelection {
+                kind: TableSelectionKind::RowRange,
+                selection: Selection::IndexRange(DataSelectionRange {
+                    first_index: 0,
+                    last_index: 2,
+                }),
+            };
+
+            // The file is sent base64-encoded, not as a path to a temporary
+            // file
+            let data = export_selection_helper_with_format(
+                small_test_data(),
+                row_range_selection,
+                ExportFormat::Parquet,
+            );
+            let bytes = general_purpose::STANDARD.decode(data).unwrap();
+            assert_eq!(&bytes[..4], b"PAR1");
+            assert_eq!(&bytes[bytes.len() - 4..], b"PAR1");
+        })
+    }
 }
@@ -16,6 +16,7 @@ use harp::object::RObject;
 use harp::r_null;
 use harp::utils::r_classes;
 use harp::utils::r_format;
+use harp::utils::r_is_data_frame;
 use harp::utils::r_is_null;
 use harp::utils::r_typeof;
 use harp::vector::CharacterVector;
@@ -84,6 +85,12 @@ fn unknown_format(x: SEXP) -> Vec<FormattedValue> {
 
 // Format a column of data for display in the data explorer.
 fn format_values(x: SEXP, format_options: &FormatOptions) -> anyhow::Result<Vec<FormattedValue>> {
+    // Nested data frame columns (e.g. created with `tidyr::pack()`) are
+    // formatted row-wise, before their class dispatches to `format()`
+    if r_is_data_frame(x) {
+        return format_data_frame(x, format_options);
+    }
+
     if let Some(_) = r_classes(x) {
         return Ok(format_object(x));
     }
@@ -179,6 +186,48 @@ fn format_list(x: SEXP) -> Vec<FormattedValue> {
     output
 }
 
+// Summarises each row of a data frame as `{name: value, ...}`
+fn format_data_frame(
+    x: SEXP,
+    format_options: &FormatOptions,
+) -> anyhow::Result<Vec<FormattedValue>> {
+    let num_rows = harp::df_dim(x).num_rows as usize;
+    let names = harp::ColumnNames::new(unsafe { Rf_getAttrib(x, R_NamesSymbol) });
+
+    let mut fields = Vec::<(String, Vec<FormattedValue>)>::new();
+    for i in 0..r_length(x) {
+        let name = names
+            .get_unchecked(i)
+            .unwrap_or(format!("[[{}]]", i + 1));
+
+        let column = harp::list_get(x, i);
+        let mut values = format_values(column, format_options).unwrap_or(unknown_format(column));
+
+        // Matrix columns don't have one value per row
+        if values.len() != num_rows {
+            values = vec![FormattedValue::Unkown; num_rows];
+        }
+
+        fields.push((name, values));
+    }
+
+    let output = (0..num_rows)
+        .map(|row| {
+            let row = fields
+                .iter()
+                .map(|(name, values)| {
+                    let value: String = values[row].clone().into();
+                    format!("{name}: {value}")
+                })
+                .collect::<Vec<String>>()
+                .join(", ");
+            FormattedValue::Value(format!("{{{row}}}"))
+        })
+        .collect();
+
+    Ok(output)
+}
+
 fn format_list_elt(x: SEXP) -> String {
     // We don't use `r_classes` because we want to see, eg 'numeric' for
     // numeric vectors, not an empty value.
@@ -628,6 +677,22 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_data_frame_formatting() {
+        r_test(|| {
+            let data = r_parse_eval0(
+                "data.frame(x = c(1, NA), y = I(data.frame(a = c('u', 'v'))))",
+                R_ENVS.global,
+            )
+            .unwrap();
+            let formatted = format_column(data.sexp, &default_options());
+            assert_eq!(formatted, vec![
+                ColumnValue::FormattedValue("{x: 1.00, y: {a: u}}".to_string()),
+                ColumnValue::FormattedValue("{x: NA, y: {a: v}}".to_string())
+            ]);
+        })
+    }
+
     #[test]
     fn test_integer_formatting() {
         r_test(|| {
@@ -0,0 +1,131 @@
+//
+// frequency_table.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use std::collections::HashMap;
+
+use amalthea::comm::data_explorer_comm::ColumnDisplayType;
+use amalthea::comm::data_explorer_comm::ColumnFrequencyTable;
+use amalthea::comm::data_explorer_comm::ColumnFrequencyTableParams;
+use amalthea::comm::data_explorer_comm::FormatOptions;
+use anyhow::bail;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use libr::SEXP;
+
+use crate::data_explorer::format::format_string;
+use crate::data_explorer::utils::get_profile_field;
+use crate::modules::ARK_ENVS;
+
+/// Computes the most frequent values of a character, factor or logical
+/// column, along with the number of remaining values. Missing values are not
+/// counted.
+///
+/// For factors, levels that don't appear in the column are reported with a
+/// zero count once all the observed levels have been listed.
+pub fn profile_frequency_table(
+    column: SEXP,
+    display_type: ColumnDisplayType,
+    params: &ColumnFrequencyTableParams,
+    format_options: &FormatOptions,
+) -> anyhow::Result<ColumnFrequencyTable> {
+    match display_type {
+        ColumnDisplayType::String | ColumnDisplayType::Boolean => {},
+        _ => bail!("Frequency tables are not supported for columns of type '{display_type}'"),
+    }
+
+    let result: HashMap<String, RObject> = RFunction::from("profile_frequency_table")
+        .add(column)
+        .param("limit", RObject::try_from(params.limit)?)
+        .call_in(ARK_ENVS.positron_ns)?
+        .try_into()?;
+
+    let values = format_string(
+        get_profile_field(&result, "values", "profile_frequency_table")?.sexp,
+        format_options,
+    );
+    let counts: Vec<i32> =
+        get_profile_field(&result, "counts", "profile_frequency_table")?.try_into()?;
+    let other_count: i32 =
+        get_profile_field(&result, "other_count", "profile_frequency_table")?.try_into()?;
+
+    Ok(ColumnFrequencyTable {
+        values,
+        counts: counts.into_iter().map(|count| count as i64).collect(),
+        other_count: Some(other_count as i64),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+
+    use super::*;
+    use crate::test::r_test;
+
+    fn default_options() -> FormatOptions {
+        FormatOptions {
+            large_num_digits: 2,
+            small_num_digits: 4,
+            max_integral_digits: 7,
+            thousands_sep: Some(",".to_string()),
+            max_value_length: 100,
+        }
+    }
+
+    fn frequency_table(
+        code: &str,
+        display_type: ColumnDisplayType,
+        limit: i64,
+    ) -> ColumnFrequencyTable {
+        let column = r_parse_eval0(code, R_ENVS.global).unwrap();
+        let params = ColumnFrequencyTableParams { limit };
+        profile_frequency_table(column.sexp, display_type, &params, &default_options()).unwrap()
+    }
+
+    #[test]
+    fn test_string_frequency_table() {
+        r_test(|| {
+            let table = frequency_table(
+                "c('b', 'a', 'c', 'a', 'b', 'a', 'd', NA, NA, NA)",
+                ColumnDisplayType::String,
+                2,
+            );
+            assert_eq!(table.values, vec!["a", "b"]);
+            assert_eq!(table.counts, vec![3, 2]);
+            // NA's are not counted
+            assert_eq!(table.other_count, Some(2));
+        })
+    }
+
+    #[test]
+    fn test_factor_frequency_table() {
+        r_test(|| {
+            let table = frequency_table(
+                "factor(c('b', 'b', 'a', NA), levels = c('a', 'b', 'c'))",
+                ColumnDisplayType::String,
+                10,
+            );
+            // Unused levels are reported with a zero count
+            assert_eq!(table.values, vec!["b", "a", "c"]);
+            assert_eq!(table.counts, vec![2, 1, 0]);
+            assert_eq!(table.other_count, Some(0));
+        })
+    }
+
+    #[test]
+    fn test_boolean_frequency_table() {
+        r_test(|| {
+            let table =
+                frequency_table("c(TRUE, FALSE, FALSE, NA)", ColumnDisplayType::Boolean, 10);
+            assert_eq!(table.values, vec!["FALSE", "TRUE"]);
+            assert_eq!(table.counts, vec![2, 1]);
+            assert_eq!(table.other_count, Some(0));
+        })
+    }
+}
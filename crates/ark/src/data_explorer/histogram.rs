@@ -0,0 +1,179 @@
+//
+// histogram.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use std::collections::HashMap;
+
+use amalthea::comm::data_explorer_comm::ColumnDisplayType;
+use amalthea::comm::data_explorer_comm::ColumnHistogram;
+use amalthea::comm::data_explorer_comm::ColumnHistogramParams;
+use amalthea::comm::data_explorer_comm::ColumnQuantileValue;
+use amalthea::comm::data_explorer_comm::FormatOptions;
+use anyhow::bail;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use libr::SEXP;
+
+use crate::data_explorer::format::format_string;
+use crate::data_explorer::utils::get_profile_field;
+use crate::modules::ARK_ENVS;
+
+/// Computes a histogram for a numeric, date or datetime column.
+///
+/// The column is expected to be already filtered, i.e. only the rows that
+/// are currently visible in the data explorer should be passed in.
+pub fn profile_histogram(
+    column: SEXP,
+    display_type: ColumnDisplayType,
+    params: &ColumnHistogramParams,
+    format_options: &FormatOptions,
+) -> anyhow::Result<ColumnHistogram> {
+    match display_type {
+        ColumnDisplayType::Number | ColumnDisplayType::Date | ColumnDisplayType::Datetime => {},
+        _ => bail!("Histograms are not supported for columns of type '{display_type}'"),
+    }
+
+    let quantiles = params.quantiles.clone().unwrap_or(vec![]);
+
+    let result: HashMap<String, RObject> = RFunction::from("profile_histogram")
+        .add(column)
+        .param("num_bins", RObject::try_from(params.num_bins)?)
+        .param("quantiles", &quantiles)
+        .call_in(ARK_ENVS.positron_ns)?
+        .try_into()?;
+
+    // Edges and quantiles keep the class of the column on the R side so they
+    // are formatted the same way as the column values.
+    let bin_edges = format_string(
+        get_profile_field(&result, "bin_edges", "profile_histogram")?.sexp,
+        format_options,
+    );
+    let bin_counts: Vec<i32> =
+        get_profile_field(&result, "bin_counts", "profile_histogram")?.try_into()?;
+    let quantile_values = format_string(
+        get_profile_field(&result, "quantiles", "profile_histogram")?.sexp,
+        format_options,
+    );
+
+    let quantiles = quantiles
+        .into_iter()
+        .zip(quantile_values)
+        .map(|(q, value)| ColumnQuantileValue {
+            q,
+            value,
+            exact: true,
+        })
+        .collect();
+
+    Ok(ColumnHistogram {
+        bin_edges,
+        bin_counts: bin_counts.into_iter().map(|count| count as i64).collect(),
+        quantiles,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+
+    use super::*;
+    use crate::test::r_test;
+
+    fn default_options() -> FormatOptions {
+        FormatOptions {
+            large_num_digits: 2,
+            small_num_digits: 4,
+            max_integral_digits: 7,
+            thousands_sep: Some(",".to_string()),
+            max_value_length: 100,
+        }
+    }
+
+    fn histogram(code: &str, display_type: ColumnDisplayType, num_bins: i64) -> ColumnHistogram {
+        let column = r_parse_eval0(code, R_ENVS.global).unwrap();
+        let params = ColumnHistogramParams {
+            num_bins,
+            quantiles: Some(vec![0.5]),
+        };
+        profile_histogram(column.sexp, display_type, &params, &default_options()).unwrap()
+    }
+
+    #[test]
+    fn test_numeric_histogram() {
+        r_test(|| {
+            let hist = histogram(
+                "c(1, 2, 2, 3, 3, 3, 4, 4, 4, 4, NA)",
+                ColumnDisplayType::Number,
+                3,
+            );
+            assert_eq!(hist.bin_edges, vec!["1.00", "2.00", "3.00", "4.00"]);
+            // The last bin is closed on the right
+            assert_eq!(hist.bin_counts, vec![1, 2, 7]);
+            assert_eq!(hist.quantiles, vec![ColumnQuantileValue {
+                q: 0.5,
+                value: "3.00".to_string(),
+                exact: true,
+            }]);
+        })
+    }
+
+    #[test]
+    fn test_numeric_histogram_constant() {
+        r_test(|| {
+            let hist = histogram("c(5L, 5L, 5L)", ColumnDisplayType::Number, 10);
+            assert_eq!(hist.bin_edges, vec!["5.00", "5.00"]);
+            assert_eq!(hist.bin_counts, vec![3]);
+        })
+    }
+
+    #[test]
+    fn test_numeric_histogram_all_nas() {
+        r_test(|| {
+            let hist = histogram("c(NA_real_, NA_real_)", ColumnDisplayType::Number, 10);
+            assert!(hist.bin_edges.is_empty());
+            assert!(hist.bin_counts.is_empty());
+            assert!(hist.quantiles.is_empty());
+        })
+    }
+
+    #[test]
+    fn test_date_histogram() {
+        r_test(|| {
+            let hist = histogram(
+                "as.Date(c('2021-01-01', '2021-01-02', '2021-01-03', NA))",
+                ColumnDisplayType::Date,
+                2,
+            );
+            assert_eq!(hist.bin_edges, vec![
+                "2021-01-01",
+                "2021-01-02",
+                "2021-01-03"
+            ]);
+            assert_eq!(hist.bin_counts, vec![1, 2]);
+            assert_eq!(hist.quantiles[0].value, "2021-01-02".to_string());
+        })
+    }
+
+    #[test]
+    fn test_unsupported_histogram() {
+        r_test(|| {
+            let column = r_parse_eval0("c('a', 'b')", R_ENVS.global).unwrap();
+            let params = ColumnHistogramParams {
+                num_bins: 10,
+                quantiles: None,
+            };
+            assert!(profile_histogram(
+                column.sexp,
+                ColumnDisplayType::String,
+                &params,
+                &default_options()
+            )
+            .is_err());
+        })
+    }
+}
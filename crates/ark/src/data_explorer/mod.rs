@@ -5,8 +5,12 @@
 //
 //
 
+pub mod column_filters;
 pub mod export_selection;
 pub mod format;
+pub mod frequency_table;
+pub mod histogram;
 pub mod r_data_explorer;
 pub mod summary_stats;
+pub mod table_backend;
 pub mod utils;
@@ -13,6 +13,11 @@ use amalthea::comm::data_explorer_comm::ArraySelection;
 use amalthea::comm::data_explorer_comm::BackendState;
 use amalthea::comm::data_explorer_comm::ColumnDisplayType;
 use amalthea::comm::data_explorer_comm::ColumnFilter;
+use amalthea::comm::data_explorer_comm::ColumnFilterType;
+use amalthea::comm::data_explorer_comm::ColumnFilterTypeSupportStatus;
+use amalthea::comm::data_explorer_comm::ColumnFrequencyTable;
+use amalthea::comm::data_explorer_comm::ColumnHistogram;
+use amalthea::comm::data_explorer_comm::ColumnProfileParams;
 use amalthea::comm::data_explorer_comm::ColumnProfileRequest;
 use amalthea::comm::data_explorer_comm::ColumnProfileResult;
 use amalthea::comm::data_explorer_comm::ColumnProfileType;
@@ -41,7 +46,12 @@ use amalthea::comm::data_explorer_comm::RowFilterParams;
 use amalthea::comm::data_explorer_comm::RowFilterType;
 use amalthea::comm::data_explorer_comm::RowFilterTypeSupportStatus;
 use amalthea::comm::data_explorer_comm::SearchSchemaFeatures;
+use amalthea::comm::data_explorer_comm::SearchSchemaParams;
+use amalthea::comm::data_explorer_comm::SearchSchemaResult;
+use amalthea::comm::data_explorer_comm::SetCellValueFeatures;
+use amalthea::comm::data_explorer_comm::SetCellValueParams;
 use amalthea::comm::data_explorer_comm::SetColumnFiltersFeatures;
+use amalthea::comm::data_explorer_comm::SetColumnFiltersParams;
 use amalthea::comm::data_explorer_comm::SetRowFiltersFeatures;
 use amalthea::comm::data_explorer_comm::SetRowFiltersParams;
 use amalthea::comm::data_explorer_comm::SetSortColumnsFeatures;
@@ -63,6 +73,7 @@ use crossbeam::channel::Sender;
 use crossbeam::select;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
+use harp::object::r_length;
 use harp::object::RObject;
 use harp::r_symbol;
 use harp::tbl_get_column;
@@ -82,10 +93,15 @@ use stdext::spawn;
 use stdext::unwrap;
 use uuid::Uuid;
 
+use crate::data_explorer::column_filters::filter_columns;
 use crate::data_explorer::export_selection;
 use crate::data_explorer::format;
 use crate::data_explorer::format::format_string;
+use crate::data_explorer::frequency_table::profile_frequency_table;
+use crate::data_explorer::histogram::profile_histogram;
 use crate::data_explorer::summary_stats::summary_stats;
+use crate::data_explorer::table_backend::table_backend;
+use crate::data_explorer::table_backend::TableBackend;
 use crate::data_explorer::utils::tbl_subset_with_view_indices;
 use crate::interface::RMain;
 use crate::lsp::events::EVENTS;
@@ -108,8 +124,50 @@ struct DataObjectShape {
     pub columns: Vec<ColumnSchema>,
     pub num_rows: i32,
     pub kind: TableKind,
+    /// The sort keys the rows are already ordered by, e.g. the keys of a
+    /// data.table. These are shown as the default sort.
+    pub sort_keys: Vec<ColumnSortKey>,
+    /// The columns of nested data frames, described as the children of their
+    /// data frame column.
+    pub sub_columns: SubColumns,
 }
 
+/// The columns of nested data frames (e.g. created with `tidyr::pack()`).
+/// They are indexed after the columns of the table, in order, so that their
+/// values can be requested like the ones of any other column.
+struct SubColumns {
+    /// The index of the first sub-column, i.e. the number of columns
+    first_index: i64,
+    /// For each sub-column, the positions of the columns leading to it,
+    /// starting with its top-level column
+    paths: Vec<Vec<i64>>,
+}
+
+impl SubColumns {
+    fn new(num_columns: i64) -> Self {
+        Self {
+            first_index: num_columns,
+            paths: vec![],
+        }
+    }
+
+    /// Registers a sub-column and returns its index
+    fn push(&mut self, path: Vec<i64>) -> i64 {
+        self.paths.push(path);
+        self.first_index + self.paths.len() as i64 - 1
+    }
+
+    /// The path to the sub-column at `column_index`, if it is one
+    fn path(&self, column_index: i64) -> Option<&Vec<i64>> {
+        let position = usize::try_from(column_index - self.first_index).ok()?;
+        self.paths.get(position)
+    }
+}
+
+/// The number of cell edits that can be undone. Each edit keeps a copy of the
+/// table, so this bounds the memory held by the undo stack.
+const MAX_CELL_EDITS: usize = 20;
+
 /// The R backend for Positron's Data Explorer.
 pub struct RDataExplorer {
     /// The human-readable title of the data viewer.
@@ -118,6 +176,16 @@ pub struct RDataExplorer {
     /// The data object that the data viewer is currently viewing.
     table: RThreadSafe<RObject>,
 
+    /// The query engine backing the data object, for tables that are not in
+    /// R's memory (e.g. database tables). Sorting, filtering and paging are
+    /// then pushed down to the backend instead of computing row indices.
+    backend: Option<Box<dyn TableBackend>>,
+
+    /// For tables with a backend, the number of rows that pass the row
+    /// filters, if any filters are applied. In-memory tables use
+    /// `filtered_indices` instead.
+    backend_num_rows: Option<i64>,
+
     /// An optional binding to the environment containing the data object.
     /// This can be omitted for cases wherein the data object isn't in an
     /// environment (e.g. a temporary or unnamed object)
@@ -150,6 +218,20 @@ pub struct RDataExplorer {
     /// data viewer.
     view_indices: Option<Vec<i32>>,
 
+    /// When column filters are applied, the (0-based) indices of the columns
+    /// that pass the filters, in ascending order. This is the set of columns
+    /// that are displayed in the data viewer.
+    column_view_indices: Option<Vec<i64>>,
+
+    /// The tables as they were before each cell edit, most recent last, so
+    /// that the edits can be undone. Only the last `MAX_CELL_EDITS` edits are
+    /// kept. Cleared when the binding is modified outside of the data viewer.
+    cell_edits: Vec<RThreadSafe<RObject>>,
+
+    /// Whether the arrow package, which writes Parquet files, is installed.
+    /// Checked once when the data viewer starts.
+    has_arrow: bool,
+
     /// The communication socket for the data viewer.
     comm: CommSocket,
 
@@ -183,22 +265,33 @@ impl RDataExplorer {
 
         spawn!(format!("ark-data-viewer-{}-{}", title, id), move || {
             // Get the initial set of column schemas for the data object
-            let shape = r_task(|| Self::r_get_shape(&data));
+            let backend = r_task(|| table_backend(data.get().sexp));
+            let shape = r_task(|| Self::r_get_shape(&data, &backend));
+            let has_arrow = r_task(Self::r_has_arrow);
             match shape {
                 // shape the columns; start the data viewer
                 Ok(shape) => {
+                    // The rows are already ordered by the default sort keys,
+                    // so there are no sorted indices to compute
+                    let sort_keys = shape.sort_keys.clone();
+
                     // Create the initial state for the data viewer
                     let viewer = Self {
                         title,
                         table: data,
+                        backend,
+                        backend_num_rows: None,
                         binding,
                         shape,
                         sorted_indices: None,
                         filtered_indices: None,
                         view_indices: None,
-                        sort_keys: vec![],
+                        column_view_indices: None,
+                        sort_keys,
                         row_filters: vec![],
                         col_filters: vec![],
+                        cell_edits: vec![],
+                        has_arrow,
                         comm,
                         comm_manager_tx,
                     };
@@ -339,19 +432,33 @@ impl RDataExplorer {
         });
 
         // No change to the value, so we're done
-        if new.is_none() {
+        let Some(new) = new else {
             return Ok(true);
-        }
+        };
+
+        // The value was modified outside of the data viewer, so our cell
+        // edits can no longer be undone
+        self.cell_edits.clear();
 
         // Update the value
-        self.table = new.unwrap();
+        self.table = new;
+        self.table_update()
+    }
+
+    /// Update the caches after the value of the table has changed, and notify
+    /// the frontend.
+    ///
+    /// Returns true if the update was processed; false if the new value can't
+    /// be viewed and the data viewer should be closed.
+    fn table_update(&mut self) -> anyhow::Result<bool> {
+        self.backend = r_task(|| table_backend(self.table.get().sexp));
 
         // Now we need to check to see if the schema has changed or just a data
         // value. Regenerate the schema.
         //
         // Consider: there may be a cheaper way to test the schema for changes
         // than regenerating it, but it'd be a lot more complicated.
-        let new_shape = match r_task(|| Self::r_get_shape(&self.table)) {
+        let new_shape = match r_task(|| Self::r_get_shape(&self.table, &self.backend)) {
             Ok(shape) => shape,
             Err(_) => {
                 // The most likely cause of this error is that the object is no
@@ -377,8 +484,12 @@ impl RDataExplorer {
             self.filtered_indices = None;
             self.view_indices = None;
 
-            // Clear active sort keys
-            self.sort_keys.clear();
+            // Reset active sort keys to the default ones, which the rows are
+            // already ordered by
+            self.sort_keys = self.shape.sort_keys.clone();
+
+            // Reapply the column filters to the new schema
+            self.column_view_indices = self.column_filters_compute(&self.col_filters)?;
 
             // Recompute and apply filters and sorts.
             let (indices, _) = self.row_filters_compute()?;
@@ -389,7 +500,8 @@ impl RDataExplorer {
         } else {
             // Columns didn't change, but the data has. If there are sort
             // keys, we need to sort the rows again to reflect the new data.
-            if self.sort_keys.len() > 0 {
+            // Tables with a backend are sorted by the backend on demand.
+            if self.sort_keys.len() > 0 && self.backend.is_none() {
                 self.sorted_indices = Some(r_task(|| self.r_sort_rows())?);
             }
 
@@ -459,9 +571,11 @@ impl RDataExplorer {
                 self.sort_keys = keys.clone();
 
                 // If there are no sort keys, clear the precomputed sorted
-                // indices; otherwise, sort the rows and save the result
+                // indices; otherwise, sort the rows and save the result.
+                // Tables with a backend are sorted by the backend on demand.
                 self.sorted_indices = match keys.len() {
                     0 => None,
+                    _ if self.backend.is_some() => None,
                     _ => Some(r_task(|| self.r_sort_rows())?),
                 };
 
@@ -471,11 +585,20 @@ impl RDataExplorer {
                 Ok(DataExplorerBackendReply::SetSortColumnsReply())
             },
             DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams { filters }) => {
-                // Save the new row filters
-                self.row_filters = filters;
+                // Save the new row filters, keeping the previous ones in case
+                // they can't be computed
+                let previous_filters = std::mem::replace(&mut self.row_filters, filters);
+                let previous_num_rows = self.backend_num_rows;
 
                 // Compute the filtered indices
-                let (indices, had_errors) = self.row_filters_compute()?;
+                let (indices, had_errors) = match self.row_filters_compute() {
+                    Ok(result) => result,
+                    Err(err) => {
+                        self.row_filters = previous_filters;
+                        self.backend_num_rows = previous_num_rows;
+                        return Err(err);
+                    },
+                };
                 self.filtered_indices = indices;
 
                 // Apply sorts to the filtered indices to create view indices
@@ -483,10 +606,7 @@ impl RDataExplorer {
 
                 Ok(DataExplorerBackendReply::SetRowFiltersReply({
                     FilterResult {
-                        selected_num_rows: match self.filtered_indices {
-                            Some(ref indices) => indices.len() as i64,
-                            None => self.shape.num_rows as i64,
-                        },
+                        selected_num_rows: self.num_filtered_rows(),
                         had_errors,
                     }
                 }))
@@ -502,11 +622,18 @@ impl RDataExplorer {
                 Ok(DataExplorerBackendReply::GetColumnProfilesReply(profiles))
             },
             DataExplorerBackendRequest::GetState => r_task(|| self.r_get_state()),
-            DataExplorerBackendRequest::SearchSchema(_) => {
-                return Err(anyhow!("Data Explorer: Not yet supported"));
-            },
-            DataExplorerBackendRequest::SetColumnFilters(_) => {
-                return Err(anyhow!("Data Explorer: Not yet supported"));
+            DataExplorerBackendRequest::SearchSchema(SearchSchemaParams {
+                filters,
+                start_index,
+                max_results,
+            }) => self.search_schema(filters, start_index, max_results),
+            DataExplorerBackendRequest::SetColumnFilters(SetColumnFiltersParams { filters }) => {
+                // Compute the visible columns first so that invalid filters
+                // are rejected without altering the current state
+                self.column_view_indices = self.column_filters_compute(&filters)?;
+                self.col_filters = filters;
+
+                Ok(DataExplorerBackendReply::SetColumnFiltersReply())
             },
             DataExplorerBackendRequest::GetRowLabels(req) => {
                 let row_labels =
@@ -526,17 +653,110 @@ impl RDataExplorer {
                     format,
                 },
             )),
+            DataExplorerBackendRequest::SetCellValue(SetCellValueParams {
+                row_index,
+                column_index,
+                value,
+            }) => {
+                self.set_cell_value(row_index, column_index, value)?;
+                Ok(DataExplorerBackendReply::SetCellValueReply())
+            },
+            DataExplorerBackendRequest::UndoCellEdit => {
+                self.undo_cell_edit()?;
+                Ok(DataExplorerBackendReply::UndoCellEditReply())
+            },
         }
     }
+
+    /// Set the value of a cell, coerced to the type of its column, and assign
+    /// the modified table back to the binding. The previous table is kept so
+    /// that the edit can be undone.
+    ///
+    /// - `row_index`: The row of the cell in the current view.
+    /// - `column_index`: The column of the cell in the current view.
+    /// - `value`: The new value, formatted as a string.
+    fn set_cell_value(
+        &mut self,
+        row_index: i64,
+        column_index: i64,
+        value: String,
+    ) -> anyhow::Result<()> {
+        let Some(binding) = &self.binding else {
+            bail!("Can't edit a table that isn't bound to a variable");
+        };
+        if self.backend.is_some() {
+            bail!("Can't edit a table that isn't in memory");
+        }
+
+        if row_index < 0 || row_index >= self.num_filtered_rows() {
+            bail!("Row index out of range {row_index}");
+        }
+        if column_index < 0 || column_index as usize >= self.num_view_columns() {
+            bail!("Column index out of range {column_index}");
+        }
+
+        // Map the indices from the view to the (1-based) table indices
+        let row = match self.view_indices {
+            Some(ref indices) => indices[row_index as usize],
+            None => row_index as i32 + 1,
+        };
+        let column = match self.column_view_indices {
+            Some(ref indices) => indices[column_index as usize] as i32 + 1,
+            None => column_index as i32 + 1,
+        };
+
+        let table = r_task(|| -> anyhow::Result<RThreadSafe<RObject>> {
+            let table = RFunction::from("set_cell_value")
+                .add(self.table.get().sexp)
+                .add(row)
+                .add(column)
+                .add(value)
+                .call_in(ARK_ENVS.positron_ns)?;
+            r_assign_binding(binding, &table)?;
+            Ok(RThreadSafe::new(table))
+        })?;
+
+        let previous = std::mem::replace(&mut self.table, table);
+        self.cell_edits.push(previous);
+        if self.cell_edits.len() > MAX_CELL_EDITS {
+            self.cell_edits.remove(0);
+        }
+
+        self.table_update()?;
+        Ok(())
+    }
+
+    /// Restore the table as it was before the last cell edit.
+    fn undo_cell_edit(&mut self) -> anyhow::Result<()> {
+        let Some(binding) = &self.binding else {
+            bail!("Can't edit a table that isn't bound to a variable");
+        };
+        let Some(previous) = self.cell_edits.last() else {
+            bail!("There are no cell edits to undo");
+        };
+
+        r_task(|| r_assign_binding(binding, previous.get()))?;
+
+        self.table = self.cell_edits.pop().unwrap();
+        self.table_update()?;
+        Ok(())
+    }
 }
 
 // Methods that must be run on the main R thread
 impl RDataExplorer {
-    fn r_get_shape(table: &RThreadSafe<RObject>) -> anyhow::Result<DataObjectShape> {
+    fn r_get_shape(
+        table: &RThreadSafe<RObject>,
+        backend: &Option<Box<dyn TableBackend>>,
+    ) -> anyhow::Result<DataObjectShape> {
         unsafe {
             let table = table.get().clone();
             let object = *table;
 
+            if let Some(backend) = backend {
+                return Self::r_get_backend_shape(object, backend.as_ref());
+            }
+
             let info = table_info_or_bail(object)?;
 
             let harp::TableInfo {
@@ -547,47 +767,167 @@ impl RDataExplorer {
                         num_cols: total_num_columns,
                     },
                 col_names: column_names,
+                sort_keys,
             } = info;
 
             let mut column_schemas = Vec::<ColumnSchema>::new();
+            let mut sub_columns = SubColumns::new(total_num_columns as i64);
             for i in 0..(total_num_columns as isize) {
                 let column_name = match column_names.get_unchecked(i) {
                     Some(name) => name,
                     None => format!("[, {}]", i + 1),
                 };
 
-                // TODO: handling for nested data frame columns
-
                 let col = match kind {
                     harp::TableKind::Dataframe => VECTOR_ELT(object, i),
                     harp::TableKind::Matrix => object,
                 };
 
-                let type_name = WorkspaceVariableDisplayType::from(col, false).display_type;
-                let type_display = display_type(col);
-
-                column_schemas.push(ColumnSchema {
+                column_schemas.push(Self::r_column_schema(
+                    col,
                     column_name,
-                    column_index: i as i64,
-                    type_name,
-                    type_display,
-                    description: None,
-                    children: None,
-                    precision: None,
-                    scale: None,
-                    timezone: None,
-                    type_size: None,
-                });
+                    i as i64,
+                    &[i as i64],
+                    &mut sub_columns,
+                ));
             }
 
+            // Sort keys that don't match a column are ignored, along with the
+            // ones after them since the rows aren't ordered by those alone
+            let sort_keys = sort_keys
+                .iter()
+                .map_while(|key| {
+                    column_schemas
+                        .iter()
+                        .find(|schema| &schema.column_name == key)
+                        .map(|schema| ColumnSortKey {
+                            column_index: schema.column_index,
+                            ascending: true,
+                        })
+                })
+                .collect();
+
             Ok(DataObjectShape {
                 columns: column_schemas,
                 kind,
                 num_rows,
+                sort_keys,
+                sub_columns,
             })
         }
     }
 
+    // Parquet files are written with the arrow package. Failing to check is
+    // not fatal, Parquet export is then just not offered.
+    fn r_has_arrow() -> bool {
+        let has_arrow: harp::Result<bool> = RFunction::from(".ps.is_installed")
+            .add("arrow")
+            .call_in(ARK_ENVS.positron_ns)
+            .and_then(|value| value.try_into());
+
+        has_arrow.unwrap_or_else(|err| {
+            log::warn!("Can't check whether arrow is installed: {err}");
+            false
+        })
+    }
+
+    /// Computes the shape of a table with a backend, without collecting its
+    /// rows into R.
+    fn r_get_backend_shape(
+        object: SEXP,
+        backend: &dyn TableBackend,
+    ) -> anyhow::Result<DataObjectShape> {
+        let prototype = backend.prototype(object)?;
+        let column_names =
+            harp::ColumnNames::new(unsafe { Rf_getAttrib(*prototype, R_NamesSymbol) });
+
+        // Query engines have flat tables, but the sub-columns are numbered
+        // the same way in case a prototype has data frame columns
+        let mut sub_columns = SubColumns::new(r_length(*prototype) as i64);
+        let columns = (0..r_length(*prototype))
+            .map(|i| {
+                let column_name = match column_names.get_unchecked(i) {
+                    Some(name) => name,
+                    None => format!("[[{}]]", i + 1),
+                };
+                Self::r_column_schema(
+                    harp::list_get(*prototype, i),
+                    column_name,
+                    i as i64,
+                    &[i as i64],
+                    &mut sub_columns,
+                )
+            })
+            .collect();
+
+        // Rows are counted by the backend, but our row indices are 32-bit
+        let num_rows = backend.num_rows(object, &[])?;
+        let num_rows = i32::try_from(num_rows).unwrap_or(i32::MAX);
+
+        Ok(DataObjectShape {
+            columns,
+            num_rows,
+            kind: TableKind::Dataframe,
+            sort_keys: vec![],
+            sub_columns,
+        })
+    }
+
+    /// Computes the schema of a column. The columns of nested data frames
+    /// (e.g. created with `tidyr::pack()`) are described as its children and
+    /// registered in `sub_columns`, which gives them their index.
+    ///
+    /// - `path`: The positions of the columns leading to this column, starting
+    ///   with its top-level column.
+    fn r_column_schema(
+        column: SEXP,
+        column_name: String,
+        column_index: i64,
+        path: &[i64],
+        sub_columns: &mut SubColumns,
+    ) -> ColumnSchema {
+        let type_name = WorkspaceVariableDisplayType::from(column, false).display_type;
+        let type_display = display_type(column);
+
+        let children = match type_display {
+            ColumnDisplayType::Struct => {
+                let names = harp::ColumnNames::new(unsafe { Rf_getAttrib(column, R_NamesSymbol) });
+                let children = (0..r_length(column))
+                    .map(|i| {
+                        let child_name = match names.get_unchecked(i) {
+                            Some(name) => name,
+                            None => format!("[[{}]]", i + 1),
+                        };
+                        let child_path = [path, &[i as i64]].concat();
+                        let child_index = sub_columns.push(child_path.clone());
+                        Self::r_column_schema(
+                            harp::list_get(column, i),
+                            child_name,
+                            child_index,
+                            &child_path,
+                            sub_columns,
+                        )
+                    })
+                    .collect();
+                Some(children)
+            },
+            _ => None,
+        };
+
+        ColumnSchema {
+            column_name,
+            column_index,
+            type_name,
+            type_display,
+            description: None,
+            children,
+            precision: None,
+            scale: None,
+            timezone: None,
+            type_size: None,
+        }
+    }
+
     fn r_get_column_profile(
         &self,
         request: ColumnProfileRequest,
@@ -600,6 +940,12 @@ impl RDataExplorer {
             frequency_table: None,
         };
 
+        // Profiles require the column's values, which we don't collect from
+        // tables with a backend
+        if self.backend.is_some() {
+            return output;
+        }
+
         for profile_req in request.profiles {
             match profile_req.profile_type {
                 ColumnProfileType::NullCount => {
@@ -632,8 +978,45 @@ impl RDataExplorer {
                         Ok(stats) => Some(stats),
                     };
                 },
-                _ => {
-                    // Other types are not supported yet
+                ColumnProfileType::Histogram => {
+                    let histogram = r_task(|| {
+                        self.r_histogram(
+                            request.column_index as i32,
+                            &profile_req.params,
+                            &format_options,
+                        )
+                    });
+                    output.histogram = match histogram {
+                        Err(err) => {
+                            log::error!(
+                                "Error getting histogram for column {}: {}",
+                                request.column_index,
+                                err
+                            );
+                            None
+                        },
+                        Ok(histogram) => Some(histogram),
+                    };
+                },
+                ColumnProfileType::FrequencyTable => {
+                    let frequency_table = r_task(|| {
+                        self.r_frequency_table(
+                            request.column_index as i32,
+                            &profile_req.params,
+                            &format_options,
+                        )
+                    });
+                    output.frequency_table = match frequency_table {
+                        Err(err) => {
+                            log::error!(
+                                "Error getting frequency table for column {}: {}",
+                                request.column_index,
+                                err
+                            );
+                            None
+                        },
+                        Ok(frequency_table) => Some(frequency_table),
+                    };
                 },
             };
         }
@@ -679,6 +1062,58 @@ impl RDataExplorer {
         Ok(summary_stats(filtered_column.sexp, dtype, format_options))
     }
 
+    /// Computes a histogram of a numeric, date or datetime column. As for the
+    /// other profiles, only the filtered rows are taken into account.
+    ///
+    /// - `column_index`: The index of the column to compute the histogram for; 0-based.
+    /// - `params`: The histogram parameters (number of bins and quantiles).
+    fn r_histogram(
+        &self,
+        column_index: i32,
+        params: &Option<ColumnProfileParams>,
+        format_options: &FormatOptions,
+    ) -> anyhow::Result<ColumnHistogram> {
+        let params = match params {
+            Some(ColumnProfileParams::Histogram(params)) => params,
+            _ => bail!("Missing histogram parameters"),
+        };
+
+        let column = tbl_get_column(self.table.get().sexp, column_index, self.shape.kind)?;
+        let dtype = display_type(column.sexp);
+
+        let filtered_column = r_filter_indices(column, &self.filtered_indices)?;
+
+        profile_histogram(filtered_column.sexp, dtype, params, format_options)
+    }
+
+    /// Computes the most frequent values of a character, factor or logical
+    /// column.
+    ///
+    /// The counts are computed on the current view, i.e. with filters and
+    /// sorts applied, so that ties are listed in the order in which they
+    /// first appear in the data explorer.
+    ///
+    /// - `column_index`: The index of the column to tabulate; 0-based.
+    /// - `params`: The frequency table parameters (number of values to return).
+    fn r_frequency_table(
+        &self,
+        column_index: i32,
+        params: &Option<ColumnProfileParams>,
+        format_options: &FormatOptions,
+    ) -> anyhow::Result<ColumnFrequencyTable> {
+        let params = match params {
+            Some(ColumnProfileParams::FrequencyTable(params)) => params,
+            _ => bail!("Missing frequency table parameters"),
+        };
+
+        let column = tbl_get_column(self.table.get().sexp, column_index, self.shape.kind)?;
+        let dtype = display_type(column.sexp);
+
+        let view_column = r_filter_indices(column, &self.view_indices)?;
+
+        profile_frequency_table(view_column.sexp, dtype, params, format_options)
+    }
+
     /// Sort the rows of the data object according to the sort keys in
     /// self.sort_keys.
     ///
@@ -758,15 +1193,24 @@ impl RDataExplorer {
         Ok((row_indices, errors))
     }
 
-    // Compute filtered indices out of the current `row_filters`.
+    // Compute filtered indices out of the current `row_filters`. Filters are
+    // combined from left to right according to their `condition`, e.g. the
+    // filters `a`, `or b`, `and c` select the rows matching `(a | b) & c`.
+    // Invalid filters are skipped.
     //
     // Implicitly updates the `row_filters` with validity status and error messages, if they
     // fail during the computation.
     fn row_filters_compute(&mut self) -> anyhow::Result<(Option<Vec<i32>>, Option<bool>)> {
+        self.backend_num_rows = None;
+
         if self.row_filters.len() == 0 {
             return Ok((None, None));
         }
 
+        if self.backend.is_some() {
+            return self.backend_row_filters_compute();
+        }
+
         let (indices, errors) = r_task(|| self.r_filter_rows())?;
         // this is called for the side-effect of updating the row_filters with validty status and
         // error messages
@@ -775,6 +1219,55 @@ impl RDataExplorer {
         Ok((Some(indices), had_errors))
     }
 
+    // Count the rows of a table with a backend that pass the `row_filters`.
+    //
+    // The backend translates the filters to queries, so unsupported filters
+    // are rejected upfront, and each of the remaining ones is checked on its
+    // own, instead of failing the whole query.
+    fn backend_row_filters_compute(&mut self) -> anyhow::Result<(Option<Vec<i32>>, Option<bool>)> {
+        let errors = self
+            .row_filters
+            .iter()
+            .map(|filter| match Self::is_valid_filter(filter) {
+                Ok(true) => None,
+                Ok(false) => Some(String::from("Unsupported column type for filter")),
+                Err(err) => Some(err.to_string()),
+            })
+            .collect();
+        self.apply_filter_errors(errors)?;
+
+        let backend = self.backend.as_ref().unwrap();
+        let errors = r_task(|| backend.filter_errors(self.table.get().sexp, &self.row_filters))?;
+        let had_errors = self.apply_filter_errors(errors)?;
+
+        let backend = self.backend.as_ref().unwrap();
+        let num_rows = r_task(|| backend.num_rows(self.table.get().sexp, &self.row_filters))?;
+        self.backend_num_rows = Some(num_rows);
+
+        Ok((None, Some(had_errors)))
+    }
+
+    // Compute the indices of the columns that pass the column `filters`.
+    //
+    // Returns `None` when there are no column filters, i.e. all columns are
+    // visible.
+    fn column_filters_compute(
+        &self,
+        filters: &Vec<ColumnFilter>,
+    ) -> anyhow::Result<Option<Vec<i64>>> {
+        if filters.len() == 0 {
+            return Ok(None);
+        }
+
+        let columns = filter_columns(&self.shape.columns, filters)?;
+        Ok(Some(
+            columns
+                .into_iter()
+                .map(|column| column.column_index)
+                .collect(),
+        ))
+    }
+
     // Check if a filter is valid by looking at it's type and the type of the column its applied to.
     // Uses logic similar to python side: https://github.com/posit-dev/positron/blob/aafe313a261fd133b9f4a9f87c92bb10dc9966ad/extensions/positron-python/python_files/positron/positron_ipykernel/data_explorer.py#L743-L744
     fn is_valid_filter(filter: &RowFilter) -> anyhow::Result<bool> {
@@ -813,7 +1306,13 @@ impl RDataExplorer {
             RowFilterType::IsTrue | RowFilterType::IsFalse => {
                 Ok(display_type == &ColumnDisplayType::Boolean)
             },
-            RowFilterType::IsNull | RowFilterType::NotNull | RowFilterType::SetMembership => {
+            RowFilterType::SetMembership => match display_type {
+                ColumnDisplayType::String |
+                ColumnDisplayType::Number |
+                ColumnDisplayType::Boolean => Ok(true),
+                _ => Ok(false),
+            },
+            RowFilterType::IsNull | RowFilterType::NotNull => {
                 // Filters always supported
                 Ok(true)
             },
@@ -885,10 +1384,11 @@ impl RDataExplorer {
 
     /// Get the schema for a vector of columns in the data object.
     ///
-    /// - `column_indices`: The vector of columns in the data object.
+    /// - `column_indices`: The vector of columns in the data object, relative
+    ///   to the columns that pass the column filters.
     fn get_schema(&self, column_indices: Vec<i64>) -> anyhow::Result<DataExplorerBackendReply> {
         // Get the columns length. (Does Rust optimize loop invariants well?)
-        let columns_len = self.shape.columns.len();
+        let columns_len = self.num_view_columns();
 
         // Gather the column schemas to return.
         let mut columns: Vec<ColumnSchema> = Vec::new();
@@ -909,6 +1409,12 @@ impl RDataExplorer {
                 break;
             }
 
+            // Map the column index to the unfiltered schema
+            let column_index = match self.column_view_indices {
+                Some(ref indices) => indices[column_index] as usize,
+                None => column_index,
+            };
+
             // Push the column schema.
             columns.push(self.shape.columns[column_index].clone());
         }
@@ -919,15 +1425,72 @@ impl RDataExplorer {
         }))
     }
 
+    /// Search the full, unfiltered schema for columns matching all of the
+    /// column filters.
+    ///
+    /// - `filters`: The column filters to match columns against.
+    /// - `start_index`: The index of the first match to return, for paging.
+    /// - `max_results`: The maximum number of matches to return.
+    fn search_schema(
+        &self,
+        filters: Vec<ColumnFilter>,
+        start_index: i64,
+        max_results: i64,
+    ) -> anyhow::Result<DataExplorerBackendReply> {
+        let matches = filter_columns(&self.shape.columns, &filters)?;
+        let total_num_matches = matches.len() as i64;
+
+        let columns = matches
+            .into_iter()
+            .skip(cmp::max(start_index, 0) as usize)
+            .take(cmp::max(max_results, 0) as usize)
+            .cloned()
+            .collect();
+
+        Ok(DataExplorerBackendReply::SearchSchemaReply(
+            SearchSchemaResult {
+                matches: TableSchema { columns },
+                total_num_matches,
+            },
+        ))
+    }
+
+    /// The number of rows that pass the row filters.
+    fn num_filtered_rows(&self) -> i64 {
+        match (&self.filtered_indices, self.backend_num_rows) {
+            (Some(indices), _) => indices.len() as i64,
+            (None, Some(num_rows)) => num_rows,
+            (None, None) => self.shape.num_rows as i64,
+        }
+    }
+
+    /// The number of columns that pass the column filters.
+    fn num_view_columns(&self) -> usize {
+        match self.column_view_indices {
+            Some(ref indices) => indices.len(),
+            None => self.shape.columns.len(),
+        }
+    }
+
     fn r_get_state(&self) -> anyhow::Result<DataExplorerBackendReply> {
+        let mut export_formats = vec![
+            ExportFormat::Csv,
+            ExportFormat::Tsv,
+            ExportFormat::Html,
+            ExportFormat::Markdown,
+            ExportFormat::JsonRecords,
+            ExportFormat::JsonColumns,
+        ];
+
+        if self.has_arrow {
+            export_formats.push(ExportFormat::Parquet);
+        }
+
         let state = BackendState {
             display_name: self.title.clone(),
             table_shape: TableShape {
-                num_rows: match self.filtered_indices {
-                    Some(ref indices) => indices.len() as i64,
-                    None => self.shape.num_rows as i64,
-                },
-                num_columns: self.shape.columns.len() as i64,
+                num_rows: self.num_filtered_rows(),
+                num_columns: self.num_view_columns() as i64,
             },
             table_unfiltered_shape: TableShape {
                 num_rows: self.shape.num_rows as i64,
@@ -942,7 +1505,10 @@ impl RDataExplorer {
             },
             supported_features: SupportedFeatures {
                 get_column_profiles: GetColumnProfilesFeatures {
-                    support_status: SupportStatus::Supported,
+                    support_status: match self.backend {
+                        Some(_) => SupportStatus::Unsupported,
+                        None => SupportStatus::Supported,
+                    },
                     supported_types: vec![
                         ColumnProfileTypeSupportStatus {
                             profile_type: ColumnProfileType::NullCount,
@@ -957,17 +1523,26 @@ impl RDataExplorer {
                         },
                         ColumnProfileTypeSupportStatus {
                             profile_type: ColumnProfileType::Histogram,
-                            support_status: SupportStatus::Unsupported,
+                            support_status: SupportStatus::Supported,
                         },
                         ColumnProfileTypeSupportStatus {
                             profile_type: ColumnProfileType::FrequencyTable,
-                            support_status: SupportStatus::Unsupported,
+                            support_status: SupportStatus::Supported,
                         },
                     ],
                 },
                 search_schema: SearchSchemaFeatures {
-                    support_status: SupportStatus::Unsupported,
-                    supported_types: vec![],
+                    support_status: SupportStatus::Supported,
+                    supported_types: vec![
+                        ColumnFilterType::TextSearch,
+                        ColumnFilterType::MatchDataTypes,
+                    ]
+                    .iter()
+                    .map(|column_filter_type| ColumnFilterTypeSupportStatus {
+                        column_filter_type: column_filter_type.clone(),
+                        support_status: SupportStatus::Supported,
+                    })
+                    .collect(),
                 },
                 set_row_filters: SetRowFiltersFeatures {
                     support_status: SupportStatus::Supported,
@@ -982,6 +1557,7 @@ impl RDataExplorer {
                         RowFilterType::NotEmpty,
                         RowFilterType::NotNull,
                         RowFilterType::Search,
+                        RowFilterType::SetMembership,
                     ]
                     .iter()
                     .map(|row_filter_type| RowFilterTypeSupportStatus {
@@ -989,26 +1565,39 @@ impl RDataExplorer {
                         support_status: SupportStatus::Supported,
                     })
                     .collect(),
-                    // Temporarily disabled for https://github.com/posit-dev/positron/issues/3489
-                    // on 6/11/2024. This will be enabled again when the UI has been reworked to
-                    // support grouping.
-                    supports_conditions: SupportStatus::Unsupported,
+                    supports_conditions: SupportStatus::Supported,
                 },
                 set_column_filters: SetColumnFiltersFeatures {
-                    support_status: SupportStatus::Unsupported,
-                    supported_types: vec![],
+                    support_status: SupportStatus::Supported,
+                    supported_types: vec![
+                        ColumnFilterType::TextSearch,
+                        ColumnFilterType::MatchDataTypes,
+                    ]
+                    .iter()
+                    .map(|column_filter_type| ColumnFilterTypeSupportStatus {
+                        column_filter_type: column_filter_type.clone(),
+                        support_status: SupportStatus::Supported,
+                    })
+                    .collect(),
                 },
                 set_sort_columns: SetSortColumnsFeatures {
                     support_status: SupportStatus::Supported,
                 },
                 export_data_selection: ExportDataSelectionFeatures {
-                    support_status: SupportStatus::Supported,
-                    supported_formats: vec![
-                        ExportFormat::Csv,
-                        ExportFormat::Tsv,
-                        ExportFormat::Html,
-                    ],
+                    support_status: match self.backend {
+                        Some(_) => SupportStatus::Unsupported,
+                        None => SupportStatus::Supported,
+                    },
+                    supported_formats: export_formats,
                 },
+                set_cell_value: Some(SetCellValueFeatures {
+                    // Edits are written back to the binding, and can't be
+                    // pushed down to a query engine
+                    support_status: match (&self.binding, &self.backend) {
+                        (Some(_), None) => SupportStatus::Supported,
+                        _ => SupportStatus::Unsupported,
+                    },
+                }),
             },
         };
         Ok(DataExplorerBackendReply::GetStateReply(state))
@@ -1019,17 +1608,35 @@ impl RDataExplorer {
         columns: Vec<ColumnSelection>,
         format_options: FormatOptions,
     ) -> anyhow::Result<DataExplorerBackendReply> {
+        if let Some(backend) = &self.backend {
+            return self.r_get_backend_data_values(backend.as_ref(), columns, format_options);
+        }
+
         let mut column_data: Vec<Vec<ColumnValue>> = Vec::with_capacity(columns.len());
         for selection in columns {
+            // Sub-columns are selected through their top-level column, which
+            // is indexed relative to the unfiltered schema
+            let sub_column = self.shape.sub_columns.path(selection.column_index);
+            let (column_index, column_view_indices) = match sub_column {
+                Some(path) => (path[0], &None),
+                None => (selection.column_index, &self.column_view_indices),
+            };
+
             let tbl = tbl_subset_with_view_indices(
                 self.table.get().sexp,
                 &self.view_indices,
+                column_view_indices,
                 Some(self.get_row_selection_indices(selection.spec)),
-                Some(vec![selection.column_index]),
+                Some(vec![column_index]),
             )?;
 
             // The column will be always at index 0 because we already selected a single column above.
-            let column = tbl_get_column(tbl.sexp, 0, self.shape.kind)?;
+            let mut column = tbl_get_column(tbl.sexp, 0, self.shape.kind)?;
+            if let Some(path) = sub_column {
+                for position in &path[1..] {
+                    column = tbl_get_column(column.sexp, *position as i32, TableKind::Dataframe)?;
+                }
+            }
             let formatted = format::format_column(column.sexp, &format_options);
             column_data.push(formatted.clone());
         }
@@ -1041,6 +1648,57 @@ impl RDataExplorer {
         Ok(DataExplorerBackendReply::GetDataValuesReply(response))
     }
 
+    /// Gets data values from a table with a backend. The rows spanned by all
+    /// the selections are fetched with a single query.
+    fn r_get_backend_data_values(
+        &self,
+        backend: &dyn TableBackend,
+        columns: Vec<ColumnSelection>,
+        format_options: FormatOptions,
+    ) -> anyhow::Result<DataExplorerBackendReply> {
+        let rows: Vec<Vec<i64>> = columns
+            .iter()
+            .map(|selection| self.get_row_selection_indices(selection.spec.clone()))
+            .collect();
+
+        let first_row = rows.iter().flatten().min().cloned().unwrap_or(0);
+        let last_row = rows.iter().flatten().max().cloned().unwrap_or(-1);
+
+        // Map the column indices to the unfiltered schema
+        let column_indices: Vec<i64> = columns
+            .iter()
+            .map(|selection| match self.column_view_indices {
+                Some(ref indices) => indices[selection.column_index as usize],
+                None => selection.column_index,
+            })
+            .collect();
+
+        let slice = backend.slice(
+            self.table.get().sexp,
+            &self.sort_keys,
+            &self.row_filters,
+            &column_indices,
+            first_row,
+            last_row - first_row + 1,
+        )?;
+
+        let mut column_data: Vec<Vec<ColumnValue>> = Vec::with_capacity(columns.len());
+        for (i, rows) in rows.into_iter().enumerate() {
+            // Rows are relative to the first fetched row, and 1-based
+            let indices = rows
+                .iter()
+                .map(|row| (row - first_row + 1) as i32)
+                .collect();
+            let column = RObject::view(harp::list_get(slice.sexp, i as isize));
+            let column = r_filter_indices(column, &Some(indices))?;
+            column_data.push(format::format_column(column.sexp, &format_options));
+        }
+
+        Ok(DataExplorerBackendReply::GetDataValuesReply(TableData {
+            columns: column_data,
+        }))
+    }
+
     fn r_get_row_labels(
         &self,
         selection: ArraySelection,
@@ -1049,6 +1707,7 @@ impl RDataExplorer {
         let tbl = tbl_subset_with_view_indices(
             self.table.get().sexp,
             &self.view_indices,
+            &self.column_view_indices,
             Some(self.get_row_selection_indices(selection)),
             Some(vec![]), // Use empty vec, because we only need the row names.
         )?;
@@ -1074,10 +1733,7 @@ impl RDataExplorer {
     // Given an ArraySelection, this materializes the indices that will actually be used.
     // Also does some sanity checks to avoid OOB access.
     fn get_row_selection_indices(&self, selection: ArraySelection) -> Vec<i64> {
-        let num_view_rows = match self.view_indices {
-            Some(ref indices) => indices.len() as i32,
-            None => self.shape.num_rows,
-        } as i64;
+        let num_view_rows = self.num_filtered_rows();
 
         // Returns the indices that will be collected
         match selection {
@@ -1099,10 +1755,15 @@ impl RDataExplorer {
         selection: TableSelection,
         format: ExportFormat,
     ) -> anyhow::Result<String> {
+        if self.backend.is_some() {
+            bail!("Exporting is not supported for tables that are not in memory");
+        }
+
         r_task(|| {
             export_selection::export_selection(
                 self.table.get().sexp,
                 &self.view_indices,
+                &self.column_view_indices,
                 selection,
                 format,
             )
@@ -1153,12 +1814,17 @@ fn display_type(x: SEXP) -> ColumnDisplayType {
             return ColumnDisplayType::Datetime;
         }
 
-        // TODO: vctrs's list_of
+        // Nested data frames, e.g. created with `tidyr::pack()`
+        if r_inherits(x, "data.frame") {
+            return ColumnDisplayType::Struct;
+        }
+
+        // List columns, including vctrs's `list_of`
         if r_inherits(x, "list") {
-            return ColumnDisplayType::Unknown;
+            return ColumnDisplayType::Array;
         }
 
-        // Catch-all, including for data frame
+        // Catch-all
         return ColumnDisplayType::Unknown;
     }
 
@@ -1166,11 +1832,22 @@ fn display_type(x: SEXP) -> ColumnDisplayType {
         LGLSXP => return ColumnDisplayType::Boolean,
         INTSXP | REALSXP | CPLXSXP => return ColumnDisplayType::Number,
         STRSXP => return ColumnDisplayType::String,
-        VECSXP => return ColumnDisplayType::Unknown,
+        VECSXP => return ColumnDisplayType::Array,
         _ => return ColumnDisplayType::Unknown,
     }
 }
 
+// Assigns the value to the binding. This fails if the binding is locked,
+// e.g. for datasets exported by packages.
+fn r_assign_binding(binding: &DataObjectEnvInfo, value: &RObject) -> anyhow::Result<()> {
+    RFunction::new("base", "assign")
+        .param("x", binding.name.as_str())
+        .param("value", value.sexp)
+        .param("envir", binding.env.get().sexp)
+        .call_in(ARK_ENVS.positron_ns)?;
+    Ok(())
+}
+
 fn table_info_or_bail(x: SEXP) -> anyhow::Result<TableInfo> {
     harp::table_info(x).ok_or(anyhow!("Unsupported type for data viewer"))
 }
@@ -0,0 +1,167 @@
+//
+// table_backend.rs
+//
+// Copyright (C) 2024 by Posit Software, PBC
+//
+//
+
+use amalthea::comm::data_explorer_comm::ColumnSortKey;
+use amalthea::comm::data_explorer_comm::RowFilter;
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::object::RObject;
+use harp::utils::r_inherits;
+use libr::SEXP;
+use serde::Serialize;
+
+use crate::modules::ARK_ENVS;
+
+/// A table whose data lives in a query engine rather than in R's memory, like
+/// a database table or an Arrow dataset. Sorting, filtering and paging are
+/// pushed down to the engine, so that the table is never collected into R.
+///
+/// All methods must be called on the R thread.
+pub trait TableBackend: Send + Sync {
+    /// Returns a data frame without rows that has the column types of
+    /// `table`, from which the schema is derived.
+    fn prototype(&self, table: SEXP) -> anyhow::Result<RObject> {
+        Ok(RFunction::from("lazy_table_prototype")
+            .add(table)
+            .call_in(ARK_ENVS.positron_ns)?)
+    }
+
+    /// Returns the number of rows of `table` that pass the row filters.
+    /// Invalid filters are skipped.
+    fn num_rows(&self, table: SEXP, row_filters: &[RowFilter]) -> anyhow::Result<i64> {
+        let num_rows: f64 = RFunction::from("lazy_table_num_rows")
+            .add(table)
+            .add(r_list(row_filters)?)
+            .call_in(ARK_ENVS.positron_ns)?
+            .try_into()?;
+        Ok(num_rows as i64)
+    }
+
+    /// Returns the error message of each row filter, or `None` for the ones
+    /// that the engine can compute. Each filter is checked with its own
+    /// query, since an engine may fail to translate some of them (e.g.
+    /// regular expressions for most databases). Invalid filters keep their
+    /// error message.
+    fn filter_errors(
+        &self,
+        table: SEXP,
+        row_filters: &[RowFilter],
+    ) -> anyhow::Result<Vec<Option<String>>> {
+        let errors = RFunction::from("lazy_table_filter_errors")
+            .add(table)
+            .add(r_list(row_filters)?)
+            .call_in(ARK_ENVS.positron_ns)?;
+        Ok(Vec::<Option<String>>::try_from(errors)?)
+    }
+
+    /// Returns a data frame with the `columns` (0-based) of `table`, for
+    /// `limit` rows starting at `offset` once the rows are filtered and
+    /// sorted.
+    fn slice(
+        &self,
+        table: SEXP,
+        sort_keys: &[ColumnSortKey],
+        row_filters: &[RowFilter],
+        columns: &[i64],
+        offset: i64,
+        limit: i64,
+    ) -> anyhow::Result<RObject>;
+}
+
+/// Returns the backend for tables that are backed by a query engine, or
+/// `None` for in-memory tables.
+pub fn table_backend(x: SEXP) -> Option<Box<dyn TableBackend>> {
+    // Includes duckdb tables, which are implemented with dbplyr
+    if r_inherits(x, "tbl_sql") {
+        return Some(Box::new(SqlTableBackend));
+    }
+
+    if r_inherits(x, "Dataset") ||
+        r_inherits(x, "arrow_dplyr_query") ||
+        r_inherits(x, "ArrowTabular")
+    {
+        return Some(Box::new(ArrowTableBackend));
+    }
+
+    None
+}
+
+/// Backend for dbplyr tables, whose queries are translated to SQL.
+struct SqlTableBackend;
+
+impl TableBackend for SqlTableBackend {
+    fn slice(
+        &self,
+        table: SEXP,
+        sort_keys: &[ColumnSortKey],
+        row_filters: &[RowFilter],
+        columns: &[i64],
+        offset: i64,
+        limit: i64,
+    ) -> anyhow::Result<RObject> {
+        table_slice(
+            "sql_table_slice",
+            table,
+            sort_keys,
+            row_filters,
+            columns,
+            offset,
+            limit,
+        )
+    }
+}
+
+/// Backend for Arrow datasets and tables, whose queries are computed by
+/// Acero.
+struct ArrowTableBackend;
+
+impl TableBackend for ArrowTableBackend {
+    fn slice(
+        &self,
+        table: SEXP,
+        sort_keys: &[ColumnSortKey],
+        row_filters: &[RowFilter],
+        columns: &[i64],
+        offset: i64,
+        limit: i64,
+    ) -> anyhow::Result<RObject> {
+        table_slice(
+            "arrow_table_slice",
+            table,
+            sort_keys,
+            row_filters,
+            columns,
+            offset,
+            limit,
+        )
+    }
+}
+
+fn table_slice(
+    function: &str,
+    table: SEXP,
+    sort_keys: &[ColumnSortKey],
+    row_filters: &[RowFilter],
+    columns: &[i64],
+    offset: i64,
+    limit: i64,
+) -> anyhow::Result<RObject> {
+    Ok(RFunction::from(function)
+        .add(table)
+        .add(r_list(sort_keys)?)
+        .add(r_list(row_filters)?)
+        .add(RObject::from(&columns.to_vec()))
+        .add(RObject::try_from(offset)?)
+        .add(RObject::try_from(limit)?)
+        .call_in(ARK_ENVS.positron_ns)?)
+}
+
+// Converts the items to an R list by marshaling them through the JSON layer
+fn r_list<T: Serialize>(items: &[T]) -> anyhow::Result<RObject> {
+    let items = serde_json::to_value(items)?;
+    Ok(RObject::try_from(items)?)
+}
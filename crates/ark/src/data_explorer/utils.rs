@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
 use harp::exec::RFunction;
 use harp::exec::RFunctionExt;
 use harp::object::RObject;
@@ -5,9 +8,18 @@ use libr::SEXP;
 
 use crate::modules::ARK_ENVS;
 
+/// Subsets a table using row and column indices relative to the current view.
+///
+/// - `view_indices`: The 1-based row indices of the view, if rows are sorted
+///   or filtered.
+/// - `column_view_indices`: The 0-based column indices of the view, if
+///   columns are filtered.
+/// - `i`, `j`: The 0-based row and column indices to select, relative to the
+///   view. `None` selects all the rows or columns of the view.
 pub fn tbl_subset_with_view_indices(
     x: SEXP,
     view_indices: &Option<Vec<i32>>,
+    column_view_indices: &Option<Vec<i64>>,
     i: Option<Vec<i64>>,
     j: Option<Vec<i64>>,
 ) -> anyhow::Result<RObject> {
@@ -21,9 +33,20 @@ pub fn tbl_subset_with_view_indices(
             None => None,
         },
     };
-    let j = match j {
-        Some(j) => Some(j.iter().map(|j| j + 1).collect()),
-        None => None,
+    let j = match column_view_indices {
+        Some(column_view_indices) => match j {
+            Some(j) => Some(
+                j.iter()
+                    .filter_map(|j| column_view_indices.get(*j as usize))
+                    .map(|j| j + 1)
+                    .collect(),
+            ),
+            None => Some(column_view_indices.iter().map(|j| j + 1).collect()),
+        },
+        None => match j {
+            Some(j) => Some(j.iter().map(|j| j + 1).collect()),
+            None => None,
+        },
     };
     tbl_subset(x, i, j)
 }
@@ -40,3 +63,18 @@ fn tbl_subset(x: SEXP, i: Option<Vec<i64>>, j: Option<Vec<i64>>) -> anyhow::Resu
 
     Ok(call.call_in(ARK_ENVS.positron_ns)?)
 }
+
+/// Extracts a field from the named list returned by one of the R profiling
+/// functions, e.g. `profile_histogram()`.
+pub fn get_profile_field(
+    result: &HashMap<String, RObject>,
+    name: &str,
+    function: &str,
+) -> anyhow::Result<RObject> {
+    match result.get(name) {
+        Some(value) => Ok(value.clone()),
+        None => Err(anyhow!(
+            "Unexpected output from `{function}()`: missing '{name}'"
+        )),
+    }
+}
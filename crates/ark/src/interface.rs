@@ -1414,6 +1414,14 @@ This is a Positron limitation we plan to fix. In the meantime, you can:
         &self.kernel
     }
 
+    /// Returns the code and execution count of the execute request that is
+    /// currently being evaluated, if any
+    pub(crate) fn get_active_request(&self) -> Option<(&str, u32)> {
+        self.active_request
+            .as_ref()
+            .map(|req| (req.request.code.as_str(), req.exec_count))
+    }
+
     pub(crate) fn set_help_fields(&mut self, help_event_tx: Sender<HelpEvent>, help_port: u16) {
         self.help_event_tx = Some(help_event_tx);
         self.help_port = Some(help_port);
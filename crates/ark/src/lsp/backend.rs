@@ -87,10 +87,23 @@ pub(crate) enum LspRequest {
     GotoDefinition(GotoDefinitionParams),
     GotoImplementation(GotoImplementationParams),
     SelectionRange(SelectionRangeParams),
+    FoldingRange(FoldingRangeParams),
+    SemanticTokensFull(SemanticTokensParams),
+    SemanticTokensFullDelta(SemanticTokensDeltaParams),
+    InlayHint(InlayHintParams),
+    PrepareCallHierarchy(CallHierarchyPrepareParams),
+    IncomingCalls(CallHierarchyIncomingCallsParams),
+    OutgoingCalls(CallHierarchyOutgoingCallsParams),
+    DocumentHighlight(DocumentHighlightParams),
     References(ReferenceParams),
+    PrepareRename(TextDocumentPositionParams),
+    Rename(RenameParams),
+    CodeAction(CodeActionParams),
     StatementRange(StatementRangeParams),
     HelpTopic(HelpTopicParams),
     OnTypeFormatting(DocumentOnTypeFormattingParams),
+    DocumentFormatting(DocumentFormattingParams),
+    DocumentRangeFormatting(DocumentRangeFormattingParams),
     VirtualDocument(VirtualDocumentParams),
 }
 
@@ -108,10 +121,23 @@ pub(crate) enum LspResponse {
     GotoDefinition(Option<GotoDefinitionResponse>),
     GotoImplementation(Option<GotoImplementationResponse>),
     SelectionRange(Option<Vec<SelectionRange>>),
+    FoldingRange(Option<Vec<FoldingRange>>),
+    SemanticTokensFull(Option<SemanticTokensResult>),
+    SemanticTokensFullDelta(Option<SemanticTokensFullDeltaResult>),
+    InlayHint(Option<Vec<InlayHint>>),
+    PrepareCallHierarchy(Option<Vec<CallHierarchyItem>>),
+    IncomingCalls(Option<Vec<CallHierarchyIncomingCall>>),
+    OutgoingCalls(Option<Vec<CallHierarchyOutgoingCall>>),
+    DocumentHighlight(Option<Vec<DocumentHighlight>>),
     References(Option<Vec<Location>>),
+    PrepareRename(Option<PrepareRenameResponse>),
+    Rename(Option<WorkspaceEdit>),
+    CodeAction(Option<CodeActionResponse>),
     StatementRange(Option<StatementRangeResponse>),
     HelpTopic(Option<HelpTopicResponse>),
     OnTypeFormatting(Option<Vec<TextEdit>>),
+    DocumentFormatting(Option<Vec<TextEdit>>),
+    DocumentRangeFormatting(Option<Vec<TextEdit>>),
     VirtualDocument(VirtualDocumentResponse),
 }
 
@@ -283,6 +309,81 @@ impl LanguageServer for Backend {
         )
     }
 
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        cast_response!(
+            self.request(LspRequest::FoldingRange(params)).await,
+            LspResponse::FoldingRange
+        )
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        cast_response!(
+            self.request(LspRequest::SemanticTokensFull(params)).await,
+            LspResponse::SemanticTokensFull
+        )
+    }
+
+    async fn semantic_tokens_full_delta(
+        &self,
+        params: SemanticTokensDeltaParams,
+    ) -> Result<Option<SemanticTokensFullDeltaResult>> {
+        cast_response!(
+            self.request(LspRequest::SemanticTokensFullDelta(params))
+                .await,
+            LspResponse::SemanticTokensFullDelta
+        )
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        cast_response!(
+            self.request(LspRequest::InlayHint(params)).await,
+            LspResponse::InlayHint
+        )
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        cast_response!(
+            self.request(LspRequest::PrepareCallHierarchy(params)).await,
+            LspResponse::PrepareCallHierarchy
+        )
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        cast_response!(
+            self.request(LspRequest::IncomingCalls(params)).await,
+            LspResponse::IncomingCalls
+        )
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        cast_response!(
+            self.request(LspRequest::OutgoingCalls(params)).await,
+            LspResponse::OutgoingCalls
+        )
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        cast_response!(
+            self.request(LspRequest::DocumentHighlight(params)).await,
+            LspResponse::DocumentHighlight
+        )
+    }
+
     async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
         cast_response!(
             self.request(LspRequest::References(params)).await,
@@ -290,6 +391,30 @@ impl LanguageServer for Backend {
         )
     }
 
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        cast_response!(
+            self.request(LspRequest::PrepareRename(params)).await,
+            LspResponse::PrepareRename
+        )
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        cast_response!(
+            self.request(LspRequest::Rename(params)).await,
+            LspResponse::Rename
+        )
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        cast_response!(
+            self.request(LspRequest::CodeAction(params)).await,
+            LspResponse::CodeAction
+        )
+    }
+
     async fn on_type_formatting(
         &self,
         params: DocumentOnTypeFormattingParams,
@@ -299,6 +424,24 @@ impl LanguageServer for Backend {
             LspResponse::OnTypeFormatting
         )
     }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        cast_response!(
+            self.request(LspRequest::DocumentFormatting(params)).await,
+            LspResponse::DocumentFormatting
+        )
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        cast_response!(
+            self.request(LspRequest::DocumentRangeFormatting(params))
+                .await,
+            LspResponse::DocumentRangeFormatting
+        )
+    }
 }
 
 // Custom methods for the backend.
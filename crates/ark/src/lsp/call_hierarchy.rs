@@ -0,0 +1,437 @@
+//
+// call_hierarchy.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::CallHierarchyIncomingCall;
+use tower_lsp::lsp_types::CallHierarchyIncomingCallsParams;
+use tower_lsp::lsp_types::CallHierarchyItem;
+use tower_lsp::lsp_types::CallHierarchyOutgoingCall;
+use tower_lsp::lsp_types::CallHierarchyOutgoingCallsParams;
+use tower_lsp::lsp_types::CallHierarchyPrepareParams;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::SymbolKind;
+use tower_lsp::lsp_types::Url;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp;
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::indexer;
+use crate::lsp::state::with_document;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::url::UrlExt;
+use crate::treesitter::node_text;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Returns the workspace function named by the identifier at `position`,
+/// which may be a call or the definition itself
+pub(crate) fn prepare_call_hierarchy(
+    params: CallHierarchyPrepareParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyItem>>> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let position = params.text_document_position_params.position;
+
+    let document = state.get_document(&uri)?;
+    let contents = &document.contents;
+    let point = convert_position_to_point(contents, position);
+
+    let Some(node) = identifier_at(document, point) else {
+        return Ok(None);
+    };
+
+    // The identifier might be the name of a function defined in this document
+    if let Some(assignment) = node.parent() {
+        if assignment.child_by_field_name("lhs") == Some(node) {
+            if let Some(item) = function_item(&assignment, &uri, document) {
+                return Ok(Some(vec![item]));
+            }
+        }
+    }
+
+    let Some(name) = node_text(&node, contents) else {
+        return Ok(None);
+    };
+
+    Ok(workspace_function_item(&name, state).map(|item| vec![item]))
+}
+
+pub(crate) fn incoming_calls(
+    params: CallHierarchyIncomingCallsParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+    let name = params.item.name;
+    let mut calls = Vec::new();
+
+    for path in state.workspace.r_files() {
+        let Ok(uri) = Url::from_file_path(&path) else {
+            continue;
+        };
+
+        let result = with_document(&path, state, |document| {
+            find_incoming_calls(&name, &uri, document, &mut calls);
+            Ok(())
+        });
+
+        if result.is_err() {
+            lsp::log_warn!("error retrieving document for path {}", path.display());
+        }
+    }
+
+    Ok(Some(calls))
+}
+
+pub(crate) fn outgoing_calls(
+    params: CallHierarchyOutgoingCallsParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+    let item = params.item;
+    let path = item.uri.file_path()?;
+
+    // Function calls in the body, grouped by callee
+    let calls = with_document(path.as_path(), state, |document| {
+        let contents = &document.contents;
+        let root = document.ast.root_node();
+
+        // Top-level calls of a file, or calls in the body of a function
+        let body = if item.kind == SymbolKind::FILE {
+            root
+        } else {
+            let point = convert_position_to_point(contents, item.selection_range.start);
+            let definition = root
+                .descendant_for_point_range(point, point)
+                .and_then(|node| node.parent())
+                .and_then(|node| node.child_by_field_name("rhs"))
+                .filter(|node| node.is_function_definition());
+
+            match definition {
+                Some(definition) => definition,
+                None => return Ok(Vec::new()),
+            }
+        };
+
+        Ok(find_outgoing_calls(&body, contents))
+    })?;
+
+    let mut items: HashMap<String, Option<CallHierarchyItem>> = HashMap::new();
+    let mut outgoing: Vec<CallHierarchyOutgoingCall> = Vec::new();
+
+    for (name, range) in calls {
+        let item = items
+            .entry(name.clone())
+            .or_insert_with(|| workspace_function_item(&name, state));
+
+        // Only functions defined in the workspace are part of the hierarchy
+        let Some(item) = item else {
+            continue;
+        };
+
+        match outgoing.iter_mut().find(|call| call.to.name == name) {
+            Some(call) => call.from_ranges.push(range),
+            None => outgoing.push(CallHierarchyOutgoingCall {
+                to: item.clone(),
+                from_ranges: vec![range],
+            }),
+        }
+    }
+
+    Ok(Some(outgoing))
+}
+
+/// Collects the calls to `name` in `document`, grouped by the function, or
+/// the file for top-level calls, that they are made from
+fn find_incoming_calls(
+    name: &str,
+    uri: &Url,
+    document: &Document,
+    calls: &mut Vec<CallHierarchyIncomingCall>,
+) {
+    let contents = &document.contents;
+
+    let mut cursor = document.ast.walk();
+    cursor.recurse(|node| {
+        let Some((callee, callee_name)) = called_function(&node, contents) else {
+            return true;
+        };
+        if callee_name != name {
+            return true;
+        }
+
+        let caller = caller_item(&node, uri, document);
+        let range = convert_tree_sitter_range_to_lsp_range(contents, callee.range());
+
+        let existing = calls.iter_mut().find(|call| {
+            call.from.uri == caller.uri && call.from.selection_range == caller.selection_range
+        });
+
+        match existing {
+            Some(call) => call.from_ranges.push(range),
+            None => calls.push(CallHierarchyIncomingCall {
+                from: caller,
+                from_ranges: vec![range],
+            }),
+        }
+
+        true
+    });
+}
+
+/// Collects the calls made from `body`, leaving out the bodies of nested
+/// named functions which have their own place in the hierarchy
+fn find_outgoing_calls(body: &Node, contents: &Rope) -> Vec<(String, Range)> {
+    let mut calls = Vec::new();
+
+    let mut cursor = body.walk();
+    cursor.recurse(|node| {
+        if node != *body && node.is_function_definition() && is_named_function(&node) {
+            return false;
+        }
+
+        if let Some((callee, name)) = called_function(&node, contents) {
+            let range = convert_tree_sitter_range_to_lsp_range(contents, callee.range());
+            calls.push((name, range));
+        }
+
+        true
+    });
+
+    calls
+}
+
+/// If `node` is a call to a function referred to by name, returns the
+/// identifier of the function and its name
+fn called_function<'tree>(node: &Node<'tree>, contents: &Rope) -> Option<(Node<'tree>, String)> {
+    if !node.is_call() {
+        return None;
+    }
+
+    let function = node.child_by_field_name("function")?;
+    if !function.is_identifier() {
+        return None;
+    }
+
+    let name = node_text(&function, contents)?;
+    Some((function, name))
+}
+
+/// The innermost named function containing `node`, or the file if `node` is
+/// not inside of one
+fn caller_item(node: &Node, uri: &Url, document: &Document) -> CallHierarchyItem {
+    let mut parent = node.parent();
+
+    while let Some(node) = parent {
+        if node.is_function_definition() {
+            if let Some(item) = node
+                .parent()
+                .and_then(|assignment| function_item(&assignment, uri, document))
+            {
+                return item;
+            }
+        }
+        parent = node.parent();
+    }
+
+    file_item(uri, document)
+}
+
+fn is_named_function(definition: &Node) -> bool {
+    let Some(assignment) = definition.parent() else {
+        return false;
+    };
+
+    let Some((lhs, rhs)) = assignment_sides(&assignment) else {
+        return false;
+    };
+
+    rhs == *definition && lhs.is_identifier_or_string()
+}
+
+fn assignment_sides<'tree>(node: &Node<'tree>) -> Option<(Node<'tree>, Node<'tree>)> {
+    if !matches!(
+        node.node_type(),
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment)
+    ) {
+        return None;
+    }
+
+    let lhs = node.child_by_field_name("lhs")?;
+    let rhs = node.child_by_field_name("rhs")?;
+    Some((lhs, rhs))
+}
+
+/// Creates the item of a function defined by `assignment`, e.g. `f <- function() {}`
+fn function_item(assignment: &Node, uri: &Url, document: &Document) -> Option<CallHierarchyItem> {
+    let (lhs, rhs) = assignment_sides(assignment)?;
+    if !rhs.is_function_definition() || !lhs.is_identifier_or_string() {
+        return None;
+    }
+
+    let contents = &document.contents;
+    let name = node_text(&lhs, contents)?
+        .trim_matches(['"', '\'', '`'])
+        .to_string();
+
+    Some(CallHierarchyItem {
+        name,
+        kind: SymbolKind::FUNCTION,
+        tags: None,
+        detail: None,
+        uri: uri.clone(),
+        range: convert_tree_sitter_range_to_lsp_range(contents, assignment.range()),
+        selection_range: convert_tree_sitter_range_to_lsp_range(contents, lhs.range()),
+        data: None,
+    })
+}
+
+fn file_item(uri: &Url, document: &Document) -> CallHierarchyItem {
+    let name = uri
+        .path_segments()
+        .and_then(|segments| segments.last())
+        .unwrap_or_default()
+        .to_string();
+
+    let root = document.ast.root_node();
+    let range = convert_tree_sitter_range_to_lsp_range(&document.contents, root.range());
+
+    CallHierarchyItem {
+        name,
+        kind: SymbolKind::FILE,
+        tags: None,
+        detail: None,
+        uri: uri.clone(),
+        range,
+        selection_range: Range::new(range.start, range.start),
+        data: None,
+    }
+}
+
+/// Creates the item of a function from the workspace index
+fn workspace_function_item(name: &str, state: &WorldState) -> Option<CallHierarchyItem> {
+    let (path, entry) = indexer::find(name)?;
+    if !matches!(entry.data, indexer::IndexEntryData::Function { .. }) {
+        return None;
+    }
+
+    let path = Path::new(&path);
+    let uri = Url::from_file_path(path).ok()?;
+
+    let item = with_document(path, state, |document| {
+        let point = convert_position_to_point(&document.contents, entry.range.start);
+        let assignment = document
+            .ast
+            .root_node()
+            .descendant_for_point_range(point, point)
+            .and_then(|node| node.parent());
+
+        Ok(assignment.and_then(|assignment| function_item(&assignment, &uri, document)))
+    });
+
+    item.ok().flatten()
+}
+
+fn identifier_at<'tree>(document: &'tree Document, point: Point) -> Option<Node<'tree>> {
+    let root = document.ast.root_node();
+
+    let node = root.descendant_for_point_range(point, point)?;
+    if node.is_identifier() {
+        return Some(node);
+    }
+
+    // The position may be at the end of the identifier, for instance after a
+    // double-click selection
+    if point.column == 0 {
+        return None;
+    }
+    let point = Point::new(point.row, point.column - 1);
+    root.descendant_for_point_range(point, point)
+        .filter(|node| node.is_identifier())
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::SymbolKind;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::call_hierarchy::find_incoming_calls;
+    use crate::lsp::call_hierarchy::find_outgoing_calls;
+    use crate::lsp::documents::Document;
+
+    #[test]
+    fn test_call_hierarchy_incoming() {
+        let text = "f <- function() g(1)\nh <- function() {\n  lapply(x, function(i) g(i))\n  g(2)\n}\ng(3)\n";
+        let document = Document::new(text, None);
+        let uri = Url::parse("file:///analysis.R").unwrap();
+
+        let mut calls = Vec::new();
+        find_incoming_calls("g", &uri, &document, &mut calls);
+
+        let calls: Vec<(String, SymbolKind, Vec<u32>)> = calls
+            .into_iter()
+            .map(|call| {
+                let lines = call
+                    .from_ranges
+                    .iter()
+                    .map(|range| range.start.line)
+                    .collect();
+                (call.from.name, call.from.kind, lines)
+            })
+            .collect();
+
+        assert_eq!(calls, vec![
+            (String::from("f"), SymbolKind::FUNCTION, vec![0]),
+            (String::from("h"), SymbolKind::FUNCTION, vec![2, 3]),
+            (String::from("analysis.R"), SymbolKind::FILE, vec![5]),
+        ]);
+    }
+
+    #[test]
+    fn test_call_hierarchy_incoming_from_string_assignment() {
+        let text = "\"f\" <- function() g(1)\n`h` <- function() g(2)\n";
+        let document = Document::new(text, None);
+        let uri = Url::parse("file:///analysis.R").unwrap();
+
+        let mut calls = Vec::new();
+        find_incoming_calls("g", &uri, &document, &mut calls);
+
+        let names: Vec<String> = calls.into_iter().map(|call| call.from.name).collect();
+        assert_eq!(names, vec![String::from("f"), String::from("h")]);
+    }
+
+    #[test]
+    fn test_call_hierarchy_outgoing() {
+        let text = "f <- function() {\n  g(1)\n  inner <- function() h()\n  k(g(2))\n}\n";
+        let document = Document::new(text, None);
+
+        let definition = document
+            .ast
+            .root_node()
+            .named_child(0)
+            .unwrap()
+            .child_by_field_name("rhs")
+            .unwrap();
+
+        let calls: Vec<(String, u32)> = find_outgoing_calls(&definition, &document.contents)
+            .into_iter()
+            .map(|(name, range)| (name, range.start.line))
+            .collect();
+
+        assert_eq!(calls, vec![
+            (String::from("g"), 1),
+            (String::from("k"), 3),
+            (String::from("g"), 3),
+        ]);
+    }
+}
@@ -0,0 +1,809 @@
+//
+// code_actions.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use once_cell::sync::Lazy;
+use ropey::Rope;
+use tower_lsp::lsp_types::CodeAction;
+use tower_lsp::lsp_types::CodeActionKind;
+use tower_lsp::lsp_types::CodeActionOrCommand;
+use tower_lsp::lsp_types::CodeActionParams;
+use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::Url;
+use tower_lsp::lsp_types::WorkspaceEdit;
+use tree_sitter::Node;
+
+use crate::lsp::diagnostics::DiagnosticKind;
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::indent::new_line_indent;
+use crate::lsp::offset::ArkPoint;
+use crate::lsp::offset::ArkRange;
+use crate::lsp::offset::ArkTextEdit;
+use crate::lsp::offset::IntoLspOffset;
+use crate::lsp::roxygen::function_assignment;
+use crate::lsp::roxygen::function_formals;
+use crate::lsp::scopes::Occurrence;
+use crate::lsp::scopes::OccurrenceKind;
+use crate::lsp::scopes::Scopes;
+use crate::lsp::scopes::ROOT;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::lsp::traits::node::NodeExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::modules::ARK_ENVS;
+use crate::r_task;
+use crate::treesitter::node_text;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// The exports of the installed packages, keyed by package name
+static PACKAGE_EXPORTS: Lazy<Mutex<HashMap<String, HashSet<String>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct Action {
+    title: String,
+    kind: CodeActionKind,
+    edits: Vec<ArkTextEdit>,
+}
+
+pub(crate) fn code_actions(
+    params: CodeActionParams,
+    state: &WorldState,
+) -> anyhow::Result<Vec<CodeActionOrCommand>> {
+    let uri = &params.text_document.uri;
+    let document = state.get_document(uri)?;
+    let contents = &document.contents;
+
+    let mut actions = Vec::new();
+
+    // Quick fixes for the diagnostics at the requested range
+    for diagnostic in params.context.diagnostics.iter() {
+        for action in quick_fixes(document, diagnostic, state) {
+            let mut action = code_action(action, uri, contents);
+            action.diagnostics = Some(vec![diagnostic.clone()]);
+            actions.push(action);
+        }
+    }
+
+    // Refactorings of the selection
+    let range = ArkRange {
+        start: convert_position_to_point(contents, params.range.start),
+        end: convert_position_to_point(contents, params.range.end),
+    };
+    let refactorings = [
+        extract_variable(document, range.clone()),
+        extract_function(document, range.clone()),
+    ];
+    for action in refactorings.into_iter().flatten() {
+        actions.push(code_action(action, uri, contents));
+    }
+
+    // Documentation of the function below the cursor
+    if let Some(action) = roxygen_skeleton(document, range.start) {
+        actions.push(code_action(action, uri, contents));
+    }
+
+    // The client may only want some kinds of actions, like quick fixes
+    if let Some(only) = &params.context.only {
+        actions.retain(|action| {
+            let kind = action.kind.as_ref().map_or("", |kind| kind.as_str());
+            only.iter().any(|only| kind.starts_with(only.as_str()))
+        });
+    }
+
+    Ok(actions
+        .into_iter()
+        .map(CodeActionOrCommand::CodeAction)
+        .collect())
+}
+
+fn code_action(action: Action, uri: &Url, contents: &Rope) -> CodeAction {
+    let edits = action.edits.into_lsp_offset(contents);
+    let changes = HashMap::from([(uri.clone(), edits)]);
+
+    CodeAction {
+        title: action.title,
+        kind: Some(action.kind),
+        edit: Some(WorkspaceEdit::new(changes)),
+        ..Default::default()
+    }
+}
+
+fn quick_fixes(document: &Document, diagnostic: &Diagnostic, state: &WorldState) -> Vec<Action> {
+    let Some(kind) = DiagnosticKind::from_diagnostic(diagnostic) else {
+        return Vec::new();
+    };
+
+    let contents = &document.contents;
+    let start = convert_position_to_point(contents, diagnostic.range.start);
+    let end = convert_position_to_point(contents, diagnostic.range.end);
+
+    let Some(node) = document
+        .ast
+        .root_node()
+        .descendant_for_point_range(start, end)
+    else {
+        return Vec::new();
+    };
+
+    match kind {
+        DiagnosticKind::InvalidNaComparison => invalid_na_comparison_fix(node, contents)
+            .into_iter()
+            .collect(),
+        DiagnosticKind::AssignmentInIfCondition => {
+            assignment_in_if_condition_fix(node).into_iter().collect()
+        },
+        DiagnosticKind::SymbolNotInScope => symbol_not_in_scope_fixes(document, node, state),
+    }
+}
+
+/// Replaces `x == NA` with `is.na(x)`, and likewise for `NaN` and `NULL`
+fn invalid_na_comparison_fix(node: Node, contents: &Rope) -> Option<Action> {
+    let function = match node_text(&node, contents)?.as_str() {
+        "NA" => "is.na",
+        "NaN" => "is.nan",
+        "NULL" => "is.null",
+        _ => return None,
+    };
+
+    let comparison = node.parent()?;
+    if comparison.node_type() != NodeType::BinaryOperator(BinaryOperatorType::Equal) {
+        return None;
+    }
+
+    let lhs = comparison.child_by_field_name("lhs")?;
+    let rhs = comparison.child_by_field_name("rhs")?;
+    let operand = if lhs == node { rhs } else { lhs };
+    let operand = node_text(&operand, contents)?;
+
+    Some(Action {
+        title: format!("Use `{function}()`"),
+        kind: CodeActionKind::QUICKFIX,
+        edits: vec![ArkTextEdit {
+            range: node_range(&comparison),
+            new_text: format!("{function}({operand})"),
+        }],
+    })
+}
+
+/// Replaces `=` with `==` in the condition of an `if` statement
+fn assignment_in_if_condition_fix(node: Node) -> Option<Action> {
+    if node.node_type() != NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment) {
+        return None;
+    }
+    let operator = node.child_by_field_name("operator")?;
+
+    Some(Action {
+        title: String::from("Use `==` to compare values"),
+        kind: CodeActionKind::QUICKFIX,
+        edits: vec![ArkTextEdit {
+            range: node_range(&operator),
+            new_text: String::from("=="),
+        }],
+    })
+}
+
+/// Suggests attaching an installed package that exports the symbol, or
+/// qualifying the symbol with the package name
+fn symbol_not_in_scope_fixes(document: &Document, node: Node, state: &WorldState) -> Vec<Action> {
+    if !node.is_identifier() {
+        return Vec::new();
+    }
+    let Some(name) = node_text(&node, &document.contents) else {
+        return Vec::new();
+    };
+
+    let packages = match r_task(|| packages_exporting(&name, &state.installed_packages)) {
+        Ok(packages) => packages,
+        Err(err) => {
+            log::error!("Can't find the packages exporting `{name}`: {err:?}");
+            return Vec::new();
+        },
+    };
+
+    let mut actions = Vec::new();
+
+    for package in packages {
+        actions.push(Action {
+            title: format!("Add `library({package})`"),
+            kind: CodeActionKind::QUICKFIX,
+            edits: vec![library_edit(document, &package)],
+        });
+        actions.push(Action {
+            title: format!("Use `{package}::{name}`"),
+            kind: CodeActionKind::QUICKFIX,
+            edits: vec![ArkTextEdit {
+                range: node_range(&node),
+                new_text: format!("{package}::{name}"),
+            }],
+        });
+    }
+
+    actions
+}
+
+/// Returns the installed packages that export `name`. Packages are added to
+/// the exports index the first time they are seen, so that their namespace
+/// is only inspected once. Must be called within an `r_task()`.
+fn packages_exporting(name: &str, installed: &[String]) -> anyhow::Result<Vec<String>> {
+    let mut index = PACKAGE_EXPORTS.lock().unwrap();
+
+    for package in installed {
+        if index.contains_key(package) {
+            continue;
+        }
+        let exports = RFunction::from("package_exports")
+            .add(package.as_str())
+            .call_in(ARK_ENVS.positron_ns)?;
+        let exports = Vec::<String>::try_from(exports)?;
+        index.insert(package.clone(), exports.into_iter().collect());
+    }
+
+    let mut packages: Vec<String> = installed
+        .iter()
+        .filter(|package| {
+            index
+                .get(*package)
+                .is_some_and(|exports| exports.contains(name))
+        })
+        .cloned()
+        .collect();
+
+    // A package may be installed in several libraries
+    packages.sort();
+    packages.dedup();
+
+    Ok(packages)
+}
+
+/// Inserts a `library()` call after the ones at the top level of the
+/// document, or before the first expression if there are none
+fn library_edit(document: &Document, package: &str) -> ArkTextEdit {
+    let root = document.ast.root_node();
+    let contents = &document.contents;
+
+    let mut cursor = root.walk();
+    let statements: Vec<Node> = root
+        .children(&mut cursor)
+        .filter(|node| !node.is_comment())
+        .collect();
+
+    let last_library = statements.iter().rev().find(|node| {
+        node.is_call() &&
+            node.child_by_field_name("function")
+                .and_then(|function| node_text(&function, contents))
+                .is_some_and(|function| matches!(function.as_str(), "library" | "require"))
+    });
+
+    if let Some(node) = last_library {
+        let end = node.end_position();
+        return ArkTextEdit {
+            range: ArkRange { start: end, end },
+            new_text: format!("\nlibrary({package})"),
+        };
+    }
+
+    let start = statements.first().map_or(ArkPoint::new(0, 0), |node| {
+        ArkPoint::new(node.start_position().row, 0)
+    });
+
+    ArkTextEdit {
+        range: ArkRange { start, end: start },
+        new_text: format!("library({package})\n"),
+    }
+}
+
+/// Assigns the selected expression to a new variable, defined before the
+/// statement containing the expression
+fn extract_variable(document: &Document, range: ArkRange) -> Option<Action> {
+    let contents = &document.contents;
+    let range = trim_range(contents, range)?;
+
+    let node = document
+        .ast
+        .root_node()
+        .descendant_for_point_range(range.start, range.end)?;
+
+    // The selection must be a whole expression
+    if node.start_position() != range.start || node.end_position() != range.end {
+        return None;
+    }
+    if !node.is_named() || node.is_identifier() || node.is_comment() || !is_expression(&node) {
+        return None;
+    }
+
+    let statement = enclosing_statement(node)?;
+    if statement == node {
+        return None;
+    }
+
+    // The variable is evaluated before the statement, so the expression
+    // can't depend on parameters or loop variables, or be evaluated
+    // conditionally
+    let mut child = node;
+    while child != statement {
+        let parent = child.parent()?;
+        match parent.node_type() {
+            NodeType::FunctionDefinition |
+            NodeType::ForStatement |
+            NodeType::WhileStatement |
+            NodeType::RepeatStatement => return None,
+            NodeType::IfStatement if parent.child_by_field_name("condition") != Some(child) => {
+                return None
+            },
+            _ => {},
+        }
+        child = parent;
+    }
+
+    let name = unique_name(document, "new_variable");
+    let text = node_text(&node, contents)?;
+    let indent = line_indentation(contents, statement.start_position().row);
+    let start = statement.start_position();
+
+    Some(Action {
+        title: String::from("Extract to variable"),
+        kind: CodeActionKind::REFACTOR_EXTRACT,
+        edits: vec![
+            ArkTextEdit {
+                range,
+                new_text: name.clone(),
+            },
+            ArkTextEdit {
+                range: ArkRange { start, end: start },
+                new_text: format!("{name} <- {text}\n{indent}"),
+            },
+        ],
+    })
+}
+
+/// Moves the selected statements to a new function defined at top level.
+/// The variables defined before the selection become parameters, and the
+/// variable defined by the selection that is used afterwards, if any, is
+/// returned.
+fn extract_function(document: &Document, range: ArkRange) -> Option<Action> {
+    let contents = &document.contents;
+    let range = trim_range(contents, range)?;
+
+    let root = document.ast.root_node();
+    let container = root
+        .descendant_for_point_range(range.start, range.end)?
+        .find_parent(|node| node.is_program() || node.is_braced_expression())?;
+
+    // The selection must consist of whole statements
+    let mut cursor = container.walk();
+    let mut selected = Vec::new();
+    for node in container
+        .children(&mut cursor)
+        .filter(|node| node.is_named())
+    {
+        let inside = node.start_position() >= range.start && node.end_position() <= range.end;
+        let outside = node.end_position() <= range.start || node.start_position() >= range.end;
+        if inside {
+            selected.push(node);
+        } else if !outside {
+            return None;
+        }
+    }
+
+    let first = *selected.first()?;
+    let last = *selected.last()?;
+    if selected.iter().all(|node| node.is_comment()) {
+        return None;
+    }
+
+    // Find the variables of the enclosing scope
+    let scope = container
+        .find_parent(|node| node.is_function_definition())
+        .unwrap_or(root);
+
+    let scopes = Scopes::new(document);
+    let scope = scopes.scope_of(&scope)?;
+
+    let occurrences: Vec<&Occurrence> = scopes
+        .occurrences()
+        .iter()
+        .filter(|occurrence| scopes.is_within(occurrence.scope, Some(scope)))
+        .collect();
+
+    // Variables bound in the enclosing scope itself, rather than in nested
+    // functions or with super-assignments
+    let is_local_binding = |occurrence: &Occurrence| {
+        occurrence.kind == OccurrenceKind::Binding && occurrence.scope == scope
+    };
+
+    let start = first.start_position();
+    let end = last.end_position();
+
+    let defined_before: HashSet<&str> = occurrences
+        .iter()
+        .filter(|occurrence| is_local_binding(**occurrence))
+        .filter(|occurrence| occurrence.node.end_position() <= start)
+        .map(|occurrence| occurrence.name.as_str())
+        .collect();
+
+    let mut parameters: Vec<&str> = Vec::new();
+    let mut defined_within: Vec<&str> = Vec::new();
+
+    for occurrence in occurrences.iter().filter(|occurrence| {
+        occurrence.node.start_position() >= start && occurrence.node.end_position() <= end
+    }) {
+        let name = occurrence.name.as_str();
+        if occurrence.kind.is_binding() {
+            if is_local_binding(*occurrence) && !defined_within.contains(&name) {
+                defined_within.push(name);
+            }
+        } else if !defined_within.contains(&name) &&
+            defined_before.contains(name) &&
+            scopes.resolve_at(name, occurrence).unwrap_or(ROOT) == scope &&
+            !parameters.contains(&name)
+        {
+            parameters.push(name);
+        }
+    }
+
+    let outputs: Vec<&str> = defined_within
+        .into_iter()
+        .filter(|name| {
+            occurrences.iter().any(|occurrence| {
+                occurrence.name == *name && occurrence.node.start_position() >= end
+            })
+        })
+        .collect();
+
+    // Functions can only return one variable
+    let output = match outputs.as_slice() {
+        [] => None,
+        [output] => Some(*output),
+        _ => return None,
+    };
+
+    // Reindent the body of the function
+    let unit = new_line_indent(&document.config.indent, document.config.indent.indent_size);
+    let text = contents
+        .byte_slice(
+            contents.point_to_byte(ArkPoint::new(start.row, 0))..contents.point_to_byte(end),
+        )
+        .to_string();
+    let base = line_indentation(contents, start.row);
+
+    let mut lines: Vec<String> = text
+        .lines()
+        .map(|line| {
+            let line = line.strip_prefix(base.as_str()).unwrap_or(line);
+            if line.trim().is_empty() {
+                String::new()
+            } else {
+                format!("{unit}{line}")
+            }
+        })
+        .collect();
+    if let Some(output) = output {
+        lines.push(format!("{unit}{output}"));
+    }
+
+    let name = unique_name(document, "new_function");
+    let parameters = parameters.join(", ");
+    let definition = format!(
+        "{name} <- function({parameters}) {{\n{body}\n}}\n\n",
+        body = lines.join("\n")
+    );
+
+    let call = match output {
+        Some(output) => format!("{output} <- {name}({parameters})"),
+        None => format!("{name}({parameters})"),
+    };
+
+    // The function is defined before the top-level statement containing the
+    // selection
+    let top_level =
+        first.find_parent(|node| node.parent().is_some_and(|parent| parent.is_program()))?;
+    let insertion = ArkPoint::new(top_level.start_position().row, 0);
+
+    let edits = if insertion == start {
+        vec![ArkTextEdit {
+            range: ArkRange { start, end },
+            new_text: definition + &call,
+        }]
+    } else {
+        vec![
+            ArkTextEdit {
+                range: ArkRange { start, end },
+                new_text: call,
+            },
+            ArkTextEdit {
+                range: ArkRange {
+                    start: insertion,
+                    end: insertion,
+                },
+                new_text: definition,
+            },
+        ]
+    };
+
+    Some(Action {
+        title: String::from("Extract to function"),
+        kind: CodeActionKind::REFACTOR_EXTRACT,
+        edits,
+    })
+}
+
+fn is_expression(node: &Node) -> bool {
+    !matches!(
+        node.kind(),
+        "program" | "arguments" | "argument" | "parameters" | "parameter" | "comma"
+    )
+}
+
+/// Returns the statement of a program or braced expression that contains
+/// `node`
+fn enclosing_statement(node: Node) -> Option<Node> {
+    node.find_parent(|node| {
+        node.parent()
+            .is_some_and(|parent| parent.is_program() || parent.is_braced_expression())
+    })
+}
+
+/// Returns a variable name based on `name` that isn't used in the document
+fn unique_name(document: &Document, name: &str) -> String {
+    let mut names = HashSet::new();
+    let mut cursor = document.ast.walk();
+    cursor.recurse(|node| {
+        if node.is_identifier() {
+            if let Some(name) = node_text(&node, &document.contents) {
+                names.insert(name);
+            }
+        }
+        true
+    });
+
+    let mut candidate = name.to_string();
+    let mut i = 1;
+    while names.contains(&candidate) {
+        i += 1;
+        candidate = format!("{name}{i}");
+    }
+    candidate
+}
+
+fn line_indentation(contents: &Rope, row: usize) -> String {
+    contents
+        .line(row)
+        .chars()
+        .take_while(|char| *char == ' ' || *char == '\t')
+        .collect()
+}
+
+/// Excludes the whitespace around the selection. Returns `None` for empty
+/// selections.
+fn trim_range(contents: &Rope, range: ArkRange) -> Option<ArkRange> {
+    let start = contents.point_to_byte(range.start);
+    let end = contents.point_to_byte(range.end);
+    if start >= end {
+        return None;
+    }
+
+    let text = contents.get_byte_slice(start..end)?.to_string();
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let leading = text.len() - text.trim_start().len();
+
+    Some(ArkRange {
+        start: byte_to_point(contents, start + leading),
+        end: byte_to_point(contents, start + leading + trimmed.len()),
+    })
+}
+
+/// Expands a lone `#'` comment right above a function definition into a
+/// roxygen skeleton documenting the function parameters
+fn roxygen_skeleton(document: &Document, point: ArkPoint) -> Option<Action> {
+    let contents = &document.contents;
+
+    // The cursor is typically right after the `#'` so look up the comment
+    // by line rather than by position
+    let root = document.ast.root_node();
+    let mut cursor = root.walk();
+    let comment = root
+        .children(&mut cursor)
+        .find(|node| node.is_comment() && node.start_position().row == point.row)?;
+
+    if node_text(&comment, contents)?.trim_end() != "#'" {
+        return None;
+    }
+
+    // The comment must be the only roxygen line above the function
+    let node = comment.next_sibling()?;
+    if node.start_position().row != comment.end_position().row + 1 {
+        return None;
+    }
+    if comment.prev_sibling().is_some_and(|previous| {
+        previous.is_comment() &&
+            previous.end_position().row + 1 == comment.start_position().row &&
+            node_text(&previous, contents).is_some_and(|text| text.starts_with("#'"))
+    }) {
+        return None;
+    }
+
+    let (_, function) = function_assignment(&node, contents)?;
+
+    let mut lines = vec![String::from("#' Title"), String::from("#'")];
+    for (name, _) in function_formals(&function, contents) {
+        lines.push(format!("#' @param {name}"));
+    }
+    lines.extend(["#'", "#' @returns", "#' @export", "#'", "#' @examples"].map(String::from));
+
+    Some(Action {
+        title: String::from("Generate a roxygen skeleton"),
+        kind: CodeActionKind::REFACTOR_REWRITE,
+        edits: vec![ArkTextEdit {
+            range: node_range(&comment),
+            new_text: lines.join("\n"),
+        }],
+    })
+}
+
+fn byte_to_point(contents: &Rope, byte: usize) -> ArkPoint {
+    let row = contents.byte_to_line(byte);
+    ArkPoint::new(row, byte - contents.line_to_byte(row))
+}
+
+fn node_range(node: &Node) -> ArkRange {
+    ArkRange {
+        start: node.start_position(),
+        end: node.end_position(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Diagnostic;
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+
+    use crate::lsp::code_actions::extract_function;
+    use crate::lsp::code_actions::extract_variable;
+    use crate::lsp::code_actions::quick_fixes;
+    use crate::lsp::code_actions::roxygen_skeleton;
+    use crate::lsp::code_actions::Action;
+    use crate::lsp::diagnostics::DiagnosticKind;
+    use crate::lsp::documents::Document;
+    use crate::lsp::offset::apply_text_edits;
+    use crate::lsp::offset::ArkPoint;
+    use crate::lsp::offset::ArkRange;
+    use crate::lsp::state::WorldState;
+
+    fn apply(text: &str, action: Action) -> String {
+        let mut text = text.to_string();
+        apply_text_edits(action.edits, &mut text).unwrap();
+        text
+    }
+
+    fn diagnostic(kind: DiagnosticKind, start: (u32, u32), end: (u32, u32)) -> Diagnostic {
+        Diagnostic {
+            range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            data: kind.data(),
+            ..Default::default()
+        }
+    }
+
+    fn range(start: (usize, usize), end: (usize, usize)) -> ArkRange {
+        ArkRange {
+            start: ArkPoint::new(start.0, start.1),
+            end: ArkPoint::new(end.0, end.1),
+        }
+    }
+
+    #[test]
+    fn test_invalid_na_comparison_fix() {
+        let text = "if (x == NA) 1\n";
+        let document = Document::new(text, None);
+
+        let diagnostic = diagnostic(DiagnosticKind::InvalidNaComparison, (0, 9), (0, 11));
+        let mut actions = quick_fixes(&document, &diagnostic, &WorldState::default());
+        assert_eq!(actions.len(), 1);
+        assert_eq!(apply(text, actions.remove(0)), "if (is.na(x)) 1\n");
+    }
+
+    #[test]
+    fn test_assignment_in_if_condition_fix() {
+        let text = "if (x = 1) y\n";
+        let document = Document::new(text, None);
+
+        let diagnostic = diagnostic(DiagnosticKind::AssignmentInIfCondition, (0, 4), (0, 9));
+        let mut actions = quick_fixes(&document, &diagnostic, &WorldState::default());
+        assert_eq!(actions.len(), 1);
+        assert_eq!(apply(text, actions.remove(0)), "if (x == 1) y\n");
+    }
+
+    #[test]
+    fn test_extract_variable() {
+        let text = "f <- function(x) {\n  y <- x * 2 + 1\n  y\n}\n";
+        let document = Document::new(text, None);
+
+        let action = extract_variable(&document, range((1, 7), (1, 12))).unwrap();
+        assert_eq!(
+            apply(text, action),
+            "f <- function(x) {\n  new_variable <- x * 2\n  y <- new_variable + 1\n  y\n}\n"
+        );
+
+        // Partial expressions can't be extracted
+        assert!(extract_variable(&document, range((1, 7), (1, 10))).is_none());
+
+        // The body of a function isn't evaluated with the function definition
+        let document = Document::new("f <- function(x) x * 2\n", None);
+        assert!(extract_variable(&document, range((0, 17), (0, 22))).is_none());
+    }
+
+    #[test]
+    fn test_extract_function() {
+        let text = "x <- 1\ny <- x + 1\nz <- y * 2\nprint(z)\n";
+        let document = Document::new(text, None);
+
+        let action = extract_function(&document, range((1, 0), (2, 10))).unwrap();
+        assert_eq!(
+            apply(text, action),
+            "x <- 1\nnew_function <- function(x) {\n  y <- x + 1\n  z <- y * 2\n  z\n}\n\nz <- new_function(x)\nprint(z)\n"
+        );
+
+        // Partial statements can't be extracted
+        assert!(extract_function(&document, range((1, 5), (2, 10))).is_none());
+    }
+
+    #[test]
+    fn test_extract_function_from_function() {
+        let text = "f <- function(a) {\n  b <- a + 1\n  print(b)\n}\n";
+        let document = Document::new(text, None);
+
+        let action = extract_function(&document, range((2, 2), (2, 10))).unwrap();
+        assert_eq!(
+            apply(text, action),
+            "new_function <- function(b) {\n  print(b)\n}\n\nf <- function(a) {\n  b <- a + 1\n  new_function(b)\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_extract_function_with_nested_binding() {
+        // The parameter of the nested function shadows the variable
+        let text = "x <- 1\ny <- sapply(1:3, function(x) x + 1)\n";
+        let document = Document::new(text, None);
+
+        let action = extract_function(&document, range((1, 0), (1, 35))).unwrap();
+        assert_eq!(
+            apply(text, action),
+            "x <- 1\nnew_function <- function() {\n  y <- sapply(1:3, function(x) x + 1)\n}\n\nnew_function()\n"
+        );
+    }
+
+    #[test]
+    fn test_roxygen_skeleton() {
+        let text = "#'\nf <- function(x, y = 1) x\n";
+        let document = Document::new(text, None);
+
+        let action = roxygen_skeleton(&document, ArkPoint::new(0, 2)).unwrap();
+        assert_eq!(
+            apply(text, action),
+            "#' Title\n#'\n#' @param x\n#' @param y\n#'\n#' @returns\n#' @export\n#'\n#' @examples\nf <- function(x, y = 1) x\n"
+        );
+
+        // Not above a function
+        let document = Document::new("#'\nx <- 1\n", None);
+        assert!(roxygen_skeleton(&document, ArkPoint::new(0, 2)).is_none());
+
+        // Already documented
+        let document = Document::new("#' Title\n#'\nf <- function(x) x\n", None);
+        assert!(roxygen_skeleton(&document, ArkPoint::new(1, 2)).is_none());
+    }
+}
@@ -192,6 +192,19 @@ pub(super) fn completion_item_from_function<T: AsRef<str>>(
     return Ok(item);
 }
 
+pub(super) fn completion_item_from_import(name: &str, package: &str) -> Result<CompletionItem> {
+    let mut item = completion_item(name, CompletionData::Function {
+        name: name.to_string(),
+        package: Some(package.to_string()),
+    })?;
+
+    item.kind = Some(CompletionItemKind::FUNCTION);
+    item.detail = Some(format!("{package}::{name}"));
+    item.insert_text = Some(sym_quote_invalid(name));
+
+    return Ok(item);
+}
+
 // TODO
 pub(super) unsafe fn completion_item_from_dataset(name: &str) -> Result<CompletionItem> {
     let mut item = completion_item(name.to_string(), CompletionData::Unknown)?;
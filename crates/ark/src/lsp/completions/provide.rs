@@ -11,6 +11,7 @@ use tower_lsp::lsp_types::CompletionItem;
 use crate::lsp::completions::sources::completions_from_composite_sources;
 use crate::lsp::completions::sources::completions_from_unique_sources;
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::package::Package;
 use crate::lsp::state::WorldState;
 
 // Entry point for completions.
@@ -18,6 +19,7 @@ use crate::lsp::state::WorldState;
 pub(crate) fn provide_completions(
     context: &DocumentContext,
     state: &WorldState,
+    package: Option<&Package>,
 ) -> Result<Vec<CompletionItem>> {
     log::info!("provide_completions()");
 
@@ -28,5 +30,5 @@ pub(crate) fn provide_completions(
     // At this point we aren't in a "unique" completion case, so just return a
     // set of reasonable completions based on loaded packages, the open
     // document, the current workspace, and any call related arguments
-    completions_from_composite_sources(context, state)
+    completions_from_composite_sources(context, state, package)
 }
@@ -7,6 +7,7 @@
 
 mod call;
 mod document;
+mod imports;
 mod keyword;
 mod pipe;
 mod search_path;
@@ -19,6 +20,7 @@ use std::collections::HashSet;
 use anyhow::Result;
 use call::completions_from_call;
 use document::completions_from_document;
+use imports::completions_from_imports;
 use keyword::completions_from_keywords;
 use pipe::completions_from_pipe;
 use pipe::find_pipe_root;
@@ -32,6 +34,7 @@ use tree_sitter::Node;
 use workspace::completions_from_workspace;
 
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::package::Package;
 use crate::lsp::state::WorldState;
 use crate::treesitter::NodeType;
 use crate::treesitter::NodeTypeExt;
@@ -39,6 +42,7 @@ use crate::treesitter::NodeTypeExt;
 pub fn completions_from_composite_sources(
     context: &DocumentContext,
     state: &WorldState,
+    package: Option<&Package>,
 ) -> Result<Vec<CompletionItem>> {
     log::info!("completions_from_composite_sources()");
 
@@ -69,6 +73,13 @@ pub fn completions_from_composite_sources(
     if is_identifier_like(context.node) {
         completions.append(&mut completions_from_keywords());
         completions.append(&mut completions_from_snippets());
+
+        // Within a package, imported symbols and packages come before their
+        // search path counterparts so they are preferred when deduplicating
+        if let Some(package) = package {
+            completions.append(&mut completions_from_imports(package)?);
+        }
+
         completions.append(&mut completions_from_search_path(context)?);
 
         if let Some(mut additional_completions) = completions_from_document(context)? {
@@ -268,7 +268,8 @@ fn completions_from_workspace_arguments(
                 }
             }
         },
-        indexer::IndexEntryData::Section { level: _, title: _ } => {
+        indexer::IndexEntryData::Section { level: _, title: _ } |
+        indexer::IndexEntryData::Variable { name: _ } => {
             // Not a function
             return Ok(None);
         },
@@ -0,0 +1,80 @@
+//
+// imports.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use anyhow::Result;
+use tower_lsp::lsp_types::CompletionItem;
+
+use crate::lsp::completions::completion_item::completion_item_from_import;
+use crate::lsp::completions::completion_item::completion_item_from_package;
+use crate::lsp::package::Package;
+
+/// Completions for the symbols imported by a package and for the packages
+/// it depends on. They sort before the other symbols and packages.
+pub(super) fn completions_from_imports(package: &Package) -> Result<Vec<CompletionItem>> {
+    log::info!("completions_from_imports()");
+
+    let mut completions = vec![];
+
+    for (from, symbol) in package.imported_symbols() {
+        match completion_item_from_import(symbol, from) {
+            Ok(item) => completions.push(item),
+            Err(err) => log::error!("{err:?}"),
+        }
+    }
+
+    let description = &package.description;
+    let packages = description
+        .depends
+        .iter()
+        .chain(description.imports.iter())
+        .filter(|name| *name != "R");
+
+    for name in packages {
+        completions.push(unsafe { completion_item_from_package(name, true)? });
+    }
+
+    for item in completions.iter_mut() {
+        item.sort_text = Some(format!("0-{}", item.label));
+    }
+
+    Ok(completions)
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::CompletionItemKind;
+
+    use crate::lsp::completions::sources::composite::imports::completions_from_imports;
+    use crate::lsp::package::Description;
+    use crate::lsp::package::Namespace;
+    use crate::lsp::package::Package;
+
+    #[test]
+    fn test_completions_from_imports() {
+        let package = Package {
+            description: Description::parse("Package: pkg\nDepends: R (>= 4.0)\nImports: cli\n")
+                .unwrap(),
+            namespace: Namespace::parse("importFrom(cli, cli_abort)\n"),
+            ..Default::default()
+        };
+
+        let completions = completions_from_imports(&package).unwrap();
+        let completions: Vec<_> = completions
+            .iter()
+            .map(|item| (item.label.as_str(), item.kind, item.sort_text.as_deref()))
+            .collect();
+
+        assert_eq!(completions, vec![
+            (
+                "cli_abort",
+                Some(CompletionItemKind::FUNCTION),
+                Some("0-cli_abort")
+            ),
+            ("cli", Some(CompletionItemKind::MODULE), Some("0-cli")),
+        ]);
+    }
+}
@@ -96,6 +96,7 @@ pub(super) fn completions_from_workspace(
             },
 
             indexer::IndexEntryData::Section { level: _, title: _ } => {},
+            indexer::IndexEntryData::Variable { name: _ } => {},
         }
     });
 
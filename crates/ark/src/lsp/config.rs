@@ -1,14 +1,17 @@
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::Value;
 use struct_field_names_as_array::FieldNamesAsArray;
 
 use crate::lsp;
 use crate::lsp::diagnostics::DiagnosticsConfig;
+use crate::lsp::inlay_hints::InlayHintsConfig;
 
 /// Configuration of the LSP
 #[derive(Clone, Debug)]
 pub(crate) struct LspConfig {
     pub(crate) diagnostics: DiagnosticsConfig,
+    pub(crate) inlay_hints: InlayHintsConfig,
 }
 
 /// Configuration of a document.
@@ -53,6 +56,16 @@ pub(crate) struct VscDiagnosticsConfig {
     pub enable: bool,
 }
 
+/// Settings that are unset on the client side fall back to the defaults of
+/// `InlayHintsConfig`
+#[derive(Serialize, Deserialize, FieldNamesAsArray, Clone, Debug)]
+#[serde(default)]
+pub(crate) struct VscInlayHintsConfig {
+    // DEV NOTE: Update `section_from_key()` method after adding a field
+    pub enable: bool,
+    pub literals_only: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
 pub(crate) enum VscIndentSize {
@@ -64,6 +77,7 @@ impl Default for LspConfig {
     fn default() -> Self {
         Self {
             diagnostics: Default::default(),
+            inlay_hints: Default::default(),
         }
     }
 }
@@ -134,6 +148,48 @@ impl From<VscDiagnosticsConfig> for DiagnosticsConfig {
     }
 }
 
+impl Default for VscInlayHintsConfig {
+    fn default() -> Self {
+        let config = InlayHintsConfig::default();
+        Self {
+            enable: config.enable,
+            literals_only: config.literals_only,
+        }
+    }
+}
+
+impl VscInlayHintsConfig {
+    /// Creates the config from the values of the settings, in the order of
+    /// `FIELD_NAMES_AS_ARRAY`. The client sends `null` for unset settings,
+    /// which are left out so that they get their default value.
+    pub(crate) fn from_values(values: Vec<Value>) -> anyhow::Result<Self> {
+        let mut map = serde_json::Map::new();
+        for (key, value) in std::iter::zip(Self::FIELD_NAMES_AS_ARRAY, values) {
+            if !value.is_null() {
+                map.insert(key.into(), value);
+            }
+        }
+        Ok(serde_json::from_value(Value::Object(map))?)
+    }
+
+    pub(crate) fn section_from_key(key: &str) -> &str {
+        match key {
+            "enable" => "positron.r.inlayHints.enable",
+            "literals_only" => "positron.r.inlayHints.literalsOnly",
+            _ => "unknown", // To be caught via downstream errors
+        }
+    }
+}
+
+impl From<VscInlayHintsConfig> for InlayHintsConfig {
+    fn from(value: VscInlayHintsConfig) -> Self {
+        Self {
+            enable: value.enable,
+            literals_only: value.literals_only,
+        }
+    }
+}
+
 pub(crate) fn indent_style_from_lsp(insert_spaces: bool) -> IndentStyle {
     if insert_spaces {
         IndentStyle::Space
@@ -141,3 +197,33 @@ pub(crate) fn indent_style_from_lsp(insert_spaces: bool) -> IndentStyle {
         IndentStyle::Tab
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::lsp::config::VscInlayHintsConfig;
+    use crate::lsp::inlay_hints::InlayHintsConfig;
+
+    #[test]
+    fn test_inlay_hints_config_from_values() {
+        // Unset settings get their default value
+        let config = VscInlayHintsConfig::from_values(vec![json!(null), json!(null)]).unwrap();
+        assert_eq!(InlayHintsConfig::from(config), InlayHintsConfig::default());
+
+        let config = VscInlayHintsConfig::from_values(vec![json!(null), json!(true)]).unwrap();
+        assert_eq!(InlayHintsConfig::from(config), InlayHintsConfig {
+            enable: true,
+            literals_only: true,
+        });
+
+        let config = VscInlayHintsConfig::from_values(vec![json!(false), json!(false)]).unwrap();
+        assert_eq!(InlayHintsConfig::from(config), InlayHintsConfig {
+            enable: false,
+            literals_only: false,
+        });
+
+        // Invalid values are still errors
+        assert!(VscInlayHintsConfig::from_values(vec![json!("yes"), json!(null)]).is_err());
+    }
+}
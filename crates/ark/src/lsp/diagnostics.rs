@@ -13,6 +13,8 @@ use anyhow::Result;
 use harp::utils::is_symbol_valid;
 use harp::utils::sym_quote_invalid;
 use ropey::Rope;
+use serde::Deserialize;
+use serde::Serialize;
 use stdext::*;
 use tower_lsp::lsp_types::Diagnostic;
 use tower_lsp::lsp_types::DiagnosticSeverity;
@@ -23,6 +25,8 @@ use crate::lsp::declarations::top_level_declare;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
 use crate::lsp::indexer;
+use crate::lsp::package::Package;
+use crate::lsp::roxygen::roxygen_diagnostics;
 use crate::lsp::state::WorldState;
 use crate::lsp::traits::rope::RopeExt;
 use crate::treesitter::BinaryOperatorType;
@@ -35,6 +39,9 @@ pub struct DiagnosticsConfig {
     pub enable: bool,
 }
 
+/// The symbols in scope while walking a document in evaluation order. Unlike
+/// the `Scopes` used to resolve variables, a symbol is only in scope after
+/// its definition.
 #[derive(Clone)]
 pub struct DiagnosticContext<'a> {
     /// The contents of the source document.
@@ -50,6 +57,13 @@ pub struct DiagnosticContext<'a> {
     /// The symbols defined in the workspace.
     pub workspace_symbols: HashSet<String>,
 
+    /// The package the document belongs to, if any.
+    pub package: Option<&'a Package>,
+
+    /// The symbols defined at the top level of the package code, along with
+    /// the symbols it imports. These are in scope regardless of file order.
+    pub package_symbols: HashSet<String>,
+
     // The set of packages that are currently installed.
     pub installed_packages: HashSet<String>,
 
@@ -60,6 +74,28 @@ pub struct DiagnosticContext<'a> {
     pub in_call: bool,
 }
 
+/// Diagnostics that can be fixed with a code action. The kind is stored in
+/// the `data` field of the diagnostic, which the client sends back when it
+/// requests code actions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DiagnosticKind {
+    InvalidNaComparison,
+    AssignmentInIfCondition,
+    SymbolNotInScope,
+}
+
+impl DiagnosticKind {
+    pub(crate) fn from_diagnostic(diagnostic: &Diagnostic) -> Option<Self> {
+        let data = diagnostic.data.clone()?;
+        serde_json::from_value(data).ok()
+    }
+
+    pub(crate) fn data(self) -> Option<serde_json::Value> {
+        serde_json::to_value(self).ok()
+    }
+}
+
 impl Default for DiagnosticsConfig {
     fn default() -> Self {
         Self { enable: true }
@@ -67,6 +103,69 @@ impl Default for DiagnosticsConfig {
 }
 
 impl<'a> DiagnosticContext<'a> {
+    pub fn new(contents: &'a Rope, state: &WorldState) -> Self {
+        let mut context = DiagnosticContext {
+            contents,
+            document_symbols: Vec::new(),
+            session_symbols: HashSet::new(),
+            workspace_symbols: HashSet::new(),
+            package: None,
+            package_symbols: HashSet::new(),
+            installed_packages: HashSet::new(),
+            in_formula: false,
+            in_call: false,
+        };
+
+        // Add a 'root' context for the document.
+        context.document_symbols.push(HashMap::new());
+
+        // Add the current workspace symbols.
+        indexer::map(|_path, _symbol, entry| match &entry.data {
+            indexer::IndexEntryData::Function { name, arguments: _ } => {
+                context.workspace_symbols.insert(name.to_string());
+            },
+            _ => {},
+        });
+
+        for scope in state.console_scopes.iter() {
+            for name in scope.iter() {
+                if is_symbol_valid(name.as_str()) {
+                    context.session_symbols.insert(name.clone());
+                } else {
+                    let name = sym_quote_invalid(name.as_str());
+                    context.session_symbols.insert(name.clone());
+                }
+            }
+        }
+
+        for pkg in state.installed_packages.iter() {
+            context.installed_packages.insert(pkg.clone());
+        }
+
+        context
+    }
+
+    pub fn add_package(&mut self, package: &'a Package) {
+        indexer::map(|path, _symbol, entry| {
+            if !package.is_source(path) {
+                return;
+            }
+            match &entry.data {
+                indexer::IndexEntryData::Function { name, arguments: _ } |
+                indexer::IndexEntryData::Variable { name } => {
+                    self.package_symbols.insert(name.to_string());
+                },
+                _ => {},
+            }
+        });
+
+        for (_package, symbol) in package.imported_symbols() {
+            self.package_symbols.insert(symbol.to_string());
+        }
+
+        self.package = Some(package);
+    }
+
     pub fn add_defined_variable(&mut self, name: &str, location: Range) {
         let symbols = self.document_symbols.last_mut().unwrap();
         symbols.insert(name.to_string(), location);
@@ -85,12 +184,32 @@ impl<'a> DiagnosticContext<'a> {
             return true;
         }
 
+        // Next, check package definitions and imports.
+        if self.package_symbols.contains(name) {
+            return true;
+        }
+
         // Finally, check session symbols.
         self.session_symbols.contains(name)
     }
+
+    /// Finds the innermost document definition of `name`. Returns the depth of
+    /// the scope holding it, where 0 is the top level of the document, along
+    /// with the location of the definition.
+    pub fn find_definition(&self, name: &str) -> Option<(usize, Range)> {
+        self.document_symbols
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, symbols)| symbols.get(name).map(|range| (depth, *range)))
+    }
 }
 
-pub(crate) fn generate_diagnostics(doc: Document, state: WorldState) -> Vec<Diagnostic> {
+pub(crate) fn generate_diagnostics(
+    doc: Document,
+    state: WorldState,
+    package: Option<&Package>,
+) -> Vec<Diagnostic> {
     let mut diagnostics = Vec::new();
 
     if !state.config.diagnostics.enable {
@@ -105,40 +224,9 @@ pub(crate) fn generate_diagnostics(doc: Document, state: WorldState) -> Vec<Diag
     }
 
     {
-        let mut context = DiagnosticContext {
-            contents: &doc.contents,
-            document_symbols: Vec::new(),
-            session_symbols: HashSet::new(),
-            workspace_symbols: HashSet::new(),
-            installed_packages: HashSet::new(),
-            in_formula: false,
-            in_call: false,
-        };
-
-        // Add a 'root' context for the document.
-        context.document_symbols.push(HashMap::new());
-
-        // Add the current workspace symbols.
-        indexer::map(|_path, _symbol, entry| match &entry.data {
-            indexer::IndexEntryData::Function { name, arguments: _ } => {
-                context.workspace_symbols.insert(name.to_string());
-            },
-            _ => {},
-        });
-
-        for scope in state.console_scopes.iter() {
-            for name in scope.iter() {
-                if is_symbol_valid(name.as_str()) {
-                    context.session_symbols.insert(name.clone());
-                } else {
-                    let name = sym_quote_invalid(name.as_str());
-                    context.session_symbols.insert(name.clone());
-                }
-            }
-        }
-
-        for pkg in state.installed_packages.iter() {
-            context.installed_packages.insert(pkg.clone());
+        let mut context = DiagnosticContext::new(&doc.contents, &state);
+        if let Some(package) = package {
+            context.add_package(package);
         }
 
         // Start iterating through the nodes.
@@ -152,6 +240,9 @@ pub(crate) fn generate_diagnostics(doc: Document, state: WorldState) -> Vec<Diag
         }
     }
 
+    // Check roxygen documentation against the functions it documents
+    diagnostics.extend(roxygen_diagnostics(&doc));
+
     diagnostics
 }
 
@@ -448,6 +539,17 @@ fn recurse_namespace(
         diagnostics.push(diagnostic);
     }
 
+    // Within a package, check that the package is declared in `DESCRIPTION`.
+    if let Some(pkg) = context.package {
+        if !pkg.declares(package.as_str()) {
+            let range = convert_tree_sitter_range_to_lsp_range(context.contents, lhs.range());
+            let message = format!("package '{}' is not declared in DESCRIPTION", package);
+            let mut diagnostic = Diagnostic::new_simple(range, message);
+            diagnostic.severity = Some(DiagnosticSeverity::WARNING);
+            diagnostics.push(diagnostic);
+        }
+    }
+
     // Check for a symbol in this namespace.
     let rhs = unwrap!(node.child_by_field_name("rhs"), None => {
         return ().ok();
@@ -832,6 +934,7 @@ fn check_invalid_na_comparison(
             let range = convert_tree_sitter_range_to_lsp_range(context.contents, range);
             let mut diagnostic = Diagnostic::new_simple(range, message.into());
             diagnostic.severity = Some(DiagnosticSeverity::INFORMATION);
+            diagnostic.data = DiagnosticKind::InvalidNaComparison.data();
             diagnostics.push(diagnostic);
         }
     }
@@ -922,7 +1025,8 @@ fn check_unexpected_assignment_in_if_conditional(
     let range = condition.range();
     let range = convert_tree_sitter_range_to_lsp_range(context.contents, range);
     let message = "unexpected '='; use '==' to compare values for equality";
-    let diagnostic = Diagnostic::new_simple(range, message.into());
+    let mut diagnostic = Diagnostic::new_simple(range, message.into());
+    diagnostic.data = DiagnosticKind::AssignmentInIfCondition.data();
     diagnostics.push(diagnostic);
 
     true.ok()
@@ -972,6 +1076,7 @@ fn check_symbol_in_scope(
     let message = format!("no symbol named '{}' in scope", identifier);
     let mut diagnostic = Diagnostic::new_simple(range, message);
     diagnostic.severity = Some(DiagnosticSeverity::WARNING);
+    diagnostic.data = DiagnosticKind::SymbolNotInScope.data();
     diagnostics.push(diagnostic);
 
     true.ok()
@@ -979,6 +1084,9 @@ fn check_symbol_in_scope(
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+    use std::path::PathBuf;
+
     use harp::eval::r_parse_eval;
     use harp::eval::RParseEvalOptions;
     use once_cell::sync::Lazy;
@@ -988,6 +1096,10 @@ mod tests {
     use crate::lsp::diagnostics::generate_diagnostics;
     use crate::lsp::diagnostics::is_unmatched_block;
     use crate::lsp::documents::Document;
+    use crate::lsp::indexer;
+    use crate::lsp::package::Description;
+    use crate::lsp::package::Namespace;
+    use crate::lsp::package::Package;
     use crate::lsp::state::WorldState;
     use crate::test::r_test;
 
@@ -1051,7 +1163,7 @@ mod tests {
                 2 # hi there
             )";
             let document = Document::new(text, None);
-            let diagnostics = generate_diagnostics(document, DEFAULT_STATE.clone());
+            let diagnostics = generate_diagnostics(document, DEFAULT_STATE.clone(), None);
             assert!(diagnostics.is_empty());
         })
     }
@@ -1062,7 +1174,7 @@ mod tests {
             let text = "match(1, 2 3)";
             let document = Document::new(text, None);
 
-            let diagnostics = generate_diagnostics(document, DEFAULT_STATE.clone());
+            let diagnostics = generate_diagnostics(document, DEFAULT_STATE.clone(), None);
             assert_eq!(diagnostics.len(), 1);
 
             let diagnostic = diagnostics.get(0).unwrap();
@@ -1089,12 +1201,12 @@ mod tests {
 
             let text = "x$foo";
             let document = Document::new(text, None);
-            let diagnostics = generate_diagnostics(document.clone(), state.clone());
+            let diagnostics = generate_diagnostics(document.clone(), state.clone(), None);
             assert!(diagnostics.is_empty());
 
             let text = "x@foo";
             let document = Document::new(text, None);
-            let diagnostics = generate_diagnostics(document.clone(), state.clone());
+            let diagnostics = generate_diagnostics(document.clone(), state.clone(), None);
             assert!(diagnostics.is_empty());
 
             // Clean up
@@ -1112,7 +1224,7 @@ mod tests {
                 y + x + z
             ";
             let document = Document::new(text, None);
-            let diagnostics = generate_diagnostics(document.clone(), DEFAULT_STATE.clone());
+            let diagnostics = generate_diagnostics(document.clone(), DEFAULT_STATE.clone(), None);
             assert!(diagnostics.is_empty());
         })
     }
@@ -1126,7 +1238,7 @@ mod tests {
                 y + x
             ";
             let document = Document::new(text, None);
-            let diagnostics = generate_diagnostics(document.clone(), DEFAULT_STATE.clone());
+            let diagnostics = generate_diagnostics(document.clone(), DEFAULT_STATE.clone(), None);
             assert!(diagnostics.is_empty());
         })
     }
@@ -1141,7 +1253,7 @@ mod tests {
             ";
             let document = Document::new(text, None);
 
-            let diagnostics = generate_diagnostics(document.clone(), DEFAULT_STATE.clone());
+            let diagnostics = generate_diagnostics(document.clone(), DEFAULT_STATE.clone(), None);
             assert_eq!(diagnostics.len(), 1);
 
             // Only marks the `x` before the `x <- 1`
@@ -1149,4 +1261,82 @@ mod tests {
             assert_eq!(diagnostic.range.start.line, 1)
         })
     }
+
+    #[test]
+    fn test_package_definitions_and_imports_are_in_scope() {
+        r_test(|| {
+            let package = Package {
+                path: PathBuf::from("/pkg"),
+                description: Description::parse("Package: pkg\nImports: cli, stats\n").unwrap(),
+                namespace: Namespace::parse("importFrom(cli, cli_abort)\n"),
+                ..Default::default()
+            };
+
+            // Defined in another file of the package
+            let helper = Document::new("helper <- 1\n", None);
+            indexer::update(&helper, Path::new("/pkg/R/helper.R")).unwrap();
+
+            let text = "
+                f <- function() {
+                    cli_abort('oops')
+                    helper + later
+                    stats::median(1)
+                    utils::head(1)
+                }
+                later <- 2
+            ";
+            let document = Document::new(text, None);
+            indexer::update(&document, Path::new("/pkg/R/f.R")).unwrap();
+
+            let messages = |package: Option<&Package>| -> Vec<String> {
+                generate_diagnostics(document.clone(), DEFAULT_STATE.clone(), package)
+                    .into_iter()
+                    .map(|diagnostic| diagnostic.message)
+                    .collect()
+            };
+
+            // Outside of the package, the definitions of the other file and the
+            // ones further down are not known
+            let outside = messages(None);
+            assert!(outside.contains(&String::from("no symbol named 'helper' in scope")));
+            assert!(outside.contains(&String::from("no symbol named 'later' in scope")));
+
+            // Within the package, only the undeclared `utils` is reported
+            assert_eq!(messages(Some(&package)), vec![String::from(
+                "package 'utils' is not declared in DESCRIPTION"
+            )]);
+
+            indexer::clear(Path::new("/pkg/R/helper.R")).unwrap();
+            indexer::clear(Path::new("/pkg/R/f.R")).unwrap();
+        })
+    }
+
+    #[test]
+    fn test_host_document_chunks_share_scope() {
+        r_test(|| {
+            let text = "---
+title: x
+---
+
+```{r}
+x <- 1
+```
+
+Some text mentioning `y`.
+
+```{r}
+x + y
+```
+";
+            let document = Document::new_host(text, None);
+
+            let diagnostics = generate_diagnostics(document, DEFAULT_STATE.clone(), None);
+            assert_eq!(diagnostics.len(), 1);
+
+            // `x` comes from the first chunk, `y` is only mentioned in the text
+            let diagnostic = diagnostics.first().unwrap();
+            assert_eq!(diagnostic.message, "no symbol named 'y' in scope");
+            assert_eq!(diagnostic.range.start, Position::new(11, 4));
+        })
+    }
 }
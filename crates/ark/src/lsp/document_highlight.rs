@@ -0,0 +1,91 @@
+//
+// document_highlight.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use tower_lsp::lsp_types::DocumentHighlight;
+use tower_lsp::lsp_types::DocumentHighlightKind;
+use tree_sitter::Point;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::scopes::Scopes;
+
+/// Highlights the references to the binding of the variable at `point`,
+/// within the scope of that binding. Occurrences binding the variable are
+/// writes, the others are reads.
+pub(crate) fn document_highlight(document: &Document, point: Point) -> Vec<DocumentHighlight> {
+    let scopes = Scopes::new(document);
+
+    let Some(occurrence) = scopes.occurrence_at(point) else {
+        return Vec::new();
+    };
+
+    let target = scopes.resolve_at(&occurrence.name, occurrence);
+
+    scopes
+        .references(&occurrence.name, target)
+        .into_iter()
+        .map(|occurrence| DocumentHighlight {
+            range: convert_tree_sitter_range_to_lsp_range(
+                &document.contents,
+                occurrence.node.range(),
+            ),
+            kind: Some(match occurrence.kind.is_binding() {
+                true => DocumentHighlightKind::WRITE,
+                false => DocumentHighlightKind::READ,
+            }),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::DocumentHighlightKind;
+    use tree_sitter::Point;
+
+    use crate::lsp::document_highlight::document_highlight;
+    use crate::lsp::documents::Document;
+
+    fn highlights(text: &str, point: Point) -> Vec<(u32, u32, DocumentHighlightKind)> {
+        let document = Document::new(text, None);
+        document_highlight(&document, point)
+            .into_iter()
+            .map(|highlight| {
+                let start = highlight.range.start;
+                (start.line, start.character, highlight.kind.unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_document_highlight_scopes() {
+        let text = "x <- 1\nf <- function(x) {\n  x <- x + 1\n  x\n}\nx\n";
+
+        // The parameter shadows the global variable within the function
+        assert_eq!(highlights(text, Point::new(2, 2)), vec![
+            (1, 14, DocumentHighlightKind::WRITE),
+            (2, 2, DocumentHighlightKind::WRITE),
+            (2, 7, DocumentHighlightKind::READ),
+            (3, 2, DocumentHighlightKind::READ),
+        ]);
+
+        assert_eq!(highlights(text, Point::new(5, 0)), vec![
+            (0, 0, DocumentHighlightKind::WRITE),
+            (5, 0, DocumentHighlightKind::READ),
+        ]);
+    }
+
+    #[test]
+    fn test_document_highlight_super_assignment() {
+        let text = "count <- 0\nf <- function() count <<- count + 1\n";
+
+        assert_eq!(highlights(text, Point::new(0, 0)), vec![
+            (0, 0, DocumentHighlightKind::WRITE),
+            (1, 16, DocumentHighlightKind::WRITE),
+            (1, 26, DocumentHighlightKind::READ),
+        ]);
+    }
+}
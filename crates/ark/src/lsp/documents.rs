@@ -16,6 +16,7 @@ use tree_sitter::Tree;
 
 use crate::lsp::config::DocumentConfig;
 use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::rmarkdown::HostDocument;
 use crate::lsp::traits::rope::RopeExt;
 
 fn compute_point(point: Point, text: &str) -> Point {
@@ -49,6 +50,10 @@ pub struct Document {
 
     // Configuration of the document, such as indentation settings.
     pub config: DocumentConfig,
+
+    // The R Markdown or Quarto document hosting the R code, if any. In that
+    // case `contents` and `ast` hold the R view of the host document.
+    pub host: Option<HostDocument>,
 }
 
 impl std::fmt::Debug for Document {
@@ -80,6 +85,36 @@ impl Document {
             version,
             ast,
             config: Default::default(),
+            host: None,
+        }
+    }
+
+    /// A one-shot variant of `new_host_with_parser()`, like `new()`
+    pub fn new_host(contents: &str, version: Option<i32>) -> Self {
+        let language = tree_sitter_r::language();
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+
+        Self::new_host_with_parser(contents, &mut parser, version)
+    }
+
+    /// Creates a document for an R Markdown or Quarto file, analysed through
+    /// the R view of its R chunks
+    pub fn new_host_with_parser(contents: &str, parser: &mut Parser, version: Option<i32>) -> Self {
+        let host = HostDocument::new(contents);
+
+        let mut document = Self::new_with_parser(&host.r_view(), parser, version);
+        document.host = Some(host);
+
+        document
+    }
+
+    /// Whether `row` holds R code. Always the case for R files, whereas only
+    /// the rows of R chunks qualify in host documents.
+    pub fn is_r_row(&self, row: usize) -> bool {
+        match &self.host {
+            Some(host) => host.r_chunk(row).is_some(),
+            None => true,
         }
     }
 
@@ -120,6 +155,18 @@ impl Document {
             None => return Ok(()),
         };
 
+        // Edits of host documents may add or remove chunks anywhere, so we
+        // rebuild the R view and parse it from scratch
+        if let Some(host) = self.host.as_mut() {
+            host.update(range, &change.text);
+
+            let contents = host.r_view();
+            self.ast = parser.parse(&contents, None).unwrap();
+            self.contents = Rope::from(contents);
+
+            return Ok(());
+        }
+
         // Update the AST. We do this before updating the underlying document
         // contents, because edit computations need to be done using the current
         // state of the document (prior to the edit being applied) so that byte
@@ -0,0 +1,297 @@
+//
+// folding_range.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use ropey::Rope;
+use tower_lsp::lsp_types::FoldingRange;
+use tower_lsp::lsp_types::FoldingRangeKind;
+use tree_sitter::Node;
+
+use crate::lsp::documents::Document;
+use crate::lsp::indexer::comment_section;
+use crate::lsp::rmarkdown::HostDocument;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+lazy_static! {
+    static ref RE_CELL_MARKER: Regex = Regex::new(r"^#\s*%%").unwrap();
+}
+
+pub fn folding_range(document: &Document) -> anyhow::Result<Vec<FoldingRange>> {
+    let contents = &document.contents;
+
+    let mut folds = Vec::new();
+    let mut comments = Vec::new();
+
+    let mut cursor = document.ast.walk();
+    cursor.recurse(|node| {
+        match node.node_type() {
+            NodeType::BracedExpression | NodeType::Arguments | NodeType::Parameters => {
+                if let Some(fold) = delimited_fold(&node) {
+                    folds.push(fold);
+                }
+            },
+            NodeType::Comment => {
+                comments.push(node);
+            },
+            _ => {},
+        }
+        true
+    });
+
+    // The comments we collected are in document order, paired with their text
+    let comments: Vec<(usize, String)> = comments
+        .iter()
+        .map(|node| {
+            let text = contents.byte_slice(node.byte_range()).to_string();
+            (node.start_position().row, text)
+        })
+        .collect();
+
+    folds.extend(roxygen_folds(&comments));
+    folds.extend(section_folds(&comments, contents));
+    folds.extend(cell_folds(&comments, contents));
+
+    if let Some(host) = &document.host {
+        folds.extend(host_folds(host));
+    }
+
+    Ok(folds)
+}
+
+/// Folds a braced expression or an argument / parameter list spanning
+/// multiple lines. The line holding the closing delimiter stays visible.
+fn delimited_fold(node: &Node) -> Option<FoldingRange> {
+    let start = node.start_position().row;
+    let end = node.end_position().row.checked_sub(1)?;
+
+    if end <= start {
+        return None;
+    }
+
+    Some(fold(start, end, None))
+}
+
+/// Folds runs of consecutive roxygen comment lines
+fn roxygen_folds(comments: &[(usize, String)]) -> Vec<FoldingRange> {
+    let mut folds = Vec::new();
+    let mut block: Option<(usize, usize)> = None;
+
+    for (row, text) in comments {
+        if !text.starts_with("#'") {
+            continue;
+        }
+
+        block = match block {
+            Some((start, end)) if end + 1 == *row => Some((start, *row)),
+            Some((start, end)) => {
+                if end > start {
+                    folds.push(fold(start, end, Some(FoldingRangeKind::Comment)));
+                }
+                Some((*row, *row))
+            },
+            None => Some((*row, *row)),
+        };
+    }
+
+    if let Some((start, end)) = block {
+        if end > start {
+            folds.push(fold(start, end, Some(FoldingRangeKind::Comment)));
+        }
+    }
+
+    folds
+}
+
+/// Folds `# Section ----` headers. A section extends up to the next header of
+/// the same or a higher level, so that nested sections fold within their parent.
+fn section_folds(comments: &[(usize, String)], contents: &Rope) -> Vec<FoldingRange> {
+    let headers: Vec<(usize, usize)> = comments
+        .iter()
+        .filter_map(|(row, text)| comment_section(text).map(|(level, _)| (*row, level)))
+        .collect();
+
+    let mut folds = Vec::new();
+
+    for (i, (start, level)) in headers.iter().enumerate() {
+        let next = headers[i + 1..]
+            .iter()
+            .find(|(_, other)| other <= level)
+            .map(|(row, _)| *row);
+
+        if let Some(fold) = region_fold(*start, next, contents) {
+            folds.push(fold);
+        }
+    }
+
+    folds
+}
+
+/// Folds `#%%` cells, each extending up to the next cell marker
+fn cell_folds(comments: &[(usize, String)], contents: &Rope) -> Vec<FoldingRange> {
+    let markers: Vec<usize> = comments
+        .iter()
+        .filter(|(_, text)| RE_CELL_MARKER.is_match(text))
+        .map(|(row, _)| *row)
+        .collect();
+
+    markers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, start)| region_fold(*start, markers.get(i + 1).copied(), contents))
+        .collect()
+}
+
+/// Folds the chunks and the sections of an R Markdown or Quarto document.
+/// The closing fence of a chunk stays visible, like a closing delimiter.
+fn host_folds(host: &HostDocument) -> Vec<FoldingRange> {
+    let contents = &host.contents;
+    let last_row = contents.len_lines().saturating_sub(1);
+
+    let mut folds: Vec<FoldingRange> = host
+        .chunks
+        .iter()
+        .filter_map(|chunk| {
+            let end = chunk.end.min(last_row + 1).checked_sub(1)?;
+            (end > chunk.start).then(|| fold(chunk.start, end, Some(FoldingRangeKind::Region)))
+        })
+        .collect();
+
+    for (i, heading) in host.headings.iter().enumerate() {
+        let next = host.headings[i + 1..]
+            .iter()
+            .find(|other| other.level <= heading.level)
+            .map(|other| other.row);
+
+        if let Some(fold) = region_fold(heading.row, next, contents) {
+            folds.push(fold);
+        }
+    }
+
+    folds
+}
+
+/// Folds from a header line up to the line before `next` (or to the end of
+/// the document), leaving out trailing blank lines
+fn region_fold(start: usize, next: Option<usize>, contents: &Rope) -> Option<FoldingRange> {
+    let mut end = match next {
+        Some(next) => next.checked_sub(1)?,
+        None => contents.len_lines().checked_sub(1)?,
+    };
+
+    while end > start && contents.line(end).chars().all(char::is_whitespace) {
+        end -= 1;
+    }
+
+    if end <= start {
+        return None;
+    }
+
+    Some(fold(start, end, Some(FoldingRangeKind::Region)))
+}
+
+fn fold(start: usize, end: usize, kind: Option<FoldingRangeKind>) -> FoldingRange {
+    FoldingRange {
+        start_line: start as u32,
+        end_line: end as u32,
+        kind,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::FoldingRangeKind;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::folding_range::folding_range;
+
+    fn folds(text: &str) -> Vec<(u32, u32, Option<FoldingRangeKind>)> {
+        let document = Document::new(text, None);
+        let mut folds: Vec<_> = folding_range(&document)
+            .unwrap()
+            .into_iter()
+            .map(|fold| (fold.start_line, fold.end_line, fold.kind))
+            .collect();
+        folds.sort_by_key(|(start, end, _)| (*start, *end));
+        folds
+    }
+
+    #[test]
+    fn test_folding_range_delimited() {
+        let text = "f <- function(\n  x,\n  y\n) {\n  g(\n    x\n  )\n}\n";
+        assert_eq!(folds(text), vec![(0, 2, None), (3, 6, None), (4, 5, None)]);
+
+        // Nothing to fold on a single line
+        assert_eq!(folds("f <- function(x) { g(x) }\n"), vec![]);
+    }
+
+    #[test]
+    fn test_folding_range_roxygen() {
+        let text =
+            "#' Title\n#'\n#' @param x A value\nf <- function(x) x\n# not roxygen\n#' Single\n";
+        assert_eq!(folds(text), vec![(0, 2, Some(FoldingRangeKind::Comment))]);
+    }
+
+    #[test]
+    fn test_folding_range_sections_and_cells() {
+        let text = "# Setup ----
+library(x)
+
+## Helpers ----
+f <- 1
+
+# Analysis ----
+#%% First cell
+y <- 2
+#%% Second cell
+z <- 3
+";
+        let region = Some(FoldingRangeKind::Region);
+        assert_eq!(folds(text), vec![
+            (0, 4, region.clone()),
+            (3, 4, region.clone()),
+            (6, 10, region.clone()),
+            (7, 8, region.clone()),
+            (9, 10, region.clone()),
+        ]);
+    }
+
+    #[test]
+    fn test_folding_range_host_document() {
+        let text = "# Analysis
+
+```{r}
+f <- function() {
+  1
+}
+```
+
+## Notes
+
+Some text.
+";
+        let document = Document::new_host(text, None);
+
+        let mut folds: Vec<_> = folding_range(&document)
+            .unwrap()
+            .into_iter()
+            .map(|fold| (fold.start_line, fold.end_line, fold.kind))
+            .collect();
+        folds.sort_by_key(|(start, end, _)| (*start, *end));
+
+        let region = Some(FoldingRangeKind::Region);
+        assert_eq!(folds, vec![
+            (0, 10, region.clone()),
+            (2, 5, region.clone()),
+            (3, 4, None),
+            (8, 10, region.clone()),
+        ]);
+    }
+}
@@ -0,0 +1,1049 @@
+//
+// format.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+// Formatter following the tidyverse style guide (https://style.tidyverse.org).
+//
+// The syntax tree is first converted to a `Doc`, a description of the layout
+// that records where lines may be broken, which is then printed within the
+// line width in the manner of Wadler's "prettier printer".
+
+use ropey::Rope;
+use tree_sitter::Node;
+
+use crate::lsp::config::IndentationConfig;
+use crate::lsp::documents::Document;
+use crate::lsp::indent::brace_parent;
+use crate::lsp::indent::line_indent;
+use crate::lsp::indent::new_line_indent;
+use crate::lsp::offset::ArkPoint;
+use crate::lsp::offset::ArkRange;
+use crate::lsp::offset::ArkTextEdit;
+use crate::lsp::traits::node::NodeExt;
+use crate::lsp::traits::rope::RopeExt;
+use crate::treesitter::node_text;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+const LINE_WIDTH: usize = 80;
+
+/// Formats the whole document. Returns `None` when the document is already
+/// formatted or can't be formatted because of syntax errors.
+pub fn format_document(doc: &Document) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    let root = doc.ast.root_node();
+    if root.has_error() {
+        return Ok(None);
+    }
+
+    let builder = Builder {
+        contents: &doc.contents,
+    };
+    let old = doc.contents.to_string();
+
+    let new = print(&builder.node(root), 0, &doc.config.indent);
+    let new = with_line_endings(new, old.contains("\r\n"));
+
+    Ok(diff_lines(&old, &new).map(|edit| vec![edit]))
+}
+
+/// Formats the statements overlapping `range`. The selection is widened to
+/// whole statements, and to the enclosing statement when other code shares
+/// their lines, since the statements are reindented.
+pub fn format_range(doc: &Document, range: ArkRange) -> anyhow::Result<Option<Vec<ArkTextEdit>>> {
+    let root = doc.ast.root_node();
+    if root.has_error() {
+        return Ok(None);
+    }
+
+    let Some(mut node) = root.descendant_for_point_range(range.start, range.end) else {
+        return Ok(None);
+    };
+
+    loop {
+        let Some(container) =
+            node.find_parent(|node| node.is_program() || node.is_braced_expression())
+        else {
+            return Ok(None);
+        };
+
+        let statements = statement_nodes(container);
+        let overlaps = |node: &Node| {
+            node.end_position().row >= range.start.row && node.start_position().row <= range.end.row
+        };
+
+        let (Some(mut first), Some(mut last)) = (
+            statements.iter().position(overlaps),
+            statements.iter().rposition(overlaps),
+        ) else {
+            return Ok(None);
+        };
+
+        // Include statements sharing a line with the selected ones
+        while first > 0 &&
+            statements[first - 1].end_position().row == statements[first].start_position().row
+        {
+            first -= 1;
+        }
+        while last + 1 < statements.len() &&
+            statements[last + 1].start_position().row == statements[last].end_position().row
+        {
+            last += 1;
+        }
+
+        let start = statements[first].start_position();
+        let end = statements[last].end_position();
+
+        let before = doc.contents.line(start.row).to_string();
+        let after = doc.contents.line(end.row).to_string();
+
+        if !before[..start.column].trim().is_empty() || !after[end.column..].trim().is_empty() {
+            match container.parent() {
+                Some(parent) => {
+                    node = parent;
+                    continue;
+                },
+                None => return Ok(None),
+            }
+        }
+
+        let indent = if container.is_program() {
+            0
+        } else {
+            let parent = brace_parent(container);
+            let (indent, _) = line_indent(
+                &doc.contents,
+                parent.start_position().row,
+                &doc.config.indent,
+            );
+            indent + doc.config.indent.indent_size
+        };
+
+        let builder = Builder {
+            contents: &doc.contents,
+        };
+        let new = print(
+            &builder.statements(&statements[first..=last]),
+            indent,
+            &doc.config.indent,
+        );
+        let new = with_line_endings(new, before.ends_with("\r\n"));
+        let new = new.trim_end_matches(['\r', '\n']).to_string();
+
+        let start = ArkPoint {
+            row: start.row,
+            column: 0,
+        };
+        let old = doc
+            .contents
+            .byte_slice(doc.contents.point_to_byte(start)..doc.contents.point_to_byte(end))
+            .to_string();
+
+        if old == new {
+            return Ok(None);
+        }
+
+        return Ok(Some(vec![ArkTextEdit {
+            range: ArkRange { start, end },
+            new_text: new,
+        }]));
+    }
+}
+
+fn statement_nodes(node: Node) -> Vec<Node> {
+    // Skips braces and semicolons
+    children(node)
+        .into_iter()
+        .filter(|child| child.is_named())
+        .collect()
+}
+
+fn children(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    node.children(&mut cursor).collect()
+}
+
+fn with_line_endings(text: String, crlf: bool) -> String {
+    if crlf {
+        text.replace("\r\n", "\n").replace('\n', "\r\n")
+    } else {
+        text
+    }
+}
+
+/// Creates a single edit replacing the lines that differ between `old` and
+/// `new`, so that the cursor and folds of the unchanged lines are preserved.
+fn diff_lines(old: &str, new: &str) -> Option<ArkTextEdit> {
+    if old == new {
+        return None;
+    }
+
+    let old_lines: Vec<&str> = old.split('\n').collect();
+    let new_lines: Vec<&str> = new.split('\n').collect();
+    let n_common = old_lines.len().min(new_lines.len());
+
+    // The last line of `old` is never part of the prefix so that the edit
+    // always has a line to replace
+    let prefix = std::iter::zip(&old_lines, &new_lines)
+        .take(n_common - 1)
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    let suffix = std::iter::zip(old_lines.iter().rev(), new_lines.iter().rev())
+        .take(n_common - prefix)
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    let end = if suffix > 0 {
+        ArkPoint {
+            row: old_lines.len() - suffix,
+            column: 0,
+        }
+    } else {
+        let row = old_lines.len() - 1;
+        ArkPoint {
+            row,
+            column: old_lines[row].len(),
+        }
+    };
+
+    let lines = &new_lines[prefix..new_lines.len() - suffix];
+    let mut new_text = lines.join("\n");
+    if suffix > 0 && !lines.is_empty() {
+        new_text.push('\n');
+    }
+
+    Some(ArkTextEdit {
+        range: ArkRange {
+            start: ArkPoint {
+                row: prefix,
+                column: 0,
+            },
+            end,
+        },
+        new_text,
+    })
+}
+
+/// Layout of formatted code
+#[derive(Debug)]
+enum Doc {
+    Text(String),
+    /// A space, or a line break when the enclosing group doesn't fit
+    Line,
+    /// Nothing, or a line break when the enclosing group doesn't fit
+    SoftLine,
+    /// A line break that also breaks the enclosing groups
+    HardLine,
+    /// A line break followed by an empty line
+    BlankLine,
+    /// Indents the line breaks of its contents when the enclosing group is
+    /// broken. This lets a braced argument hug the parentheses of a call.
+    Indent(Box<Doc>),
+    Concat(Vec<Doc>),
+    /// Contents that are laid out on a single line if they fit
+    Group {
+        contents: Box<Doc>,
+        force_break: bool,
+    },
+    /// Braced expression. Its lines are always broken but it doesn't break
+    /// the enclosing groups.
+    Block(Box<Doc>),
+}
+
+impl Doc {
+    fn text(text: impl Into<String>) -> Self {
+        Doc::Text(text.into())
+    }
+
+    fn concat(docs: Vec<Doc>) -> Self {
+        Doc::Concat(docs)
+    }
+
+    fn indent(doc: Doc) -> Self {
+        Doc::Indent(Box::new(doc))
+    }
+
+    fn block(doc: Doc) -> Self {
+        Doc::Block(Box::new(doc))
+    }
+
+    fn group_with(doc: Doc, force_break: bool) -> Self {
+        let force_break = force_break || doc.must_break();
+        Doc::Group {
+            contents: Box::new(doc),
+            force_break,
+        }
+    }
+
+    fn must_break(&self) -> bool {
+        match self {
+            Doc::HardLine | Doc::BlankLine => true,
+            Doc::Text(_) | Doc::Line | Doc::SoftLine | Doc::Block(_) => false,
+            Doc::Indent(doc) => doc.must_break(),
+            Doc::Concat(docs) => docs.iter().any(|doc| doc.must_break()),
+            Doc::Group { force_break, .. } => *force_break,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+struct Command<'a> {
+    indent: usize,
+    mode: Mode,
+    doc: &'a Doc,
+}
+
+struct Printer<'a> {
+    config: &'a IndentationConfig,
+    output: String,
+    column: usize,
+    // Indentation is written lazily with the first text of a line so that
+    // empty lines stay empty
+    line_empty: bool,
+    line_indent: usize,
+}
+
+fn print(doc: &Doc, indent: usize, config: &IndentationConfig) -> String {
+    let mut printer = Printer {
+        config,
+        output: String::new(),
+        column: indent,
+        line_empty: true,
+        line_indent: indent,
+    };
+
+    let mut commands = vec![Command {
+        indent,
+        mode: Mode::Break,
+        doc,
+    }];
+
+    while let Some(Command { indent, mode, doc }) = commands.pop() {
+        match doc {
+            Doc::Text(text) => printer.text(text),
+            Doc::Line => match mode {
+                Mode::Flat => printer.text(" "),
+                Mode::Break => printer.newline(indent),
+            },
+            Doc::SoftLine => {
+                if mode == Mode::Break {
+                    printer.newline(indent);
+                }
+            },
+            Doc::HardLine => printer.newline(indent),
+            Doc::BlankLine => printer.blank_line(indent),
+            Doc::Indent(doc) => {
+                let indent = match mode {
+                    Mode::Flat => indent,
+                    Mode::Break => indent + config.indent_size,
+                };
+                commands.push(Command { indent, mode, doc });
+            },
+            Doc::Concat(docs) => {
+                commands.extend(docs.iter().rev().map(|doc| Command { indent, mode, doc }));
+            },
+            Doc::Group {
+                contents,
+                force_break,
+            } => {
+                let width = LINE_WIDTH as isize - printer.column as isize;
+                let mode = if *force_break {
+                    Mode::Break
+                } else if mode == Mode::Flat || fits(contents, &commands, width) {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                commands.push(Command {
+                    indent,
+                    mode,
+                    doc: contents,
+                });
+            },
+            Doc::Block(doc) => commands.push(Command {
+                indent,
+                mode: Mode::Break,
+                doc,
+            }),
+        }
+    }
+
+    printer.newline(0);
+    printer.output
+}
+
+/// Whether `doc` fits on the rest of the line when laid out flat, along with
+/// the commands that follow it up to their first possible line break
+fn fits(doc: &Doc, rest: &[Command], mut width: isize) -> bool {
+    let mut stack = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    while width >= 0 {
+        let (mode, doc) = match stack.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some(command) => (command.mode, command.doc),
+                None => return true,
+            },
+        };
+
+        match doc {
+            Doc::Text(text) => match text.split_once('\n') {
+                Some((first, _)) => return width >= first.chars().count() as isize,
+                None => width -= text.chars().count() as isize,
+            },
+            Doc::Line => match mode {
+                Mode::Flat => width -= 1,
+                Mode::Break => return true,
+            },
+            Doc::SoftLine => {
+                if mode == Mode::Break {
+                    return true;
+                }
+            },
+            Doc::HardLine | Doc::BlankLine => return true,
+            Doc::Indent(doc) => stack.push((mode, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (mode, doc))),
+            Doc::Group {
+                contents,
+                force_break,
+            } => {
+                let mode = if *force_break { Mode::Break } else { mode };
+                stack.push((mode, contents));
+            },
+            Doc::Block(doc) => stack.push((Mode::Break, doc)),
+        }
+    }
+
+    false
+}
+
+impl Printer<'_> {
+    fn text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        if self.line_empty {
+            self.output
+                .push_str(&new_line_indent(self.config, self.line_indent));
+            self.line_empty = false;
+        }
+        self.output.push_str(text);
+
+        match text.rsplit_once('\n') {
+            Some((_, last)) => self.column = last.chars().count(),
+            None => self.column += text.chars().count(),
+        }
+    }
+
+    fn newline(&mut self, indent: usize) {
+        if !self.line_empty {
+            let len = self.output.trim_end_matches([' ', '\t']).len();
+            self.output.truncate(len);
+            if !self.output.is_empty() && !self.output.ends_with('\n') {
+                self.output.push('\n');
+            }
+            self.line_empty = true;
+        }
+        self.line_indent = indent;
+        self.column = indent;
+    }
+
+    fn blank_line(&mut self, indent: usize) {
+        self.newline(indent);
+        if !self.output.is_empty() && !self.output.ends_with("\n\n") {
+            self.output.push('\n');
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum OperatorLayout {
+    /// No spaces around the operator, like `x^2` or `1:n`
+    Tight,
+    /// Spaces around the operator, which is never followed by a line break
+    Spaced,
+    /// Spaces around the operator, which may be followed by a line break.
+    /// Successive operators of the same class form a chain whose lines are
+    /// all broken or none of them.
+    Chain(u8),
+}
+
+fn operator_layout(operator: &str) -> OperatorLayout {
+    match operator {
+        "^" | "**" | ":" => OperatorLayout::Tight,
+        "|>" => OperatorLayout::Chain(0),
+        operator if operator.starts_with('%') => OperatorLayout::Chain(0),
+        "||" | "|" => OperatorLayout::Chain(1),
+        "&&" | "&" => OperatorLayout::Chain(2),
+        "+" | "-" => OperatorLayout::Chain(3),
+        "*" | "/" => OperatorLayout::Chain(4),
+        // Assignments, comparisons, formulas and help
+        _ => OperatorLayout::Spaced,
+    }
+}
+
+enum ListStyle {
+    /// Arguments of calls and subsets, with the closing delimiter on its own
+    /// line when broken
+    Call,
+    /// Parameters of function definitions, which are indented twice when
+    /// broken so that they stand out from the body
+    Parameters,
+}
+
+struct Builder<'a> {
+    contents: &'a Rope,
+}
+
+impl Builder<'_> {
+    fn text(&self, node: Node) -> String {
+        node_text(&node, self.contents).unwrap_or_default()
+    }
+
+    fn node(&self, node: Node) -> Doc {
+        match node.node_type() {
+            NodeType::Program => self.statements(&statement_nodes(node)),
+            NodeType::BracedExpression => self.braced_expression(node),
+            NodeType::FunctionDefinition => self.function_definition(node),
+            NodeType::IfStatement |
+            NodeType::ForStatement |
+            NodeType::WhileStatement |
+            NodeType::RepeatStatement => self.control_flow(node),
+            NodeType::Call | NodeType::Subset | NodeType::Subset2 => self.call(node),
+            NodeType::Argument | NodeType::Parameter => self.argument(node),
+            NodeType::UnaryOperator(_) => self.unary_operator(node),
+            NodeType::BinaryOperator(_) => self.binary_operator(node),
+            NodeType::ParenthesizedExpression |
+            NodeType::ExtractOperator(_) |
+            NodeType::NamespaceOperator(_) => self.tight(node),
+            NodeType::Comment => Doc::text(self.comment_text(node)),
+            // Leaves and nodes that are kept verbatim, like strings
+            _ => Doc::text(self.text(node)),
+        }
+    }
+
+    fn statements(&self, nodes: &[Node]) -> Doc {
+        let mut docs = Vec::new();
+        let mut prev: Option<Node> = None;
+
+        for node in nodes {
+            if let Some(prev) = prev {
+                let prev_row = prev.end_position().row;
+                let row = node.start_position().row;
+
+                if node.is_comment() && row == prev_row {
+                    docs.push(Doc::text(" "));
+                } else if row > prev_row + 1 {
+                    docs.push(Doc::BlankLine);
+                } else {
+                    docs.push(Doc::HardLine);
+                }
+            }
+
+            docs.push(self.node(*node));
+            prev = Some(*node);
+        }
+
+        Doc::concat(docs)
+    }
+
+    fn braced_expression(&self, node: Node) -> Doc {
+        let statements = statement_nodes(node);
+        let Some(first) = statements.first() else {
+            return Doc::text("{}");
+        };
+
+        // A comment on the line of the opening brace stays there
+        let (open, statements) =
+            if first.is_comment() && first.start_position().row == node.start_position().row {
+                let open = format!("{{ {}", self.comment_text(*first));
+                (Doc::text(open), &statements[1..])
+            } else {
+                (Doc::text("{"), &statements[..])
+            };
+
+        let body = if statements.is_empty() {
+            Doc::concat(vec![])
+        } else {
+            Doc::concat(vec![Doc::HardLine, self.statements(statements)])
+        };
+
+        Doc::block(Doc::concat(vec![
+            open,
+            Doc::indent(body),
+            Doc::HardLine,
+            Doc::text("}"),
+        ]))
+    }
+
+    fn function_definition(&self, node: Node) -> Doc {
+        let docs = children(node)
+            .into_iter()
+            .map(|child| match child.kind() {
+                "parameters" => self.delimited(child, ListStyle::Parameters),
+                "comment" => self.comment(child),
+                _ if child.is_named() => Doc::concat(vec![Doc::text(" "), self.node(child)]),
+                _ => Doc::text(self.text(child)),
+            })
+            .collect();
+
+        Doc::concat(docs)
+    }
+
+    fn control_flow(&self, node: Node) -> Doc {
+        let mut docs = Vec::new();
+        let mut prev = "";
+
+        for child in children(node) {
+            if child.is_comment() {
+                docs.push(self.comment(child));
+                continue;
+            }
+
+            let kind = child.kind();
+            match kind {
+                "if" | "for" | "while" => docs.push(Doc::text(format!("{kind} "))),
+                "in" | "else" => docs.push(Doc::text(format!(" {kind} "))),
+                _ if child.is_named() => {
+                    // The body is separated from the header
+                    if matches!(prev, ")" | "repeat") {
+                        docs.push(Doc::text(" "));
+                    }
+                    docs.push(self.node(child));
+                },
+                _ => docs.push(Doc::text(self.text(child))),
+            }
+            prev = kind;
+        }
+
+        Doc::concat(docs)
+    }
+
+    fn call(&self, node: Node) -> Doc {
+        let docs = children(node)
+            .into_iter()
+            .map(|child| match child.kind() {
+                "arguments" => self.delimited(child, ListStyle::Call),
+                "comment" => self.comment(child),
+                _ => self.node(child),
+            })
+            .collect();
+
+        Doc::concat(docs)
+    }
+
+    fn delimited(&self, node: Node, style: ListStyle) -> Doc {
+        let mut open = String::new();
+        let mut close = String::new();
+        let mut open_comment = None;
+        let mut items = Vec::new();
+        let mut first_item_row = None;
+
+        for child in children(node) {
+            if !child.is_named() {
+                if items.is_empty() && close.is_empty() {
+                    open.push_str(&self.text(child));
+                } else {
+                    close.push_str(&self.text(child));
+                }
+                continue;
+            }
+
+            match child.kind() {
+                "comma" => {
+                    items.push(Doc::text(","));
+
+                    // A trailing comment breaks the line itself
+                    let next = child.next_sibling();
+                    if next.is_some_and(|next| is_trailing_comment(next)) {
+                        continue;
+                    }
+
+                    // Empty arguments at the end, like in `x[1, ]`
+                    if next.map_or(true, |next| !next.is_named()) {
+                        items.push(Doc::text(" "));
+                    } else {
+                        items.push(Doc::Line);
+                    }
+                },
+                "comment" => {
+                    if items.is_empty() && is_trailing_comment(child) {
+                        open_comment = Some(self.comment_text(child));
+                    } else {
+                        items.push(self.comment(child));
+                    }
+                },
+                _ => {
+                    first_item_row.get_or_insert(child.start_position().row);
+                    items.push(self.node(child));
+                },
+            }
+        }
+
+        if items.is_empty() && open_comment.is_none() {
+            return Doc::text(open + &close);
+        }
+
+        let mut open = vec![Doc::text(open)];
+        if let Some(comment) = &open_comment {
+            open.push(Doc::text(format!(" {comment}")));
+        }
+
+        let mut contents = vec![Doc::SoftLine];
+        contents.append(&mut items);
+
+        match style {
+            ListStyle::Call => {
+                // Arguments starting on a new line stay broken, which allows
+                // to opt out of hugging
+                let force_break = open_comment.is_some() ||
+                    first_item_row.is_some_and(|row| row > node.start_position().row);
+
+                open.append(&mut vec![
+                    Doc::indent(Doc::concat(contents)),
+                    Doc::SoftLine,
+                    Doc::text(close),
+                ]);
+                Doc::group_with(Doc::concat(open), force_break)
+            },
+            ListStyle::Parameters => {
+                open.append(&mut vec![
+                    Doc::indent(Doc::indent(Doc::concat(contents))),
+                    Doc::text(close),
+                ]);
+                Doc::group_with(Doc::concat(open), open_comment.is_some())
+            },
+        }
+    }
+
+    fn argument(&self, node: Node) -> Doc {
+        let docs = children(node)
+            .into_iter()
+            .map(|child| match child.kind() {
+                "=" => Doc::text(" = "),
+                "comment" => self.comment(child),
+                _ => self.node(child),
+            })
+            .collect();
+
+        Doc::concat(docs)
+    }
+
+    fn unary_operator(&self, node: Node) -> Doc {
+        let operator = node.child_by_field_name("operator");
+        let mut docs = Vec::new();
+
+        for child in children(node) {
+            if Some(child) == operator {
+                let operator = self.text(child);
+
+                // One-sided formulas are spaced like two-sided ones unless
+                // they consist of a single term, like `~x`
+                let spaced = operator == "~" &&
+                    node.child_by_field_name("rhs")
+                        .is_some_and(|rhs| rhs.is_binary_operator());
+
+                docs.push(Doc::text(operator));
+                if spaced {
+                    docs.push(Doc::text(" "));
+                }
+            } else if child.is_comment() {
+                docs.push(self.comment(child));
+            } else {
+                docs.push(self.node(child));
+            }
+        }
+
+        Doc::concat(docs)
+    }
+
+    fn binary_operator(&self, node: Node) -> Doc {
+        let operator = node.child_by_field_name("operator");
+        let layout = operator.map_or(OperatorLayout::Spaced, |operator| {
+            operator_layout(&self.text(operator))
+        });
+
+        if let OperatorLayout::Chain(class) = layout {
+            return self.chain(node, class);
+        }
+
+        let docs = children(node)
+            .into_iter()
+            .map(|child| {
+                if Some(child) == operator {
+                    let operator = self.text(child);
+                    match layout {
+                        OperatorLayout::Tight => Doc::text(operator),
+                        _ => Doc::text(format!(" {operator} ")),
+                    }
+                } else if child.is_comment() {
+                    self.comment(child)
+                } else {
+                    self.node(child)
+                }
+            })
+            .collect();
+
+        Doc::concat(docs)
+    }
+
+    /// Lays out a chain of operators, like a pipeline or the layers of a
+    /// ggplot. Either all operators are followed by a line break or none of
+    /// them. The chain stays broken if it was broken in the source.
+    fn chain(&self, node: Node, class: u8) -> Doc {
+        // Binary operators are left-associative, so the chain is nested in
+        // the left-hand sides
+        let mut links = vec![node];
+        loop {
+            let lhs = links
+                .last()
+                .and_then(|link| link.child_by_field_name("lhs"));
+            match lhs {
+                Some(lhs) if self.is_chain_link(lhs, class) => links.push(lhs),
+                _ => break,
+            }
+        }
+        links.reverse();
+
+        let mut head = Vec::new();
+        let mut tail = Vec::new();
+        let mut force_break = false;
+
+        for (i, link) in links.iter().enumerate() {
+            let lhs = link.child_by_field_name("lhs");
+            let operator = link.child_by_field_name("operator");
+            let rhs = link.child_by_field_name("rhs");
+
+            if let (Some(operator), Some(rhs)) = (operator, rhs) {
+                force_break |= rhs.start_position().row > operator.end_position().row;
+            }
+
+            for child in children(*link) {
+                if Some(child) == lhs {
+                    // The left-hand side of the other links is the previous link
+                    if i == 0 {
+                        head.push(self.node(child));
+                    }
+                } else if Some(child) == operator {
+                    tail.push(Doc::text(format!(" {}", self.text(child))));
+                } else if child.is_comment() {
+                    let docs = if i == 0 && tail.is_empty() {
+                        &mut head
+                    } else {
+                        &mut tail
+                    };
+                    docs.push(self.comment(child));
+                } else {
+                    tail.push(Doc::Line);
+                    tail.push(self.node(child));
+                }
+            }
+        }
+
+        head.push(Doc::indent(Doc::concat(tail)));
+        Doc::group_with(Doc::concat(head), force_break)
+    }
+
+    fn is_chain_link(&self, node: Node, class: u8) -> bool {
+        if !node.is_binary_operator() {
+            return false;
+        }
+        let Some(operator) = node.child_by_field_name("operator") else {
+            return false;
+        };
+        operator_layout(&self.text(operator)) == OperatorLayout::Chain(class)
+    }
+
+    fn tight(&self, node: Node) -> Doc {
+        let docs = children(node)
+            .into_iter()
+            .map(|child| {
+                if child.is_comment() {
+                    self.comment(child)
+                } else if child.is_named() {
+                    self.node(child)
+                } else {
+                    Doc::text(self.text(child))
+                }
+            })
+            .collect();
+
+        Doc::concat(docs)
+    }
+
+    /// A comment within an expression. Comments end the line, and comments
+    /// that were on their own line stay on their own line.
+    fn comment(&self, node: Node) -> Doc {
+        let text = Doc::text(self.comment_text(node));
+
+        if is_trailing_comment(node) {
+            Doc::concat(vec![Doc::text(" "), text, Doc::HardLine])
+        } else {
+            Doc::concat(vec![Doc::HardLine, text, Doc::HardLine])
+        }
+    }
+
+    fn comment_text(&self, node: Node) -> String {
+        self.text(node).trim_end().to_string()
+    }
+}
+
+fn is_trailing_comment(node: Node) -> bool {
+    node.is_comment() &&
+        node.prev_sibling()
+            .is_some_and(|prev| prev.end_position().row == node.start_position().row)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lsp::config::IndentStyle;
+    use crate::lsp::config::IndentationConfig;
+    use crate::lsp::documents::Document;
+    use crate::lsp::format::format_document;
+    use crate::lsp::format::format_range;
+    use crate::lsp::offset::apply_text_edits;
+    use crate::lsp::offset::ArkPoint;
+    use crate::lsp::offset::ArkRange;
+
+    fn format(text: &str) -> String {
+        let doc = Document::new(text, None);
+        let mut text = text.to_string();
+
+        if let Some(edits) = format_document(&doc).unwrap() {
+            apply_text_edits(edits, &mut text).unwrap();
+        }
+        text
+    }
+
+    #[test]
+    fn test_format_spacing() {
+        assert_eq!(format("x<-c(1,2)\ny=x+1"), "x <- c(1, 2)\ny = x + 1\n");
+        assert_eq!(
+            format("a[1,]\nb:c\nx^2\npkg::fn(x$y)\n-x\n!y\n"),
+            "a[1, ]\nb:c\nx^2\npkg::fn(x$y)\n-x\n!y\n"
+        );
+        assert_eq!(format("x< -1"), "x < -1\n");
+    }
+
+    #[test]
+    fn test_format_unchanged() {
+        let doc = Document::new("x <- 1\n", None);
+        assert!(format_document(&doc).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_format_syntax_error() {
+        let doc = Document::new("f(x<-1", None);
+        assert!(format_document(&doc).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_format_braces() {
+        assert_eq!(
+            format("f <- function(x,y=2){x+y}"),
+            "f <- function(x, y = 2) {\n  x + y\n}\n"
+        );
+        assert_eq!(
+            format("if(a){b}else{c}"),
+            "if (a) {\n  b\n} else {\n  c\n}\n"
+        );
+        assert_eq!(format("f <- function() {}"), "f <- function() {}\n");
+    }
+
+    #[test]
+    fn test_format_long_call() {
+        assert_eq!(
+            format("result <- some_function(first_argument = 1, second_argument = 2, third_argument = 3)"),
+            "result <- some_function(\n  first_argument = 1,\n  second_argument = 2,\n  third_argument = 3\n)\n"
+        );
+    }
+
+    #[test]
+    fn test_format_long_parameters() {
+        assert_eq!(
+            format("long_function_name <- function(first_parameter = 1, second_parameter = 2, third = 3) {\n  NULL\n}"),
+            "long_function_name <- function(\n    first_parameter = 1,\n    second_parameter = 2,\n    third = 3) {\n  NULL\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_pipe_chain() {
+        assert_eq!(format("x|>f()|>g()"), "x |> f() |> g()\n");
+        assert_eq!(
+            format("data |> filter(value > 10) |> mutate(ratio = value / total) |> summarise(n = n())"),
+            "data |>\n  filter(value > 10) |>\n  mutate(ratio = value / total) |>\n  summarise(n = n())\n"
+        );
+
+        // Chains broken in the source stay broken
+        assert_eq!(format("x %>%\nf()"), "x %>%\n  f()\n");
+    }
+
+    #[test]
+    fn test_format_hugging() {
+        assert_eq!(
+            format("test_that(\"x\", {expect_true(TRUE)})"),
+            "test_that(\"x\", {\n  expect_true(TRUE)\n})\n"
+        );
+    }
+
+    #[test]
+    fn test_format_comments() {
+        assert_eq!(
+            format("x <- 1 # one\n\n\n# two\ny <- function() { # open\n  2\n}\n"),
+            "x <- 1 # one\n\n# two\ny <- function() { # open\n  2\n}\n"
+        );
+        assert_eq!(
+            format("list(a = 1, # first\nb = 2)"),
+            "list(\n  a = 1, # first\n  b = 2\n)\n"
+        );
+    }
+
+    #[test]
+    fn test_format_idempotent() {
+        let text = "f <- function(x) {\n  if (x) {\n    y <- x |> g() # comment\n  }\n\n  list(a = 1, b = c(2, 3))[[1]]\n}\n";
+        assert_eq!(format(text), text);
+        assert_eq!(
+            format(&format("f<-function(x){for(i in x)print(i)}")),
+            "f <- function(x) {\n  for (i in x) print(i)\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_tabs() {
+        let mut doc = Document::new("f <- function() {\nx\n}", None);
+        doc.config.indent = IndentationConfig {
+            indent_style: IndentStyle::Tab,
+            indent_size: 4,
+            tab_width: 4,
+        };
+
+        let mut text = doc.contents.to_string();
+        let edits = format_document(&doc).unwrap().unwrap();
+        apply_text_edits(edits, &mut text).unwrap();
+        assert_eq!(text, "f <- function() {\n\tx\n}\n");
+    }
+
+    #[test]
+    fn test_format_range() {
+        let mut text = String::from("x<-1\nf <- function() {\ny<-2\n    z<-3\n}\n");
+        let doc = Document::new(&text, None);
+
+        let range = ArkRange {
+            start: ArkPoint { row: 2, column: 0 },
+            end: ArkPoint { row: 3, column: 8 },
+        };
+        let edits = format_range(&doc, range).unwrap().unwrap();
+        apply_text_edits(edits, &mut text).unwrap();
+        assert_eq!(text, "x<-1\nf <- function() {\n  y <- 2\n  z <- 3\n}\n");
+    }
+}
@@ -11,26 +11,54 @@ use once_cell::sync::Lazy;
 use serde_json::Value;
 use stdext::unwrap;
 use struct_field_names_as_array::FieldNamesAsArray;
+use tower_lsp::lsp_types::CallHierarchyIncomingCall;
+use tower_lsp::lsp_types::CallHierarchyIncomingCallsParams;
+use tower_lsp::lsp_types::CallHierarchyItem;
+use tower_lsp::lsp_types::CallHierarchyOutgoingCall;
+use tower_lsp::lsp_types::CallHierarchyOutgoingCallsParams;
+use tower_lsp::lsp_types::CallHierarchyPrepareParams;
+use tower_lsp::lsp_types::CodeActionParams;
+use tower_lsp::lsp_types::CodeActionResponse;
 use tower_lsp::lsp_types::CompletionItem;
 use tower_lsp::lsp_types::CompletionParams;
 use tower_lsp::lsp_types::CompletionResponse;
+use tower_lsp::lsp_types::DidChangeWatchedFilesRegistrationOptions;
+use tower_lsp::lsp_types::DocumentFormattingParams;
+use tower_lsp::lsp_types::DocumentHighlight;
+use tower_lsp::lsp_types::DocumentHighlightParams;
 use tower_lsp::lsp_types::DocumentOnTypeFormattingParams;
+use tower_lsp::lsp_types::DocumentRangeFormattingParams;
 use tower_lsp::lsp_types::DocumentSymbolParams;
 use tower_lsp::lsp_types::DocumentSymbolResponse;
+use tower_lsp::lsp_types::FileSystemWatcher;
+use tower_lsp::lsp_types::FoldingRange;
+use tower_lsp::lsp_types::FoldingRangeParams;
+use tower_lsp::lsp_types::GlobPattern;
 use tower_lsp::lsp_types::GotoDefinitionParams;
 use tower_lsp::lsp_types::GotoDefinitionResponse;
 use tower_lsp::lsp_types::Hover;
 use tower_lsp::lsp_types::HoverContents;
 use tower_lsp::lsp_types::HoverParams;
+use tower_lsp::lsp_types::InlayHint;
+use tower_lsp::lsp_types::InlayHintParams;
 use tower_lsp::lsp_types::Location;
 use tower_lsp::lsp_types::MessageType;
+use tower_lsp::lsp_types::PrepareRenameResponse;
 use tower_lsp::lsp_types::ReferenceParams;
 use tower_lsp::lsp_types::Registration;
+use tower_lsp::lsp_types::RenameParams;
 use tower_lsp::lsp_types::SelectionRange;
 use tower_lsp::lsp_types::SelectionRangeParams;
+use tower_lsp::lsp_types::SemanticTokens;
+use tower_lsp::lsp_types::SemanticTokensDelta;
+use tower_lsp::lsp_types::SemanticTokensDeltaParams;
+use tower_lsp::lsp_types::SemanticTokensFullDeltaResult;
+use tower_lsp::lsp_types::SemanticTokensParams;
+use tower_lsp::lsp_types::SemanticTokensResult;
 use tower_lsp::lsp_types::SignatureHelp;
 use tower_lsp::lsp_types::SignatureHelpParams;
 use tower_lsp::lsp_types::SymbolInformation;
+use tower_lsp::lsp_types::TextDocumentPositionParams;
 use tower_lsp::lsp_types::TextEdit;
 use tower_lsp::lsp_types::WorkspaceEdit;
 use tower_lsp::lsp_types::WorkspaceSymbolParams;
@@ -39,23 +67,40 @@ use tracing::Instrument;
 use tree_sitter::Point;
 
 use crate::lsp;
+use crate::lsp::call_hierarchy::incoming_calls;
+use crate::lsp::call_hierarchy::outgoing_calls;
+use crate::lsp::call_hierarchy::prepare_call_hierarchy;
+use crate::lsp::code_actions::code_actions;
 use crate::lsp::completions::provide_completions;
 use crate::lsp::completions::resolve_completion;
 use crate::lsp::config::VscDiagnosticsConfig;
 use crate::lsp::config::VscDocumentConfig;
+use crate::lsp::config::VscInlayHintsConfig;
 use crate::lsp::definitions::goto_definition;
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::document_highlight::document_highlight;
 use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::folding_range::folding_range;
+use crate::lsp::format::format_document;
+use crate::lsp::format::format_range;
 use crate::lsp::help_topic::help_topic;
 use crate::lsp::help_topic::HelpTopicParams;
 use crate::lsp::help_topic::HelpTopicResponse;
 use crate::lsp::hover::r_hover;
+use crate::lsp::hover::workspace_hover;
 use crate::lsp::indent::indent_edit;
+use crate::lsp::inlay_hints::inlay_hints;
 use crate::lsp::main_loop::LspState;
+use crate::lsp::offset::ArkRange;
 use crate::lsp::offset::IntoLspOffset;
 use crate::lsp::references::find_references;
+use crate::lsp::rename::prepare_rename;
+use crate::lsp::rename::rename;
 use crate::lsp::selection_range::convert_selection_range_from_tree_sitter_to_lsp;
 use crate::lsp::selection_range::selection_range;
+use crate::lsp::semantic_tokens::semantic_tokens;
+use crate::lsp::semantic_tokens::semantic_tokens_edits;
+use crate::lsp::semantic_tokens::semantic_tokens_result_id;
 use crate::lsp::signature_help::r_signature_help;
 use crate::lsp::state::WorldState;
 use crate::lsp::statement_range::statement_range;
@@ -102,8 +147,33 @@ pub(crate) async fn handle_initialized(
             VscDiagnosticsConfig::section_from_key,
         );
 
+        let mut config_inlay_hints_regs: Vec<Registration> = collect_regs(
+            VscInlayHintsConfig::FIELD_NAMES_AS_ARRAY.to_vec(),
+            VscInlayHintsConfig::section_from_key,
+        );
+
         regs.append(&mut config_document_regs);
         regs.append(&mut config_diagnostics_regs);
+        regs.append(&mut config_inlay_hints_regs);
+    }
+
+    if lsp_state.needs_registration.did_change_watched_files {
+        // Watch the package metadata so we can reload the package model of
+        // workspace folders when their imports change
+        let watchers = ["**/DESCRIPTION", "**/NAMESPACE"]
+            .into_iter()
+            .map(|pattern| FileSystemWatcher {
+                glob_pattern: GlobPattern::String(pattern.to_string()),
+                kind: None,
+            })
+            .collect();
+        let options = DidChangeWatchedFilesRegistrationOptions { watchers };
+
+        regs.push(Registration {
+            id: uuid::Uuid::new_v4().to_string(),
+            method: String::from("workspace/didChangeWatchedFiles"),
+            register_options: Some(serde_json::to_value(options)?),
+        });
     }
 
     client
@@ -172,13 +242,20 @@ pub(crate) fn handle_completion(
     let position = params.text_document_position.position;
     let point = convert_position_to_point(&document.contents, position);
 
+    if !document.is_r_row(point.row) {
+        return Ok(None);
+    }
+
     let trigger = params.context.and_then(|ctxt| ctxt.trigger_character);
 
     // Build the document context.
     let context = DocumentContext::new(&document, point, trigger);
     lsp::log_info!("Completion context: {:#?}", context);
 
-    let completions = r_task(|| provide_completions(&context, state))?;
+    // Completions within a package take its imports into account
+    let package = state.workspace.package(&uri);
+
+    let completions = r_task(|| provide_completions(&context, state, package))?;
 
     if !completions.is_empty() {
         Ok(Some(CompletionResponse::Array(completions)))
@@ -206,9 +283,25 @@ pub(crate) fn handle_hover(
     let position = params.text_document_position_params.position;
     let point = convert_position_to_point(&document.contents, position);
 
+    if !document.is_r_row(point.row) {
+        return Ok(None);
+    }
+
     // build document context
     let context = DocumentContext::new(&document, point, None);
 
+    // prefer the roxygen documentation of workspace functions
+    match workspace_hover(&context, state) {
+        Ok(Some(result)) => {
+            return Ok(Some(Hover {
+                contents: HoverContents::Markup(result),
+                range: None,
+            }))
+        },
+        Ok(None) => {},
+        Err(err) => lsp::log_error!("{err:?}"),
+    }
+
     // request hover information
     let result = r_task(|| unsafe { r_hover(&context) });
 
@@ -241,6 +334,10 @@ pub(crate) fn handle_signature_help(
     let position = params.text_document_position_params.position;
     let point = convert_position_to_point(&document.contents, position);
 
+    if !document.is_r_row(point.row) {
+        return Ok(None);
+    }
+
     let context = DocumentContext::new(&document, point, None);
 
     // request signature help
@@ -309,6 +406,115 @@ pub(crate) fn handle_selection_range(
     Ok(Some(selections))
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_folding_range(
+    params: FoldingRangeParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<FoldingRange>>> {
+    let uri = params.text_document.uri;
+    let document = state.get_document(&uri)?;
+    folding_range(document).map(Some)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_semantic_tokens_full(
+    params: SemanticTokensParams,
+    lsp_state: &mut LspState,
+    state: &WorldState,
+) -> anyhow::Result<Option<SemanticTokensResult>> {
+    let uri = params.text_document.uri;
+    let document = state.get_document(&uri)?;
+
+    let tokens = SemanticTokens {
+        result_id: Some(semantic_tokens_result_id(
+            lsp_state.semantic_tokens.get(&uri),
+        )),
+        data: semantic_tokens(document, state)?,
+    };
+
+    lsp_state.semantic_tokens.insert(uri, tokens.clone());
+    Ok(Some(SemanticTokensResult::Tokens(tokens)))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_semantic_tokens_full_delta(
+    params: SemanticTokensDeltaParams,
+    lsp_state: &mut LspState,
+    state: &WorldState,
+) -> anyhow::Result<Option<SemanticTokensFullDeltaResult>> {
+    let uri = params.text_document.uri;
+    let document = state.get_document(&uri)?;
+
+    let previous = lsp_state.semantic_tokens.get(&uri);
+    let tokens = SemanticTokens {
+        result_id: Some(semantic_tokens_result_id(previous)),
+        data: semantic_tokens(document, state)?,
+    };
+
+    // Send edits if the client holds the tokens we last sent, otherwise fall
+    // back to the full set of tokens
+    let result = match previous {
+        Some(previous) if previous.result_id.as_ref() == Some(&params.previous_result_id) => {
+            SemanticTokensFullDeltaResult::TokensDelta(SemanticTokensDelta {
+                result_id: tokens.result_id.clone(),
+                edits: semantic_tokens_edits(&previous.data, &tokens.data),
+            })
+        },
+        _ => SemanticTokensFullDeltaResult::Tokens(tokens.clone()),
+    };
+
+    lsp_state.semantic_tokens.insert(uri, tokens);
+    Ok(Some(result))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_inlay_hint(
+    params: InlayHintParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<InlayHint>>> {
+    let uri = params.text_document.uri;
+    let document = state.get_document(&uri)?;
+    inlay_hints(document, params.range, state).map(Some)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_prepare_call_hierarchy(
+    params: CallHierarchyPrepareParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyItem>>> {
+    prepare_call_hierarchy(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_incoming_calls(
+    params: CallHierarchyIncomingCallsParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+    incoming_calls(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_outgoing_calls(
+    params: CallHierarchyOutgoingCallsParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+    outgoing_calls(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_document_highlight(
+    params: DocumentHighlightParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<DocumentHighlight>>> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let document = state.get_document(&uri)?;
+
+    let position = params.text_document_position_params.position;
+    let point = convert_position_to_point(&document.contents, position);
+
+    Ok(Some(document_highlight(document, point)))
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn handle_references(
     params: ReferenceParams,
@@ -328,6 +534,40 @@ pub(crate) fn handle_references(
     }
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_prepare_rename(
+    params: TextDocumentPositionParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<PrepareRenameResponse>> {
+    let document = state.get_document(&params.text_document.uri)?;
+    let point = convert_position_to_point(&document.contents, params.position);
+
+    let range = prepare_rename(document, point)?;
+    Ok(range.map(PrepareRenameResponse::Range))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_rename(
+    params: RenameParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    rename(params, state)
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_code_action(
+    params: CodeActionParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<CodeActionResponse>> {
+    let actions = code_actions(params, state)?;
+
+    if actions.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(actions))
+    }
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn handle_statement_range(
     params: StatementRangeParams,
@@ -344,7 +584,19 @@ pub(crate) fn handle_statement_range(
 
     let row = point.row;
 
-    statement_range(root, contents, point, row)
+    // Statements of host documents don't extend outside of their R chunk
+    let Some(host) = &document.host else {
+        return statement_range(root, contents, point, row);
+    };
+    let Some(chunk) = host.r_chunk(row) else {
+        return Ok(None);
+    };
+
+    let response = statement_range(root, contents, point, row)?;
+    Ok(response.filter(|response| {
+        chunk.contains(response.range.start.line as usize) &&
+            chunk.contains(response.range.end.line as usize)
+    }))
 }
 
 #[tracing::instrument(level = "info", skip_all)]
@@ -374,6 +626,10 @@ pub(crate) fn handle_indent(
     let pos = ctxt.position;
     let point = convert_position_to_point(&doc.contents, pos);
 
+    if !doc.is_r_row(point.row) {
+        return Ok(None);
+    }
+
     let res = indent_edit(doc, point.row);
 
     Result::map(res, |opt| {
@@ -381,6 +637,42 @@ pub(crate) fn handle_indent(
     })
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_formatting(
+    params: DocumentFormattingParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let doc = state.get_document(&params.text_document.uri)?;
+
+    // The formatter only knows about R files
+    if doc.host.is_some() {
+        return Ok(None);
+    }
+
+    let edits = format_document(doc)?;
+    Ok(edits.map(|edits| edits.into_lsp_offset(&doc.contents)))
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn handle_range_formatting(
+    params: DocumentRangeFormattingParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<Vec<TextEdit>>> {
+    let doc = state.get_document(&params.text_document.uri)?;
+
+    if doc.host.is_some() {
+        return Ok(None);
+    }
+
+    let range = ArkRange {
+        start: convert_position_to_point(&doc.contents, params.range.start),
+        end: convert_position_to_point(&doc.contents, params.range.end),
+    };
+
+    let edits = format_range(doc, range)?;
+    Ok(edits.map(|edits| edits.into_lsp_offset(&doc.contents)))
+}
+
 // TODO: Should be in WorldState and updated via message passing
 pub static mut ARK_VDOCS: Lazy<DashMap<String, String>> = Lazy::new(|| DashMap::new());
 
@@ -5,6 +5,8 @@
 //
 //
 
+use std::path::Path;
+
 use anyhow::*;
 use stdext::unwrap;
 use stdext::unwrap::IntoResult;
@@ -13,7 +15,13 @@ use tower_lsp::lsp_types::MarkupKind;
 use tree_sitter::Node;
 
 use crate::lsp::document_context::DocumentContext;
+use crate::lsp::encoding::convert_position_to_point;
 use crate::lsp::help::RHtmlHelp;
+use crate::lsp::indexer;
+use crate::lsp::roxygen::function_assignment;
+use crate::lsp::roxygen::RoxygenBlock;
+use crate::lsp::state::with_document;
+use crate::lsp::state::WorldState;
 use crate::lsp::traits::rope::RopeExt;
 use crate::treesitter::NodeTypeExt;
 
@@ -66,6 +74,51 @@ fn hover_context(node: Node, context: &DocumentContext) -> Result<Option<HoverCo
     Ok(None)
 }
 
+/// Renders the roxygen documentation of a function defined in the workspace.
+/// This takes precedence over installed help since the workspace is likely
+/// more up to date than an installed version of the same package.
+pub(crate) fn workspace_hover(
+    context: &DocumentContext,
+    state: &WorldState,
+) -> anyhow::Result<Option<MarkupContent>> {
+    let node = &context.node;
+
+    if !node.is_identifier_or_string() {
+        return Ok(None);
+    }
+
+    let Some(HoverContext::Topic { topic }) = hover_context(*node, context)? else {
+        return Ok(None);
+    };
+
+    let Some((path, entry)) = indexer::find(topic.as_str()) else {
+        return Ok(None);
+    };
+    if !matches!(entry.data, indexer::IndexEntryData::Function { .. }) {
+        return Ok(None);
+    }
+
+    with_document(Path::new(&path), state, |document| {
+        let contents = &document.contents;
+        let point = convert_position_to_point(contents, entry.range.start);
+
+        let root = document.ast.root_node();
+        let mut cursor = root.walk();
+        let definition = root.children(&mut cursor).find(|node| {
+            function_assignment(node, contents).is_some() && node.start_position() == point
+        });
+
+        let Some(block) = definition.and_then(|node| RoxygenBlock::new(&node, contents)) else {
+            return Ok(None);
+        };
+
+        Ok(Some(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: block.markdown(),
+        }))
+    })
+}
+
 pub(crate) unsafe fn r_hover(context: &DocumentContext) -> anyhow::Result<Option<MarkupContent>> {
     // get the node
     let node = &context.node;
@@ -156,7 +156,7 @@ pub fn indent_edit(doc: &Document, line: usize) -> anyhow::Result<Option<Vec<Ark
     Ok(Some(edits))
 }
 
-fn brace_parent(node: tree_sitter::Node) -> tree_sitter::Node {
+pub(crate) fn brace_parent(node: tree_sitter::Node) -> tree_sitter::Node {
     let Some(parent) = node.parent() else {
         return node;
     };
@@ -7,6 +7,7 @@
 
 use std::collections::HashMap;
 use std::path::Path;
+use std::path::PathBuf;
 use std::result::Result::Ok;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -40,6 +41,9 @@ pub enum IndexEntryData {
         level: usize,
         title: String,
     },
+    Variable {
+        name: String,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -65,14 +69,9 @@ pub fn start(folders: Vec<String>) {
     lsp::log_info!("Initial indexing started");
 
     for folder in folders {
-        let walker = WalkDir::new(folder);
-        for entry in walker.into_iter().filter_entry(|e| filter_entry(e)) {
-            if let Ok(entry) = entry {
-                if entry.file_type().is_file() {
-                    if let Err(err) = index_file(entry.path()) {
-                        lsp::log_error!("Can't index file {:?}: {err:?}", entry.path());
-                    }
-                }
+        for path in r_files(Path::new(&folder)) {
+            if let Err(err) = index_file(&path) {
+                lsp::log_error!("Can't index file {:?}: {err:?}", path);
             }
         }
     }
@@ -118,12 +117,25 @@ fn insert(path: &Path, entry: IndexEntry) -> anyhow::Result<()> {
     let path = str_from_path(path)?;
 
     let index = index.entry(path.to_string()).or_default();
+
+    // Keep the function definition of `f` when it's reassigned further down,
+    // e.g. by `f <- memoise(f)`
+    if let IndexEntryData::Variable { .. } = entry.data {
+        if let Some(IndexEntry {
+            data: IndexEntryData::Function { .. },
+            ..
+        }) = index.get(&entry.key)
+        {
+            return Ok(());
+        }
+    }
+
     index.insert(entry.key.clone(), entry);
 
     Ok(())
 }
 
-fn clear(path: &Path) -> anyhow::Result<()> {
+pub fn clear(path: &Path) -> anyhow::Result<()> {
     let mut index = WORKSPACE_INDEX.lock().unwrap();
     let path = str_from_path(path)?;
 
@@ -166,13 +178,21 @@ pub fn filter_entry(entry: &DirEntry) -> bool {
     true
 }
 
-fn index_file(path: &Path) -> anyhow::Result<()> {
-    // only index R files
-    let ext = path.extension().unwrap_or_default();
-    if ext != "r" && ext != "R" {
-        return Ok(());
-    }
+/// The R files in `folder` and its subfolders, skipping ignored folders
+pub(crate) fn r_files(folder: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(folder)
+        .into_iter()
+        .filter_entry(|entry| filter_entry(entry))
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            let ext = path.extension().unwrap_or_default();
+            ext == "r" || ext == "R"
+        })
+}
 
+fn index_file(path: &Path) -> anyhow::Result<()> {
     // TODO: Handle document encodings here.
     // TODO: Check if there's an up-to-date buffer to be used.
     let contents = std::fs::read(path)?;
@@ -206,6 +226,10 @@ fn index_node(path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<Optio
         return Ok(Some(entry));
     }
 
+    if let Ok(Some(entry)) = index_variable(path, contents, node) {
+        return Ok(Some(entry));
+    }
+
     if let Ok(Some(entry)) = index_comment(path, contents, node) {
         return Ok(Some(entry));
     }
@@ -263,26 +287,45 @@ fn index_function(
     }))
 }
 
+fn index_variable(
+    _path: &Path,
+    contents: &Rope,
+    node: &Node,
+) -> anyhow::Result<Option<IndexEntry>> {
+    // Check for assignment.
+    matches!(
+        node.node_type(),
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment)
+    )
+    .into_result()?;
+
+    // Check for identifier on left-hand side. Function definitions on the
+    // right-hand side have already been indexed by `index_function()`.
+    let lhs = node.child_by_field_name("lhs").into_result()?;
+    lhs.is_identifier_or_string().into_result()?;
+
+    let name = contents.node_slice(&lhs)?.to_string();
+
+    let start = convert_point_to_position(contents, lhs.start_position());
+    let end = convert_point_to_position(contents, lhs.end_position());
+
+    Ok(Some(IndexEntry {
+        key: name.clone(),
+        range: Range { start, end },
+        data: IndexEntryData::Variable { name },
+    }))
+}
+
 fn index_comment(_path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<Option<IndexEntry>> {
     // check for comment
     node.is_comment().into_result()?;
 
     // see if it looks like a section
     let comment = contents.node_slice(node)?.to_string();
-    let matches = RE_COMMENT_SECTION
-        .captures(comment.as_str())
-        .into_result()?;
-
-    let level = matches.get(1).into_result()?;
-    let title = matches.get(2).into_result()?;
-
-    let level = level.as_str().len();
-    let title = title.as_str().to_string();
-
-    // skip things that look like knitr output
-    if title.starts_with("----") {
+    let Some((level, title)) = comment_section(comment.as_str()) else {
         return Ok(None);
-    }
+    };
 
     let start = convert_point_to_position(contents, node.start_position());
     let end = convert_point_to_position(contents, node.end_position());
@@ -293,3 +336,46 @@ fn index_comment(_path: &Path, contents: &Rope, node: &Node) -> anyhow::Result<O
         data: IndexEntryData::Section { level, title },
     }))
 }
+
+/// Parses a comment section header like `# Section ----`, returning its level
+/// and title
+pub(crate) fn comment_section(comment: &str) -> Option<(usize, String)> {
+    let matches = RE_COMMENT_SECTION.captures(comment)?;
+
+    let level = matches.get(1)?.as_str().len();
+    let title = matches.get(2)?.as_str().to_string();
+
+    // skip things that look like knitr output
+    if title.starts_with("----") {
+        return None;
+    }
+
+    Some((level, title))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::indexer;
+    use crate::lsp::indexer::IndexEntryData;
+
+    #[test]
+    fn test_reassigned_function_stays_a_function() {
+        let document = Document::new("f <- function(x) x\nf <- memoise(f)\n", None);
+        let path = Path::new("/indexer/R/memoise.R");
+        indexer::update(&document, path).unwrap();
+
+        let mut data = None;
+        indexer::map(|entry_path, symbol, entry| {
+            if entry_path == path && symbol == "f" {
+                data = Some(entry.data.clone());
+            }
+        });
+
+        indexer::clear(path).unwrap();
+
+        assert!(matches!(data, Some(IndexEntryData::Function { .. })));
+    }
+}
@@ -0,0 +1,508 @@
+//
+// inlay_hints.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use harp::utils::r_is_null;
+use ropey::Rope;
+use tower_lsp::lsp_types::InlayHint;
+use tower_lsp::lsp_types::InlayHintKind;
+use tower_lsp::lsp_types::InlayHintLabel;
+use tower_lsp::lsp_types::Range;
+use tree_sitter::Node;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::indexer;
+use crate::lsp::state::WorldState;
+use crate::lsp::traits::cursor::TreeCursorExt;
+use crate::modules::ARK_ENVS;
+use crate::r_task;
+use crate::treesitter::node_text;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+use crate::treesitter::UnaryOperatorType;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InlayHintsConfig {
+    /// Whether to show parameter names before positional arguments.
+    pub enable: bool,
+
+    /// Whether to only show parameter names before literal arguments such as
+    /// `10` or `"foo"`, leaving out variables and calls.
+    pub literals_only: bool,
+}
+
+impl Default for InlayHintsConfig {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            literals_only: false,
+        }
+    }
+}
+
+/// The function called by a call node, possibly qualified with a package
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct Callee {
+    package: Option<String>,
+    name: String,
+}
+
+impl Callee {
+    fn from_call(node: &Node, contents: &Rope) -> Option<Self> {
+        let function = node.child_by_field_name("function")?;
+
+        if function.is_identifier() {
+            return Some(Self {
+                package: None,
+                name: node_text(&function, contents)?,
+            });
+        }
+
+        if function.is_namespace_operator() {
+            let package = function.child_by_field_name("lhs")?;
+            let name = function.child_by_field_name("rhs")?;
+            return Some(Self {
+                package: Some(node_text(&package, contents)?),
+                name: node_text(&name, contents)?,
+            });
+        }
+
+        None
+    }
+}
+
+pub(crate) fn inlay_hints(
+    document: &Document,
+    range: Range,
+    state: &WorldState,
+) -> anyhow::Result<Vec<InlayHint>> {
+    let config = &state.config.inlay_hints;
+    if !config.enable {
+        return Ok(Vec::new());
+    }
+
+    let contents = &document.contents;
+    let start = convert_position_to_point(contents, range.start);
+    let end = convert_position_to_point(contents, range.end);
+
+    // Collect the calls overlapping the requested range
+    let mut calls = Vec::new();
+    let mut cursor = document.ast.walk();
+    cursor.recurse(|node| {
+        if node.end_position() < start || node.start_position() > end {
+            return false;
+        }
+        if node.is_call() {
+            if let Some(callee) = Callee::from_call(&node, contents) {
+                calls.push((node, callee));
+            }
+        }
+        true
+    });
+
+    if calls.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Functions defined in the workspace take precedence over the session
+    let mut formals: HashMap<Callee, Option<Vec<String>>> = HashMap::new();
+    let mut unresolved: Vec<Callee> = Vec::new();
+
+    for (_, callee) in calls.iter() {
+        if formals.contains_key(callee) || unresolved.contains(callee) {
+            continue;
+        }
+        match workspace_formals(callee) {
+            Some(arguments) => {
+                formals.insert(callee.clone(), Some(arguments));
+            },
+            None => unresolved.push(callee.clone()),
+        }
+    }
+
+    if !unresolved.is_empty() {
+        let resolved = r_task(|| {
+            unresolved
+                .iter()
+                .map(|callee| {
+                    let arguments = session_formals(callee).unwrap_or_else(|err| {
+                        log::error!("Can't resolve the formals of `{}`: {err:?}", callee.name);
+                        None
+                    });
+                    (callee.clone(), arguments)
+                })
+                .collect::<Vec<_>>()
+        });
+        formals.extend(resolved);
+    }
+
+    let mut hints = Vec::new();
+
+    for (node, callee) in calls.iter() {
+        if let Some(Some(arguments)) = formals.get(callee) {
+            hints.extend(call_hints(node, arguments, contents, config));
+        }
+    }
+
+    Ok(hints)
+}
+
+fn workspace_formals(callee: &Callee) -> Option<Vec<String>> {
+    if callee.package.is_some() {
+        return None;
+    }
+
+    let (_path, entry) = indexer::find(&callee.name)?;
+    match entry.data {
+        indexer::IndexEntryData::Function { arguments, .. } => Some(arguments),
+        _ => None,
+    }
+}
+
+fn session_formals(callee: &Callee) -> anyhow::Result<Option<Vec<String>>> {
+    let mut call = RFunction::from("call_formal_names");
+    call.add(callee.name.as_str());
+
+    if let Some(package) = &callee.package {
+        call.param("package", package.as_str());
+    }
+
+    let formals = call.call_in(ARK_ENVS.positron_ns)?;
+    if r_is_null(formals.sexp) {
+        return Ok(None);
+    }
+
+    Ok(Some(Vec::<String>::try_from(formals)?))
+}
+
+/// Labels the positional arguments of `call` with the name of the formal they
+/// are matched to. Arguments are matched like R does: first by exact name,
+/// then by partial name for formals before `...`, then by position.
+fn call_hints(
+    call: &Node,
+    formals: &[String],
+    contents: &Rope,
+    config: &InlayHintsConfig,
+) -> Vec<InlayHint> {
+    let Some(arguments) = call.child_by_field_name("arguments") else {
+        return Vec::new();
+    };
+
+    let mut cursor = arguments.walk();
+    let arguments: Vec<Node> = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .collect();
+
+    let names: Vec<String> = arguments
+        .iter()
+        .filter_map(|argument| argument.child_by_field_name("name"))
+        .filter_map(|name| node_text(&name, contents))
+        .collect();
+
+    let mut remaining: Vec<&str> = formals.iter().map(String::as_str).collect();
+
+    let mut partial = Vec::new();
+    for name in names.iter() {
+        match remaining.iter().position(|formal| formal == name) {
+            Some(index) => {
+                remaining.remove(index);
+            },
+            None => partial.push(name),
+        }
+    }
+
+    let mut dots = remaining
+        .iter()
+        .position(|formal| *formal == "...")
+        .unwrap_or(remaining.len());
+
+    for name in partial {
+        let candidates: Vec<usize> = remaining[..dots]
+            .iter()
+            .enumerate()
+            .filter(|(_, formal)| formal.starts_with(name.as_str()))
+            .map(|(index, _)| index)
+            .collect();
+
+        if let [index] = candidates[..] {
+            remaining.remove(index);
+            dots -= 1;
+        }
+    }
+
+    let mut positional = remaining.into_iter().take_while(|formal| *formal != "...");
+
+    // The left-hand side of a pipe is passed as the first positional argument
+    if is_piped_into(call, contents) {
+        positional.next();
+    }
+
+    let mut hints = Vec::new();
+
+    for argument in arguments.iter() {
+        if argument.child_by_field_name("name").is_some() {
+            continue;
+        }
+        let Some(value) = argument.child_by_field_name("value") else {
+            continue;
+        };
+        let Some(formal) = positional.next() else {
+            break;
+        };
+
+        // Don't repeat what's already obvious from the argument
+        if value.is_identifier() && node_text(&value, contents).as_deref() == Some(formal) {
+            continue;
+        }
+        if config.literals_only && !is_literal(&value) {
+            continue;
+        }
+
+        hints.push(InlayHint {
+            position: convert_point_to_position(contents, value.start_position()),
+            label: InlayHintLabel::String(format!("{formal}:")),
+            kind: Some(InlayHintKind::PARAMETER),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: Some(true),
+            data: None,
+        });
+    }
+
+    hints
+}
+
+/// Whether `call` is on the right-hand side of a pipe that inserts its
+/// left-hand side as first argument. This is not the case when the
+/// arguments contain a placeholder: `_` for the native pipe, which must be
+/// named, or `.` for the magrittr pipe.
+fn is_piped_into(call: &Node, contents: &Rope) -> bool {
+    let Some(parent) = call.parent() else {
+        return false;
+    };
+
+    if parent.child_by_field_name("rhs") != Some(*call) {
+        return false;
+    }
+
+    let placeholder = match parent.node_type() {
+        NodeType::BinaryOperator(BinaryOperatorType::Pipe) => "_",
+        NodeType::BinaryOperator(BinaryOperatorType::Special) => {
+            let operator = parent
+                .child_by_field_name("operator")
+                .and_then(|operator| node_text(&operator, contents));
+            if operator.as_deref() != Some("%>%") {
+                return false;
+            }
+            "."
+        },
+        _ => return false,
+    };
+
+    let Some(arguments) = call.child_by_field_name("arguments") else {
+        return true;
+    };
+
+    let mut cursor = arguments.walk();
+    let has_placeholder = arguments
+        .children_by_field_name("argument", &mut cursor)
+        .filter_map(|argument| argument.child_by_field_name("value"))
+        .any(|value| node_text(&value, contents).as_deref() == Some(placeholder));
+
+    !has_placeholder
+}
+
+fn is_literal(node: &Node) -> bool {
+    match node.node_type() {
+        NodeType::String |
+        NodeType::Integer |
+        NodeType::Float |
+        NodeType::Complex |
+        NodeType::True |
+        NodeType::False |
+        NodeType::Null |
+        NodeType::Inf |
+        NodeType::Nan |
+        NodeType::Na(_) => true,
+        NodeType::UnaryOperator(UnaryOperatorType::Minus | UnaryOperatorType::Plus) => node
+            .child_by_field_name("rhs")
+            .is_some_and(|operand| is_literal(&operand)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+    use tree_sitter::Point;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::inlay_hints::call_hints;
+    use crate::lsp::inlay_hints::session_formals;
+    use crate::lsp::inlay_hints::Callee;
+    use crate::lsp::inlay_hints::InlayHintsConfig;
+    use crate::lsp::traits::node::NodeExt;
+    use crate::test::r_test;
+
+    fn hints(text: &str, call: Point, formals: &[&str], config: InlayHintsConfig) -> Vec<String> {
+        let document = Document::new(text, None);
+        let root = document.ast.root_node();
+
+        let node = root.descendant_for_point_range(call, call).unwrap();
+        let call = node.find_parent(|node| node.kind() == "call").unwrap();
+
+        let formals: Vec<String> = formals.iter().map(|formal| formal.to_string()).collect();
+
+        call_hints(&call, &formals, &document.contents, &config)
+            .into_iter()
+            .map(|hint| {
+                let label = match hint.label {
+                    tower_lsp::lsp_types::InlayHintLabel::String(label) => label,
+                    _ => panic!("Unexpected label parts"),
+                };
+                format!("{}:{} {label}", hint.position.line, hint.position.character)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_inlay_hints_positional() {
+        let formals = ["n", "mean", "sd"];
+        let config = InlayHintsConfig::default();
+
+        assert_eq!(
+            hints(
+                "rnorm(10, 0, 1)",
+                Point::new(0, 0),
+                &formals,
+                config.clone()
+            ),
+            vec!["0:6 n:", "0:10 mean:", "0:13 sd:"]
+        );
+
+        // Named arguments, exact or partial, take their formal out of the
+        // positional matching
+        assert_eq!(
+            hints(
+                "rnorm(me = 0, 10, 1)",
+                Point::new(0, 0),
+                &formals,
+                config.clone()
+            ),
+            vec!["0:14 n:", "0:18 sd:"]
+        );
+
+        // Arguments named like their formal are not labelled
+        assert_eq!(
+            hints("rnorm(n, mean)", Point::new(0, 0), &formals, config),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_inlay_hints_dots() {
+        let formals = ["...", "sep", "collapse"];
+        let config = InlayHintsConfig::default();
+
+        // Arguments after `...` can't be matched by position or partially
+        assert_eq!(
+            hints("paste(x, y, se = 1)", Point::new(0, 0), &formals, config),
+            Vec::<String>::new()
+        );
+
+        let formals = ["x", "..."];
+        assert_eq!(
+            hints(
+                "c(1, 2)",
+                Point::new(0, 0),
+                &formals,
+                InlayHintsConfig::default()
+            ),
+            vec!["0:2 x:"]
+        );
+    }
+
+    #[test]
+    fn test_inlay_hints_literals_only() {
+        let formals = ["x", "size", "replace"];
+        let config = InlayHintsConfig {
+            enable: true,
+            literals_only: true,
+        };
+
+        assert_eq!(
+            hints("sample(y, -1, TRUE)", Point::new(0, 0), &formals, config),
+            vec!["0:10 size:", "0:14 replace:"]
+        );
+    }
+
+    #[test]
+    fn test_inlay_hints_pipes() {
+        let formals = ["data", "x", "y"];
+        let config = InlayHintsConfig::default();
+
+        assert_eq!(
+            hints("df |> f(1, 2)", Point::new(0, 6), &formals, config.clone()),
+            vec!["0:8 x:", "0:11 y:"]
+        );
+        assert_eq!(
+            hints("df %>% f(1, 2)", Point::new(0, 7), &formals, config.clone()),
+            vec!["0:9 x:", "0:12 y:"]
+        );
+
+        // Placeholders take the left-hand side elsewhere
+        assert_eq!(
+            hints(
+                "df |> f(1, data = _)",
+                Point::new(0, 6),
+                &formals,
+                config.clone()
+            ),
+            vec!["0:8 x:"]
+        );
+        assert_eq!(
+            hints("df %>% f(1, .)", Point::new(0, 7), &formals, config),
+            vec!["0:9 data:", "0:12 x:"]
+        );
+    }
+
+    #[test]
+    fn test_session_formals_of_unloaded_package() {
+        r_test(|| {
+            let is_loaded = || -> bool {
+                r_parse_eval0("isNamespaceLoaded('splines')", R_ENVS.global)
+                    .unwrap()
+                    .try_into()
+                    .unwrap()
+            };
+
+            // splines is installed along with R, but isn't loaded by the session
+            if is_loaded() {
+                return;
+            }
+
+            let callee = Callee {
+                package: Some(String::from("splines")),
+                name: String::from("bs"),
+            };
+            let formals = session_formals(&callee).unwrap().unwrap();
+            assert_eq!(formals[..3], ["x", "df", "knots"]);
+
+            // The formals are read from the help page without loading the
+            // package
+            assert!(!is_loaded());
+        })
+    }
+}
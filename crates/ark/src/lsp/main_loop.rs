@@ -16,6 +16,7 @@ use tokio::task::JoinHandle;
 use tower_lsp::lsp_types;
 use tower_lsp::lsp_types::Diagnostic;
 use tower_lsp::lsp_types::MessageType;
+use tower_lsp::lsp_types::SemanticTokens;
 use tower_lsp::Client;
 use url::Url;
 
@@ -112,11 +113,16 @@ pub(crate) struct LspState {
     /// List of capabilities for which we need to send a registration request
     /// when we get the `Initialized` notification.
     pub(crate) needs_registration: ClientCaps,
+
+    /// The last semantic tokens sent for each document, against which
+    /// `semanticTokens/full/delta` requests compute their edits.
+    pub(crate) semantic_tokens: HashMap<Url, SemanticTokens>,
 }
 
 #[derive(Debug, Default)]
 pub(crate) struct ClientCaps {
     pub(crate) did_change_configuration: bool,
+    pub(crate) did_change_watched_files: bool,
 }
 
 /// State for the auxiliary loop
@@ -221,14 +227,14 @@ impl GlobalState {
                         LspNotification::Initialized(_params) => {
                             handlers::handle_initialized(&self.client, &self.lsp_state).await?;
                         },
-                        LspNotification::DidChangeWorkspaceFolders(_params) => {
-                            // TODO: Restart indexer with new folders.
+                        LspNotification::DidChangeWorkspaceFolders(params) => {
+                            state_handlers::did_change_workspace_folders(params, &mut self.world)?;
                         },
                         LspNotification::DidChangeConfiguration(params) => {
                             state_handlers::did_change_configuration(params, &self.client, &mut self.world).await?;
                         },
-                        LspNotification::DidChangeWatchedFiles(_params) => {
-                            // TODO: Re-index the changed files.
+                        LspNotification::DidChangeWatchedFiles(params) => {
+                            state_handlers::did_change_watched_files(params, &mut self.world)?;
                         },
                         LspNotification::DidOpenTextDocument(params) => {
                             state_handlers::did_open(params, &mut self.lsp_state, &mut self.world)?;
@@ -236,8 +242,8 @@ impl GlobalState {
                         LspNotification::DidChangeTextDocument(params) => {
                             state_handlers::did_change(params, &mut self.lsp_state, &mut self.world)?;
                         },
-                        LspNotification::DidSaveTextDocument(_params) => {
-                            // Currently ignored
+                        LspNotification::DidSaveTextDocument(params) => {
+                            state_handlers::did_save(params, &mut self.world)?;
                         },
                         LspNotification::DidCloseTextDocument(params) => {
                             state_handlers::did_close(params, &mut self.lsp_state, &mut self.world)?;
@@ -287,9 +293,42 @@ impl GlobalState {
                         LspRequest::SelectionRange(params) => {
                             respond(tx, handlers::handle_selection_range(params, &self.world), LspResponse::SelectionRange)?;
                         },
+                        LspRequest::FoldingRange(params) => {
+                            respond(tx, handlers::handle_folding_range(params, &self.world), LspResponse::FoldingRange)?;
+                        },
+                        LspRequest::SemanticTokensFull(params) => {
+                            respond(tx, handlers::handle_semantic_tokens_full(params, &mut self.lsp_state, &self.world), LspResponse::SemanticTokensFull)?;
+                        },
+                        LspRequest::SemanticTokensFullDelta(params) => {
+                            respond(tx, handlers::handle_semantic_tokens_full_delta(params, &mut self.lsp_state, &self.world), LspResponse::SemanticTokensFullDelta)?;
+                        },
+                        LspRequest::InlayHint(params) => {
+                            respond(tx, handlers::handle_inlay_hint(params, &self.world), LspResponse::InlayHint)?;
+                        },
+                        LspRequest::PrepareCallHierarchy(params) => {
+                            respond(tx, handlers::handle_prepare_call_hierarchy(params, &self.world), LspResponse::PrepareCallHierarchy)?;
+                        },
+                        LspRequest::IncomingCalls(params) => {
+                            respond(tx, handlers::handle_incoming_calls(params, &self.world), LspResponse::IncomingCalls)?;
+                        },
+                        LspRequest::OutgoingCalls(params) => {
+                            respond(tx, handlers::handle_outgoing_calls(params, &self.world), LspResponse::OutgoingCalls)?;
+                        },
+                        LspRequest::DocumentHighlight(params) => {
+                            respond(tx, handlers::handle_document_highlight(params, &self.world), LspResponse::DocumentHighlight)?;
+                        },
                         LspRequest::References(params) => {
                             respond(tx, handlers::handle_references(params, &self.world), LspResponse::References)?;
                         },
+                        LspRequest::PrepareRename(params) => {
+                            respond(tx, handlers::handle_prepare_rename(params, &self.world), LspResponse::PrepareRename)?;
+                        },
+                        LspRequest::Rename(params) => {
+                            respond(tx, handlers::handle_rename(params, &self.world), LspResponse::Rename)?;
+                        },
+                        LspRequest::CodeAction(params) => {
+                            respond(tx, handlers::handle_code_action(params, &self.world), LspResponse::CodeAction)?;
+                        },
                         LspRequest::StatementRange(params) => {
                             respond(tx, handlers::handle_statement_range(params, &self.world), LspResponse::StatementRange)?;
                         },
@@ -300,6 +339,14 @@ impl GlobalState {
                             state_handlers::did_change_formatting_options(&params.text_document_position.text_document.uri, &params.options, &mut self.world);
                             respond(tx, handlers::handle_indent(params, &self.world), LspResponse::OnTypeFormatting)?;
                         },
+                        LspRequest::DocumentFormatting(params) => {
+                            state_handlers::did_change_formatting_options(&params.text_document.uri, &params.options, &mut self.world);
+                            respond(tx, handlers::handle_formatting(params, &self.world), LspResponse::DocumentFormatting)?;
+                        },
+                        LspRequest::DocumentRangeFormatting(params) => {
+                            state_handlers::did_change_formatting_options(&params.text_document.uri, &params.options, &mut self.world);
+                            respond(tx, handlers::handle_range_formatting(params, &self.world), LspResponse::DocumentRangeFormatting)?;
+                        },
                         LspRequest::VirtualDocument(params) => {
                             respond(tx, handlers::handle_virtual_document(params), LspResponse::VirtualDocument)?;
                         },
@@ -528,7 +575,8 @@ pub(crate) fn spawn_diagnostics_refresh(uri: Url, document: Document, state: Wor
         let _s = tracing::info_span!("diagnostics_refresh", uri = %uri).entered();
 
         let version = document.version;
-        let diagnostics = diagnostics::generate_diagnostics(document, state);
+        let package = state.workspace.package(&uri).cloned();
+        let diagnostics = diagnostics::generate_diagnostics(document, state, package.as_ref());
 
         Ok(Some(AuxiliaryEvent::PublishDiagnostics(
             uri,
@@ -6,6 +6,8 @@
 //
 
 pub mod backend;
+pub mod call_hierarchy;
+pub mod code_actions;
 pub mod comm;
 pub mod completions;
 mod config;
@@ -13,9 +15,12 @@ mod declarations;
 pub mod definitions;
 pub mod diagnostics;
 pub mod document_context;
+pub mod document_highlight;
 pub mod documents;
 pub mod encoding;
 pub mod events;
+pub mod folding_range;
+pub mod format;
 pub mod handler;
 pub mod handlers;
 pub mod help;
@@ -23,11 +28,18 @@ pub mod help_topic;
 pub mod hover;
 pub mod indent;
 pub mod indexer;
+pub mod inlay_hints;
 pub mod main_loop;
 pub mod markdown;
 pub mod offset;
+pub mod package;
 pub mod references;
+pub mod rename;
+pub mod rmarkdown;
+pub mod roxygen;
+pub mod scopes;
 pub mod selection_range;
+pub mod semantic_tokens;
 pub mod signature_help;
 pub mod state;
 pub mod state_handlers;
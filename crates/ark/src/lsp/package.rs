@@ -0,0 +1,380 @@
+//
+// package.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use harp::exec::RFunction;
+use harp::exec::RFunctionExt;
+use ropey::Rope;
+use tree_sitter::Node;
+
+use crate::lsp::documents::Document;
+use crate::modules::ARK_ENVS;
+use crate::treesitter::node_text;
+use crate::treesitter::NodeTypeExt;
+
+/// An R package found at the root of a workspace folder
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Package {
+    /// The package root, i.e. the folder holding the `DESCRIPTION` file
+    pub path: PathBuf,
+
+    pub description: Description,
+    pub namespace: Namespace,
+
+    /// The exports of the packages imported wholesale with `import()` or
+    /// attached through `Depends`, by package. These are resolved against the
+    /// installed packages with `resolve_imports()`.
+    pub exports: HashMap<String, Vec<String>>,
+}
+
+/// The fields of a `DESCRIPTION` file that the LSP cares about
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Description {
+    pub name: String,
+    pub depends: Vec<String>,
+    pub imports: Vec<String>,
+    pub linking_to: Vec<String>,
+    pub suggests: Vec<String>,
+
+    /// The files of the `R/` folder, in collation order. Empty when the
+    /// package doesn't have a `Collate` field, in which case all files of
+    /// `R/` are part of the package.
+    pub collate: Vec<String>,
+}
+
+/// The directives of a `NAMESPACE` file that the LSP cares about
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Namespace {
+    /// Packages imported with `import()`
+    pub imports: Vec<String>,
+
+    /// Symbols imported with `importFrom()`, as `(package, symbol)` pairs
+    pub import_from: Vec<(String, String)>,
+
+    pub exports: Vec<String>,
+}
+
+impl Package {
+    /// Loads the package rooted at `path`. Returns `None` if there is no
+    /// `DESCRIPTION` file there or if it doesn't describe a package.
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        let description = path.join("DESCRIPTION");
+        if !description.is_file() {
+            return Ok(None);
+        }
+
+        let description = std::fs::read_to_string(description)?;
+        let Some(description) = Description::parse(&description) else {
+            return Ok(None);
+        };
+
+        // A package without a `NAMESPACE` doesn't import anything
+        let namespace = path.join("NAMESPACE");
+        let namespace = if namespace.is_file() {
+            Namespace::parse(&std::fs::read_to_string(namespace)?)
+        } else {
+            Namespace::default()
+        };
+
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            description,
+            namespace,
+            exports: HashMap::new(),
+        }))
+    }
+
+    /// Whether `path` belongs to this package, e.g. one of its R files or
+    /// one of its tests
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.path)
+    }
+
+    /// Whether `path` is one of the R files making up the package code
+    pub(crate) fn is_source(&self, path: &Path) -> bool {
+        let Ok(file) = path.strip_prefix(self.path.join("R")) else {
+            return false;
+        };
+
+        if self.description.collate.is_empty() {
+            return true;
+        }
+
+        let file = file.to_string_lossy();
+        self.description.collate.iter().any(|name| *name == file)
+    }
+
+    /// Whether `package` can be referred to with `::` from the package code.
+    /// Like `R CMD check`, only `base` may be used without being declared in
+    /// `DESCRIPTION`.
+    pub(crate) fn declares(&self, package: &str) -> bool {
+        let description = &self.description;
+
+        package == description.name ||
+            package == "base" ||
+            [
+                &description.depends,
+                &description.imports,
+                &description.linking_to,
+                &description.suggests,
+            ]
+            .iter()
+            .any(|packages| packages.iter().any(|name| name == package))
+    }
+
+    /// The symbols available to the package code through its imports, as
+    /// `(package, symbol)` pairs
+    pub(crate) fn imported_symbols(&self) -> impl Iterator<Item = (&str, &str)> {
+        let imported = self
+            .namespace
+            .import_from
+            .iter()
+            .map(|(package, symbol)| (package.as_str(), symbol.as_str()));
+
+        let exported = self.exports.iter().flat_map(|(package, symbols)| {
+            symbols
+                .iter()
+                .map(move |symbol| (package.as_str(), symbol.as_str()))
+        });
+
+        imported.chain(exported)
+    }
+
+    /// Resolves the exports of the packages imported wholesale or attached
+    /// through `Depends`. Packages that aren't installed are skipped.
+    /// Must be called within an `r_task()`.
+    pub(crate) fn resolve_imports(&mut self) -> anyhow::Result<()> {
+        let packages: Vec<String> = self
+            .namespace
+            .imports
+            .iter()
+            .chain(self.description.depends.iter())
+            .filter(|package| *package != "R" && !self.exports.contains_key(*package))
+            .cloned()
+            .collect();
+
+        for package in packages {
+            let exports = RFunction::from("package_exports")
+                .add(package.as_str())
+                .call_in(ARK_ENVS.positron_ns)?;
+
+            self.exports
+                .insert(package, Vec::<String>::try_from(exports)?);
+        }
+
+        Ok(())
+    }
+}
+
+impl Description {
+    /// Parses the contents of a `DESCRIPTION` file. Returns `None` if there
+    /// is no `Package` field.
+    pub(crate) fn parse(contents: &str) -> Option<Self> {
+        let mut fields: HashMap<String, String> = HashMap::new();
+        let mut current: Option<String> = None;
+
+        // Fields are `Key: Value` lines. Values may continue over indented lines.
+        for line in contents.lines() {
+            if line.starts_with([' ', '\t']) {
+                if let Some(field) = current.as_ref().and_then(|key| fields.get_mut(key)) {
+                    field.push(' ');
+                    field.push_str(line.trim());
+                }
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(':') else {
+                current = None;
+                continue;
+            };
+
+            let key = key.trim().to_string();
+            fields.insert(key.clone(), value.trim().to_string());
+            current = Some(key);
+        }
+
+        let name = fields.get("Package")?.clone();
+
+        let packages = |key: &str| -> Vec<String> {
+            let Some(field) = fields.get(key) else {
+                return Vec::new();
+            };
+
+            // Drop the version requirements, e.g. `rlang (>= 1.0.0)`
+            field
+                .split(',')
+                .filter_map(|entry| entry.split('(').next())
+                .map(|package| package.trim().to_string())
+                .filter(|package| !package.is_empty())
+                .collect()
+        };
+
+        let collate = fields
+            .get("Collate")
+            .map(|field| {
+                field
+                    .split_whitespace()
+                    .map(|file| file.trim_matches(['\'', '"']).to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(Self {
+            name,
+            depends: packages("Depends"),
+            imports: packages("Imports"),
+            linking_to: packages("LinkingTo"),
+            suggests: packages("Suggests"),
+            collate,
+        })
+    }
+}
+
+impl Namespace {
+    /// Parses the contents of a `NAMESPACE` file. The directives are R calls
+    /// so we parse the file as R code and look at the top-level calls.
+    pub(crate) fn parse(contents: &str) -> Self {
+        let document = Document::new(contents, None);
+        let contents = &document.contents;
+
+        let mut namespace = Self::default();
+
+        let root = document.ast.root_node();
+        let mut cursor = root.walk();
+
+        for node in root.children(&mut cursor) {
+            if !node.is_call() {
+                continue;
+            }
+
+            let Some(function) = node.child_by_field_name("function") else {
+                continue;
+            };
+            let Some(directive) = node_text(&function, contents) else {
+                continue;
+            };
+
+            let arguments = directive_arguments(&node, contents);
+
+            match directive.as_str() {
+                "import" => namespace.imports.extend(arguments),
+                "importFrom" => {
+                    let mut arguments = arguments.into_iter();
+                    if let Some(package) = arguments.next() {
+                        namespace
+                            .import_from
+                            .extend(arguments.map(|symbol| (package.clone(), symbol)));
+                    }
+                },
+                "export" => namespace.exports.extend(arguments),
+                _ => {},
+            }
+        }
+
+        namespace
+    }
+}
+
+/// The unnamed arguments of a `NAMESPACE` directive, unquoted. Named
+/// arguments like `except` are skipped.
+fn directive_arguments(node: &Node, contents: &Rope) -> Vec<String> {
+    let Some(arguments) = node.child_by_field_name("arguments") else {
+        return Vec::new();
+    };
+
+    let mut cursor = arguments.walk();
+    arguments
+        .children_by_field_name("argument", &mut cursor)
+        .filter(|argument| argument.child_by_field_name("name").is_none())
+        .filter_map(|argument| argument.child_by_field_name("value"))
+        .filter(|value| value.is_identifier_or_string())
+        .filter_map(|value| node_text(&value, contents))
+        .map(|text| text.trim_matches(['"', '\'', '`']).to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::lsp::package::Description;
+    use crate::lsp::package::Namespace;
+    use crate::lsp::package::Package;
+
+    #[test]
+    fn test_description_parse() {
+        let contents = "Package: mypkg
+Title: My Package
+Version: 0.1.0
+Depends:
+    R (>= 4.1.0)
+Imports:
+    cli,
+    rlang (>= 1.1.0)
+Suggests: testthat (>= 3.0.0)
+Collate:
+    'aaa.R'
+    'utils.R'
+";
+        let description = Description::parse(contents).unwrap();
+        assert_eq!(description.name, "mypkg");
+        assert_eq!(description.depends, vec!["R"]);
+        assert_eq!(description.imports, vec!["cli", "rlang"]);
+        assert_eq!(description.suggests, vec!["testthat"]);
+        assert!(description.linking_to.is_empty());
+        assert_eq!(description.collate, vec!["aaa.R", "utils.R"]);
+
+        // Not a package
+        assert!(Description::parse("Title: Not a package\n").is_none());
+    }
+
+    #[test]
+    fn test_namespace_parse() {
+        let contents = r#"# Generated by roxygen2: do not edit by hand
+
+S3method(print,foo)
+export(bar)
+export("baz")
+import(rlang, except = c(abort))
+importFrom(cli,cli_abort)
+importFrom(cli,"cli_warn",cli_inform)
+"#;
+        let namespace = Namespace::parse(contents);
+        assert_eq!(namespace.exports, vec!["bar", "baz"]);
+        assert_eq!(namespace.imports, vec!["rlang"]);
+        assert_eq!(namespace.import_from, vec![
+            (String::from("cli"), String::from("cli_abort")),
+            (String::from("cli"), String::from("cli_warn")),
+            (String::from("cli"), String::from("cli_inform")),
+        ]);
+    }
+
+    #[test]
+    fn test_package_files_and_declarations() {
+        let package = Package {
+            path: Path::new("/pkg").to_path_buf(),
+            description: Description::parse("Package: pkg\nImports: cli\nCollate: 'a.R' 'b.R'\n")
+                .unwrap(),
+            ..Default::default()
+        };
+
+        assert!(package.contains(Path::new("/pkg/tests/testthat/test-a.R")));
+        assert!(!package.contains(Path::new("/other/R/a.R")));
+
+        assert!(package.is_source(Path::new("/pkg/R/a.R")));
+        assert!(!package.is_source(Path::new("/pkg/R/c.R")));
+        assert!(!package.is_source(Path::new("/pkg/tests/testthat/test-a.R")));
+
+        assert!(package.declares("cli"));
+        assert!(package.declares("base"));
+        assert!(package.declares("pkg"));
+        assert!(!package.declares("utils"));
+    }
+}
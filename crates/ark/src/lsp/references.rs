@@ -18,13 +18,11 @@ use tower_lsp::lsp_types::ReferenceParams;
 use tower_lsp::lsp_types::Url;
 use tree_sitter::Node;
 use tree_sitter::Point;
-use walkdir::WalkDir;
 
 use crate::lsp;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::convert_point_to_position;
 use crate::lsp::encoding::convert_position_to_point;
-use crate::lsp::indexer::filter_entry;
 use crate::lsp::state::with_document;
 use crate::lsp::state::WorldState;
 use crate::lsp::traits::cursor::TreeCursorExt;
@@ -148,37 +146,6 @@ fn build_context(uri: &Url, position: Position, state: &WorldState) -> anyhow::R
     return context;
 }
 
-fn find_references_in_folder(
-    context: &Context,
-    path: &Path,
-    locations: &mut Vec<Location>,
-    state: &WorldState,
-) {
-    let walker = WalkDir::new(path);
-    for entry in walker.into_iter().filter_entry(|entry| filter_entry(entry)) {
-        let entry = unwrap!(entry, Err(_) => { continue; });
-        let path = entry.path();
-        let ext = unwrap!(path.extension(), None => { continue; });
-        if ext != "r" && ext != "R" {
-            continue;
-        }
-
-        lsp::log_info!("found R file {}", path.display());
-        let result = with_document(path, state, |document| {
-            find_references_in_document(context, path, document, locations);
-            return Ok(());
-        });
-
-        match result {
-            Ok(result) => result,
-            Err(_error) => {
-                lsp::log_warn!("error retrieving document for path {}", path.display());
-                continue;
-            },
-        }
-    }
-}
-
 fn find_references_in_document(
     context: &Context,
     path: &Path,
@@ -214,11 +181,18 @@ pub(crate) fn find_references(
         return Err(anyhow!("Failed to find build context at position {position:?}: {err:?}"));
     });
 
-    // Now, start searching through workspace folders for references to that identifier.
-    for folder in state.workspace.folders.iter() {
-        if let Ok(path) = folder.to_file_path() {
-            lsp::log_info!("searching references in folder {}", path.display());
-            find_references_in_folder(&context, &path, &mut locations, state);
+    // Now, start searching through workspace files for references to that identifier.
+    for path in state.workspace.r_files() {
+        let path = path.as_path();
+        lsp::log_info!("found R file {}", path.display());
+
+        let result = with_document(path, state, |document| {
+            find_references_in_document(&context, path, document, &mut locations);
+            return Ok(());
+        });
+
+        if let Err(_error) = result {
+            lsp::log_warn!("error retrieving document for path {}", path.display());
         }
     }
 
@@ -0,0 +1,270 @@
+//
+// rename.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+
+use anyhow::bail;
+use harp::utils::is_symbol_valid;
+use ropey::Rope;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::RenameParams;
+use tower_lsp::lsp_types::TextEdit;
+use tower_lsp::lsp_types::Url;
+use tower_lsp::lsp_types::WorkspaceEdit;
+use tree_sitter::Point;
+
+use crate::lsp;
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::lsp::indexer;
+use crate::lsp::scopes::Scopes;
+use crate::lsp::state::with_document;
+use crate::lsp::state::WorldState;
+
+/// Creates the edits renaming the references to the binding of `name` in
+/// `target`. Fails if the new name would shadow another binding, or would be
+/// shadowed by one.
+fn rename_edits(
+    scopes: &Scopes,
+    name: &str,
+    target: Option<usize>,
+    new_name: &str,
+    contents: &Rope,
+) -> anyhow::Result<Vec<TextEdit>> {
+    if scopes.is_bound(new_name, target) {
+        bail!("Can't rename `{name}` to `{new_name}`, which is already defined in the same scope");
+    }
+
+    for occurrence in scopes.occurrences() {
+        if occurrence.name == name && scopes.resolve_at(name, occurrence) == target {
+            // A binding of the new name between the reference and the
+            // renamed binding would capture the reference
+            if let Some(binding) = scopes.resolve_at(new_name, occurrence) {
+                if Some(binding) != target && scopes.is_within(binding, target) {
+                    bail!("Can't rename `{name}` to `{new_name}`, which would be shadowed by another binding of `{new_name}`");
+                }
+            }
+        } else if occurrence.name == new_name && scopes.is_within(occurrence.scope, target) {
+            // A reference to an outer binding of the new name would be
+            // captured by the renamed binding
+            let binding = scopes.resolve_at(new_name, occurrence);
+            if !binding.is_some_and(|binding| scopes.is_within(binding, target)) {
+                bail!("Can't rename `{name}` to `{new_name}`, which would shadow another binding of `{new_name}`");
+            }
+        }
+    }
+
+    let edits = scopes
+        .references(name, target)
+        .into_iter()
+        .map(|occurrence| {
+            let range = convert_tree_sitter_range_to_lsp_range(contents, occurrence.node.range());
+            TextEdit::new(range, new_name.to_string())
+        })
+        .collect();
+
+    Ok(edits)
+}
+
+fn is_workspace_function(name: &str) -> bool {
+    matches!(
+        indexer::find(name),
+        Some((_, indexer::IndexEntry {
+            data: indexer::IndexEntryData::Function { .. },
+            ..
+        }))
+    )
+}
+
+/// Returns the range of the variable at `point` if it can be renamed.
+/// Variables that are not defined in the workspace, like the functions of
+/// packages, can't be renamed.
+pub(crate) fn prepare_rename(document: &Document, point: Point) -> anyhow::Result<Option<Range>> {
+    let scopes = Scopes::new(document);
+
+    let Some(occurrence) = scopes.occurrence_at(point) else {
+        return Ok(None);
+    };
+    let name = &occurrence.name;
+
+    let is_defined = scopes.resolve_at(name, occurrence).is_some() ||
+        scopes.is_global_binding(name) ||
+        is_workspace_function(name);
+
+    if !is_defined {
+        bail!("Can't rename `{name}`, which isn't defined in the workspace");
+    }
+
+    let range = convert_tree_sitter_range_to_lsp_range(&document.contents, occurrence.node.range());
+    Ok(Some(range))
+}
+
+pub(crate) fn rename(
+    params: RenameParams,
+    state: &WorldState,
+) -> anyhow::Result<Option<WorkspaceEdit>> {
+    let uri = params.text_document_position.text_document.uri;
+    let document = state.get_document(&uri)?;
+
+    let position = params.text_document_position.position;
+    let point = convert_position_to_point(&document.contents, position);
+
+    let new_name = params.new_name;
+
+    let scopes = Scopes::new(document);
+    let Some(occurrence) = scopes.occurrence_at(point) else {
+        bail!("Can't find a variable to rename at {point:?}");
+    };
+    let name = &occurrence.name;
+
+    if !is_symbol_valid(&new_name) {
+        bail!("Can't rename `{name}` to `{new_name}`, which isn't a valid R symbol");
+    }
+    if *name == new_name {
+        return Ok(None);
+    }
+
+    let target = scopes.resolve_at(name, occurrence);
+    let is_function = target.is_none() && is_workspace_function(name);
+
+    if target.is_none() && !scopes.is_global_binding(name) && !is_function {
+        bail!("Can't rename `{name}`, which isn't defined in the workspace");
+    }
+
+    let mut changes = HashMap::new();
+
+    let edits = rename_edits(&scopes, name, target, &new_name, &document.contents)?;
+    changes.insert(uri.clone(), edits);
+
+    // Local variables, and global variables that are not workspace-level
+    // functions, are only renamed within the document
+    if !is_function {
+        return Ok(Some(WorkspaceEdit::new(changes)));
+    }
+
+    if is_workspace_function(&new_name) {
+        bail!("Can't rename `{name}` to `{new_name}`, which is already defined in the workspace");
+    }
+
+    for path in state.workspace.r_files() {
+        let Ok(file_uri) = Url::from_file_path(&path) else {
+            continue;
+        };
+        if file_uri == uri {
+            continue;
+        }
+
+        let result = with_document(&path, state, |document| {
+            let scopes = Scopes::new(document);
+            Ok(rename_edits(
+                &scopes,
+                name,
+                None,
+                &new_name,
+                &document.contents,
+            ))
+        });
+
+        let edits = match result {
+            Ok(edits) => edits?,
+            Err(_error) => {
+                lsp::log_warn!("error retrieving document for path {}", path.display());
+                continue;
+            },
+        };
+
+        if !edits.is_empty() {
+            changes.insert(file_uri, edits);
+        }
+    }
+
+    Ok(Some(WorkspaceEdit::new(changes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tree_sitter::Point;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::rename::rename_edits;
+    use crate::lsp::scopes::Scopes;
+
+    fn rename(text: &str, point: Point, new_name: &str) -> anyhow::Result<Vec<Position>> {
+        let document = Document::new(text, None);
+        let scopes = Scopes::new(&document);
+
+        let occurrence = scopes.occurrence_at(point).unwrap();
+        let target = scopes.resolve_at(&occurrence.name, occurrence);
+        let edits = rename_edits(
+            &scopes,
+            &occurrence.name,
+            target,
+            new_name,
+            &document.contents,
+        )?;
+
+        Ok(edits.into_iter().map(|edit| edit.range.start).collect())
+    }
+
+    #[test]
+    fn test_rename_local_variable() {
+        let text = "f <- function() {\n  x <- 1\n  x + 1\n}\nx <- 2\n";
+        assert_eq!(rename(text, Point::new(1, 2), "y").unwrap(), vec![
+            Position::new(1, 2),
+            Position::new(2, 2)
+        ]);
+
+        // Top-level variables are also renamed in the functions that don't
+        // define them
+        let text = "x <- 1\nf <- function(y) x + y\n";
+        assert_eq!(rename(text, Point::new(0, 0), "z").unwrap(), vec![
+            Position::new(0, 0),
+            Position::new(1, 17)
+        ]);
+    }
+
+    #[test]
+    fn test_rename_parameter() {
+        let text = "f <- function(a, b = a) a + b\n";
+        assert_eq!(rename(text, Point::new(0, 14), "n").unwrap(), vec![
+            Position::new(0, 14),
+            Position::new(0, 21),
+            Position::new(0, 24)
+        ]);
+    }
+
+    #[test]
+    fn test_rename_super_assignment() {
+        let text = "count <- 0\nf <- function() count <<- count + 1\n";
+        assert_eq!(rename(text, Point::new(0, 0), "n").unwrap(), vec![
+            Position::new(0, 0),
+            Position::new(1, 16),
+            Position::new(1, 26)
+        ]);
+    }
+
+    #[test]
+    fn test_rename_refuses_shadowing() {
+        // Already defined in the same scope
+        let text = "f <- function(x) {\n  y <- 1\n  x + y\n}\n";
+        assert!(rename(text, Point::new(0, 14), "y").is_err());
+
+        // Would capture a reference to a global variable
+        let text = "y <- 1\nf <- function(x) x + y\n";
+        assert!(rename(text, Point::new(1, 14), "y").is_err());
+
+        // Would be captured by the parameter of a nested function
+        let text = "f <- function(x) {\n  function(y) x + y\n}\n";
+        assert!(rename(text, Point::new(0, 14), "y").is_err());
+
+        // Bindings in sibling scopes are unaffected
+        let text = "f <- function(x) x\ng <- function(y) y\n";
+        assert!(rename(text, Point::new(0, 14), "y").is_ok());
+    }
+}
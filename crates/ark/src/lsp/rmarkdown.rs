@@ -0,0 +1,370 @@
+//
+// rmarkdown.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use ropey::Rope;
+use tower_lsp::lsp_types::Position;
+use tower_lsp::lsp_types::Range;
+use tower_lsp::lsp_types::Url;
+
+use crate::lsp::encoding::convert_position_to_point;
+use crate::lsp::traits::rope::RopeExt;
+
+lazy_static! {
+    // The opening fence of a code block, e.g. ```{r setup, echo = FALSE}
+    static ref RE_FENCE_START: Regex = Regex::new(r"^\s*(`{3,})\s*(.*?)\s*$").unwrap();
+    static ref RE_FENCE_END: Regex = Regex::new(r"^\s*(`{3,})\s*$").unwrap();
+
+    // The braced header of an executable chunk, e.g. {r setup, echo = FALSE}
+    static ref RE_CHUNK_HEADER: Regex = Regex::new(r"^\{\s*([A-Za-z0-9_]+)(.*)\}$").unwrap();
+    static ref RE_LABEL_OPTION: Regex = Regex::new(r#"^label\s*=\s*['"]?([^'"]*)['"]?$"#).unwrap();
+
+    // A Quarto chunk option, e.g. #| label: setup
+    static ref RE_QUARTO_LABEL: Regex =
+        Regex::new(r#"^\s*#\|\s*label:\s*['"]?([^'"]*?)['"]?\s*$"#).unwrap();
+
+    static ref RE_HEADING: Regex = Regex::new(r"^(#{1,6})\s+(.*\S)\s*$").unwrap();
+}
+
+/// Whether `uri` points to an R Markdown or Quarto document, i.e. a markdown
+/// document hosting R chunks
+pub fn is_host_document(uri: &Url) -> bool {
+    let Some((_, extension)) = uri.path().rsplit_once('.') else {
+        return false;
+    };
+
+    matches!(extension.to_lowercase().as_str(), "rmd" | "qmd")
+}
+
+/// An R Markdown or Quarto document. The LSP features work on an R view of
+/// the document where everything but the code of R chunks is blanked out,
+/// see `r_view()`. Since the R view keeps the lines and columns of the host
+/// document, positions are the same in both.
+#[derive(Clone, Debug)]
+pub struct HostDocument {
+    pub contents: Rope,
+    pub chunks: Vec<Chunk>,
+    pub headings: Vec<Heading>,
+}
+
+/// A fenced code block of a host document
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    /// The engine of an executable chunk, e.g. `r` for ```` ```{r} ````.
+    /// `None` for plain code blocks.
+    pub engine: Option<String>,
+
+    pub label: Option<String>,
+
+    /// The rows of the opening and closing fences. An unterminated chunk
+    /// ends past the last row of the document.
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A markdown heading of a host document
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heading {
+    pub level: usize,
+    pub title: String,
+    pub row: usize,
+}
+
+impl Chunk {
+    pub fn is_r(&self) -> bool {
+        matches!(self.engine.as_deref(), Some("r" | "R"))
+    }
+
+    /// Whether `row` is one of the code rows of the chunk, fences excluded
+    pub fn contains(&self, row: usize) -> bool {
+        row > self.start && row < self.end
+    }
+}
+
+impl HostDocument {
+    pub fn new(contents: &str) -> Self {
+        let contents = Rope::from(contents);
+        let (chunks, headings) = parse(&contents);
+
+        Self {
+            contents,
+            chunks,
+            headings,
+        }
+    }
+
+    /// Applies an edit to the host document
+    pub fn update(&mut self, range: Range, text: &str) {
+        let start = self.char_index(range.start);
+        let end = self.char_index(range.end);
+
+        self.contents.remove(start..end);
+        self.contents.insert(start, text);
+
+        // Any edit may open or close a chunk
+        (self.chunks, self.headings) = parse(&self.contents);
+    }
+
+    /// The R chunk whose code contains `row`, if any
+    pub fn r_chunk(&self, row: usize) -> Option<&Chunk> {
+        self.chunks
+            .iter()
+            .find(|chunk| chunk.is_r() && chunk.contains(row))
+    }
+
+    /// The R view of the document: the code of the R chunks, with all other
+    /// characters replaced by spaces. Each replaced character takes as many
+    /// spaces as it has UTF-16 code units so that LSP positions are the same
+    /// in the host document and in the R view. As the R chunks form a single
+    /// program, the variables of a chunk are in scope in the following ones.
+    pub fn r_view(&self) -> String {
+        let mut view = String::with_capacity(self.contents.len_bytes());
+
+        for (row, line) in self.contents.lines().enumerate() {
+            if self.r_chunk(row).is_some() {
+                view.extend(line.chars());
+                continue;
+            }
+
+            for char in line.chars() {
+                match char {
+                    '\n' | '\r' => view.push(char),
+                    _ => view.extend(std::iter::repeat(' ').take(char.len_utf16())),
+                }
+            }
+        }
+
+        view
+    }
+
+    fn char_index(&self, position: Position) -> usize {
+        let point = convert_position_to_point(&self.contents, position);
+        let byte = self.contents.point_to_byte(point);
+        self.contents.byte_to_char(byte)
+    }
+}
+
+/// Finds the code blocks and the headings of a host document. Lines starting
+/// with `#` are only headings outside of code blocks and of the YAML front
+/// matter.
+fn parse(contents: &Rope) -> (Vec<Chunk>, Vec<Heading>) {
+    let mut chunks = Vec::new();
+    let mut headings = Vec::new();
+
+    // The chunk we're in, with the length of its opening fence
+    let mut open: Option<(usize, Chunk)> = None;
+    let mut front_matter = false;
+
+    for (row, line) in contents.lines().enumerate() {
+        let line = line.to_string();
+        let line = line.trim_end_matches(['\n', '\r']);
+
+        if row == 0 && line == "---" {
+            front_matter = true;
+            continue;
+        }
+
+        if front_matter {
+            front_matter = !matches!(line, "---" | "...");
+            continue;
+        }
+
+        if let Some((fence, chunk)) = open.as_mut() {
+            let closes = RE_FENCE_END
+                .captures(line)
+                .is_some_and(|captures| captures[1].len() >= *fence);
+
+            if closes {
+                chunk.end = row;
+                chunks.push(chunk.clone());
+                open = None;
+            } else if chunk.label.is_none() {
+                if let Some(captures) = RE_QUARTO_LABEL.captures(line) {
+                    chunk.label = Some(captures[1].to_string());
+                }
+            }
+
+            continue;
+        }
+
+        if let Some(captures) = RE_FENCE_START.captures(line) {
+            let (engine, label) = chunk_header(&captures[2]);
+            let chunk = Chunk {
+                engine,
+                label,
+                start: row,
+                end: row,
+            };
+            open = Some((captures[1].len(), chunk));
+            continue;
+        }
+
+        if let Some(captures) = RE_HEADING.captures(line) {
+            headings.push(Heading {
+                level: captures[1].len(),
+                title: captures[2].to_string(),
+                row,
+            });
+        }
+    }
+
+    if let Some((_, mut chunk)) = open {
+        chunk.end = contents.len_lines();
+        chunks.push(chunk);
+    }
+
+    (chunks, headings)
+}
+
+/// The engine and the label of a chunk header. The label is either the first
+/// unnamed option, as in `{r setup}`, or the `label` option.
+fn chunk_header(header: &str) -> (Option<String>, Option<String>) {
+    let Some(captures) = RE_CHUNK_HEADER.captures(header) else {
+        return (None, None);
+    };
+
+    let engine = Some(captures[1].to_string());
+
+    let options = captures[2].trim_start_matches([',', ' ']);
+    let mut options = options.split(',').map(str::trim);
+
+    let label = match options.next() {
+        Some(first) if !first.is_empty() && !first.contains('=') => Some(first.to_string()),
+        first => first
+            .into_iter()
+            .chain(options)
+            .find_map(|option| RE_LABEL_OPTION.captures(option))
+            .map(|captures| captures[1].to_string()),
+    };
+
+    (engine, label.filter(|label| !label.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+    use tower_lsp::lsp_types::Range;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::rmarkdown::is_host_document;
+    use crate::lsp::rmarkdown::Chunk;
+    use crate::lsp::rmarkdown::Heading;
+    use crate::lsp::rmarkdown::HostDocument;
+
+    const TEXT: &str = "---
+title: \"Report\"
+# not a heading
+---
+
+# Setup
+
+```{r setup, echo = FALSE}
+x <- 1
+```
+
+## Résumé 🎉
+
+```{python}
+y = 2
+```
+
+```{r}
+#| label: plot
+x + 1
+```
+";
+
+    #[test]
+    fn test_host_document_chunks_and_headings() {
+        let host = HostDocument::new(TEXT);
+
+        assert_eq!(host.chunks, vec![
+            Chunk {
+                engine: Some(String::from("r")),
+                label: Some(String::from("setup")),
+                start: 7,
+                end: 9,
+            },
+            Chunk {
+                engine: Some(String::from("python")),
+                label: None,
+                start: 13,
+                end: 15,
+            },
+            Chunk {
+                engine: Some(String::from("r")),
+                label: Some(String::from("plot")),
+                start: 17,
+                end: 20,
+            },
+        ]);
+
+        assert_eq!(host.headings, vec![
+            Heading {
+                level: 1,
+                title: String::from("Setup"),
+                row: 5,
+            },
+            Heading {
+                level: 2,
+                title: String::from("Résumé 🎉"),
+                row: 11,
+            },
+        ]);
+
+        assert!(host.r_chunk(8).is_some());
+        assert!(host.r_chunk(9).is_none());
+        assert!(host.r_chunk(14).is_none());
+    }
+
+    #[test]
+    fn test_host_document_r_view() {
+        let host = HostDocument::new(TEXT);
+        let view = host.r_view();
+        let lines: Vec<&str> = view.lines().collect();
+
+        assert_eq!(lines.len(), host.contents.len_lines() - 1);
+        assert_eq!(lines[7], " ".repeat("```{r setup, echo = FALSE}".len()));
+        assert_eq!(lines[8], "x <- 1");
+        assert_eq!(lines[14], "     ");
+        assert_eq!(lines[18], "#| label: plot");
+
+        // One space per UTF-16 code unit
+        assert_eq!(lines[11], " ".repeat("## Résumé 🎉".encode_utf16().count()));
+    }
+
+    #[test]
+    fn test_host_document_update() {
+        let mut host = HostDocument::new("# Title\n\nx <- 1\n");
+        assert!(host.chunks.is_empty());
+
+        host.update(
+            Range::new(Position::new(2, 0), Position::new(2, 0)),
+            "```{r}\n",
+        );
+        assert_eq!(host.contents.to_string(), "# Title\n\n```{r}\nx <- 1\n");
+        assert!(host.r_chunk(3).is_some());
+
+        // Unterminated chunks extend to the end of the document
+        assert_eq!(host.r_view(), "       \n\n      \nx <- 1\n");
+    }
+
+    #[test]
+    fn test_is_host_document() {
+        assert!(is_host_document(
+            &Url::parse("file:///a/report.Rmd").unwrap()
+        ));
+        assert!(is_host_document(
+            &Url::parse("file:///a/report.qmd").unwrap()
+        ));
+        assert!(!is_host_document(
+            &Url::parse("file:///a/script.R").unwrap()
+        ));
+        assert!(!is_host_document(&Url::parse("file:///a/README").unwrap()));
+    }
+}
@@ -0,0 +1,471 @@
+//
+// roxygen.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use ropey::Rope;
+use tower_lsp::lsp_types::Diagnostic;
+use tower_lsp::lsp_types::DiagnosticSeverity;
+use tower_lsp::lsp_types::Range;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::encoding::convert_tree_sitter_range_to_lsp_range;
+use crate::treesitter::node_text;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// Tags that pull the documentation of parameters from elsewhere, or that
+/// don't generate documentation at all. Blocks using them aren't required to
+/// document every parameter.
+const INHERITING_TAGS: &[&str] = &[
+    "describeIn",
+    "eval",
+    "inherit",
+    "inheritDotParams",
+    "inheritParams",
+    "noRd",
+    "rdname",
+    "template",
+];
+
+/// The roxygen comments documenting a top-level expression
+#[derive(Debug)]
+pub(crate) struct RoxygenBlock {
+    lines: Vec<RoxygenLine>,
+}
+
+#[derive(Debug)]
+struct RoxygenLine {
+    /// Where the text following the `#'` prefix starts
+    start: Point,
+    text: String,
+}
+
+/// A tag of a roxygen block along with its contents. The contents start
+/// after the tag name and extend up to the next tag.
+struct RoxygenTag<'a> {
+    name: &'a str,
+    lines: Vec<&'a str>,
+}
+
+/// A parameter name documented by a `@param` tag
+#[derive(Debug, PartialEq)]
+pub(crate) struct RoxygenParam {
+    pub name: String,
+    pub start: Point,
+    pub end: Point,
+}
+
+impl RoxygenBlock {
+    /// Collects the `#'` comments right above `node`
+    pub(crate) fn new(node: &Node, contents: &Rope) -> Option<Self> {
+        let mut lines = Vec::new();
+        let mut row = node.start_position().row;
+        let mut current = node.prev_sibling();
+
+        while let Some(comment) = current {
+            if !comment.is_comment() || comment.end_position().row + 1 != row {
+                break;
+            }
+            let Some(text) = node_text(&comment, contents) else {
+                break;
+            };
+            let Some(text) = text.strip_prefix("#'") else {
+                break;
+            };
+
+            let start = comment.start_position();
+            lines.push(RoxygenLine {
+                start: Point::new(start.row, start.column + 2),
+                text: text.to_string(),
+            });
+
+            row = start.row;
+            current = comment.prev_sibling();
+        }
+
+        if lines.is_empty() {
+            return None;
+        }
+
+        lines.reverse();
+        Some(Self { lines })
+    }
+
+    pub(crate) fn has_tag(&self, name: &str) -> bool {
+        let (_, tags) = self.sections();
+        tags.iter().any(|tag| tag.name == name)
+    }
+
+    /// Whether the block generates a help topic, i.e. whether it has a title,
+    /// either as its first paragraph or with `@title`. Blocks only made of
+    /// tags like `@export` or `@importFrom` don't.
+    pub(crate) fn has_topic(&self) -> bool {
+        let (intro, tags) = self.sections();
+        intro.iter().any(|line| !line.trim().is_empty()) ||
+            tags.iter().any(|tag| tag.name == "title")
+    }
+
+    /// The parameters documented with `@param`. A single tag may document
+    /// several parameters separated by commas, like `@param x,y`.
+    pub(crate) fn params(&self) -> Vec<RoxygenParam> {
+        let mut params = Vec::new();
+
+        for line in self.lines.iter() {
+            let text = line.text.as_str();
+            let Some(rest) = text.trim_start().strip_prefix("@param") else {
+                continue;
+            };
+            if !rest.starts_with(char::is_whitespace) {
+                continue;
+            }
+
+            let rest = rest.trim_start();
+            let Some(names) = rest.split_whitespace().next() else {
+                continue;
+            };
+
+            let mut offset = text.len() - rest.len();
+            for name in names.split(',') {
+                if !name.is_empty() {
+                    let column = line.start.column + offset;
+                    params.push(RoxygenParam {
+                        name: name.to_string(),
+                        start: Point::new(line.start.row, column),
+                        end: Point::new(line.start.row, column + name.len()),
+                    });
+                }
+                offset += name.len() + 1;
+            }
+        }
+
+        params
+    }
+
+    /// Renders the block as markdown, in the spirit of the help pages
+    /// generated by roxygen. Tags that don't contribute to the help page
+    /// content, like `@export`, are left out.
+    pub(crate) fn markdown(&self) -> String {
+        let (intro, tags) = self.sections();
+
+        // Without explicit tags, the first paragraph is the title and the
+        // following ones are the description
+        let mut paragraphs = paragraphs(&intro).into_iter();
+        let mut title = paragraphs.next();
+        let mut description: Vec<String> = paragraphs.collect();
+
+        let mut details = Vec::new();
+        let mut params = Vec::new();
+        let mut value = None;
+        let mut examples = None;
+
+        for tag in tags.iter() {
+            match tag.name {
+                "title" => title = Some(tag.lines.join("\n").trim().to_string()),
+                "description" => description.push(tag.lines.join("\n").trim().to_string()),
+                "details" => details.push(tag.lines.join("\n").trim().to_string()),
+                "param" => {
+                    let text = tag.lines.join("\n");
+                    let text = text.trim();
+                    let (names, text) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+                    params.push(format!("- `{}`: {}", names, text.trim()));
+                },
+                "return" | "returns" => value = Some(tag.lines.join("\n").trim().to_string()),
+                "examples" => examples = Some(tag.lines.join("\n").trim().to_string()),
+                _ => {},
+            }
+        }
+
+        let mut sections = Vec::new();
+
+        if let Some(title) = title.filter(|title| !title.is_empty()) {
+            sections.push(format!("**{title}**"));
+        }
+        sections.extend(description.into_iter().filter(|text| !text.is_empty()));
+        sections.extend(details.into_iter().filter(|text| !text.is_empty()));
+
+        if !params.is_empty() {
+            sections.push(format!("**Parameters**\n\n{}", params.join("\n")));
+        }
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            sections.push(format!("**Value**\n\n{value}"));
+        }
+        if let Some(examples) = examples.filter(|examples| !examples.is_empty()) {
+            sections.push(format!("**Examples**\n\n```r\n{examples}\n```"));
+        }
+
+        sections.join("\n\n")
+    }
+
+    /// Splits the block into the lines preceding the first tag and the tags
+    fn sections(&self) -> (Vec<&str>, Vec<RoxygenTag>) {
+        let mut intro = Vec::new();
+        let mut tags: Vec<RoxygenTag> = Vec::new();
+
+        for line in self.lines.iter() {
+            // Roxygen comments conventionally start with a space
+            let text = line.text.strip_prefix(' ').unwrap_or(&line.text);
+
+            if let Some(tag) = text.trim_start().strip_prefix('@') {
+                if tag.starts_with(char::is_alphabetic) {
+                    let (name, rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+                    tags.push(RoxygenTag {
+                        name,
+                        lines: vec![rest],
+                    });
+                    continue;
+                }
+            }
+
+            match tags.last_mut() {
+                Some(tag) => tag.lines.push(text),
+                None => intro.push(text),
+            }
+        }
+
+        (intro, tags)
+    }
+}
+
+/// Groups lines into paragraphs separated by blank lines
+fn paragraphs(lines: &[&str]) -> Vec<String> {
+    lines
+        .split(|line| line.trim().is_empty())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.join("\n"))
+        .collect()
+}
+
+/// Returns the name and the function definition of a top-level assignment
+/// like `name <- function() {}`
+pub(crate) fn function_assignment<'tree>(
+    node: &Node<'tree>,
+    contents: &Rope,
+) -> Option<(String, Node<'tree>)> {
+    if !matches!(
+        node.node_type(),
+        NodeType::BinaryOperator(BinaryOperatorType::LeftAssignment) |
+            NodeType::BinaryOperator(BinaryOperatorType::EqualsAssignment)
+    ) {
+        return None;
+    }
+
+    let lhs = node.child_by_field_name("lhs")?;
+    let rhs = node.child_by_field_name("rhs")?;
+    if !lhs.is_identifier_or_string() || !rhs.is_function_definition() {
+        return None;
+    }
+
+    Some((node_text(&lhs, contents)?, rhs))
+}
+
+/// Returns the names of the parameters of a function definition, along
+/// with their nodes
+pub(crate) fn function_formals<'tree>(
+    function: &Node<'tree>,
+    contents: &Rope,
+) -> Vec<(String, Node<'tree>)> {
+    let Some(parameters) = function.child_by_field_name("parameters") else {
+        return Vec::new();
+    };
+
+    let mut cursor = parameters.walk();
+    parameters
+        .children_by_field_name("parameter", &mut cursor)
+        .filter_map(|parameter| parameter.child_by_field_name("name"))
+        .filter_map(|name| Some((node_text(&name, contents)?, name)))
+        .collect()
+}
+
+/// Checks the `@param` tags of the roxygen blocks against the formals of the
+/// functions they document. Parameters that aren't documented and
+/// documented parameters that don't exist are reported. Only the blocks that
+/// generate a help topic are checked.
+pub(crate) fn roxygen_diagnostics(document: &Document) -> Vec<Diagnostic> {
+    let contents = &document.contents;
+    let mut diagnostics = Vec::new();
+
+    let root = document.ast.root_node();
+    let mut cursor = root.walk();
+
+    for node in root.children(&mut cursor) {
+        let Some((name, function)) = function_assignment(&node, contents) else {
+            continue;
+        };
+        let Some(block) = RoxygenBlock::new(&node, contents) else {
+            continue;
+        };
+        if !block.has_topic() {
+            continue;
+        }
+
+        let formals = function_formals(&function, contents);
+        let params = block.params();
+
+        for param in params.iter() {
+            if formals.iter().any(|(formal, _)| *formal == param.name) {
+                continue;
+            }
+            let range = Range::new(
+                convert_point_to_position(contents, param.start),
+                convert_point_to_position(contents, param.end),
+            );
+            let message = format!(
+                "documented parameter '{}' is not an argument of '{}'",
+                param.name, name
+            );
+            diagnostics.push(warning(range, message));
+        }
+
+        if INHERITING_TAGS.iter().any(|tag| block.has_tag(tag)) {
+            continue;
+        }
+
+        for (formal, node) in formals.iter() {
+            if params.iter().any(|param| param.name == *formal) {
+                continue;
+            }
+            let range = convert_tree_sitter_range_to_lsp_range(contents, node.range());
+            let message = format!("parameter '{}' of '{}' is not documented", formal, name);
+            diagnostics.push(warning(range, message));
+        }
+    }
+
+    diagnostics
+}
+
+fn warning(range: Range, message: String) -> Diagnostic {
+    let mut diagnostic = Diagnostic::new_simple(range, message);
+    diagnostic.severity = Some(DiagnosticSeverity::WARNING);
+    diagnostic
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::roxygen::roxygen_diagnostics;
+    use crate::lsp::roxygen::RoxygenBlock;
+    use crate::lsp::roxygen::RoxygenParam;
+
+    fn block(text: &str) -> RoxygenBlock {
+        let document = Document::new(text, None);
+        let root = document.ast.root_node();
+        let node = root.named_child(root.named_child_count() - 1).unwrap();
+        RoxygenBlock::new(&node, &document.contents).unwrap()
+    }
+
+    #[test]
+    fn test_roxygen_params() {
+        let block =
+            block("#' Title\n#' @param x,y Values\n#'   @param z\nf <- function(x, y, z) x\n");
+        assert_eq!(block.params(), vec![
+            RoxygenParam {
+                name: String::from("x"),
+                start: Point::new(1, 10),
+                end: Point::new(1, 11),
+            },
+            RoxygenParam {
+                name: String::from("y"),
+                start: Point::new(1, 12),
+                end: Point::new(1, 13),
+            },
+            RoxygenParam {
+                name: String::from("z"),
+                start: Point::new(2, 12),
+                end: Point::new(2, 13),
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_roxygen_markdown() {
+        let text = "# Not part of the block
+
+#' Add numbers
+#'
+#' Adds `x` to `y`.
+#'
+#' @param x A number.
+#' @param y Another number,
+#'   possibly negative.
+#' @returns The sum.
+#' @export
+#' @examples
+#' add(1, 2)
+add <- function(x, y) x + y
+";
+        assert_eq!(
+            block(text).markdown(),
+            "**Add numbers**
+
+Adds `x` to `y`.
+
+**Parameters**
+
+- `x`: A number.
+- `y`: Another number,
+  possibly negative.
+
+**Value**
+
+The sum.
+
+**Examples**
+
+```r
+add(1, 2)
+```"
+        );
+    }
+
+    #[test]
+    fn test_roxygen_diagnostics() {
+        let text = "#' Title
+#' @param x A value
+#' @param z Not an argument
+f <- function(x, ...) x
+
+#' Inherits its parameters
+#' @inheritParams f
+g <- function(x, y) x
+
+#' Not roxygen
+# @param x
+h <- function(x) x
+
+#' @export
+#' @importFrom stats median
+#' @keywords internal
+i <- function(x) x
+
+#' @description No title
+j <- function(x) x
+
+#' @title Uses a template
+#' @template params
+k <- function(x) x
+
+#' Evaluates its parameters
+#' @eval params()
+l <- function(x) x
+";
+        let document = Document::new(text, None);
+        let messages: Vec<String> = roxygen_diagnostics(&document)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+
+        assert_eq!(messages, vec![
+            String::from("documented parameter 'z' is not an argument of 'f'"),
+            String::from("parameter '...' of 'f' is not documented"),
+        ]);
+    }
+}
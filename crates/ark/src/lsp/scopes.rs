@@ -0,0 +1,457 @@
+//
+// scopes.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use ropey::Rope;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::documents::Document;
+use crate::treesitter::node_text;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+
+/// The scope of the top level of a document
+pub(crate) const ROOT: usize = 0;
+
+struct Scope<'tree> {
+    /// The function definition creating the scope, or the program for the
+    /// top level
+    node: Node<'tree>,
+    parent: Option<usize>,
+    bindings: HashSet<String>,
+    parameters: HashSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum OccurrenceKind {
+    /// Refers to a binding of the variable
+    Reference,
+    /// Binds the variable in the scope of the occurrence
+    Binding,
+    /// Binds the variable in an enclosing scope, with `<<-` or `->>`
+    SuperBinding,
+}
+
+impl OccurrenceKind {
+    pub(crate) fn is_binding(&self) -> bool {
+        matches!(self, Self::Binding | Self::SuperBinding)
+    }
+}
+
+/// An occurrence of a variable in a document
+pub(crate) struct Occurrence<'tree> {
+    pub node: Node<'tree>,
+    pub name: String,
+    /// The scope where the occurrence is evaluated
+    pub scope: usize,
+    pub kind: OccurrenceKind,
+}
+
+/// The lexical scopes of a document, shared by the features resolving
+/// variables. Scopes are created by function definitions, and variables are
+/// bound by parameters, `for` loops and assignments. As R looks variables up
+/// when they are evaluated, a binding applies to its whole scope regardless
+/// of where it occurs in the scope.
+///
+/// Super-assignments bind in the closest enclosing scope that already has a
+/// binding, or at top level.
+///
+/// Bindings at top level are global: they are shared by all the files of the
+/// workspace along with the symbols of the session. A `None` scope stands for
+/// this global scope.
+///
+/// These rules differ on purpose from the ones of `DiagnosticContext`, which
+/// follows the order of evaluation: diagnostics only consider a symbol to be
+/// in scope after its definition, so that using a variable before assigning
+/// it is reported. That order doesn't change which binding a name refers to,
+/// which is all that renames, highlights and semantic tokens need. In
+/// `f <- function() x; x <- 1`, the diagnostics report the first `x` while
+/// both refer to the same global binding here.
+pub(crate) struct Scopes<'tree> {
+    scopes: Vec<Scope<'tree>>,
+
+    /// The occurrences of variables in evaluation order, so that the value of
+    /// an assignment comes before its target
+    occurrences: Vec<Occurrence<'tree>>,
+
+    /// The indices of the occurrences, keyed by node ID
+    by_node: HashMap<usize, usize>,
+
+    super_assignments: Vec<(String, usize)>,
+}
+
+impl<'tree> Scopes<'tree> {
+    pub(crate) fn new(document: &'tree Document) -> Self {
+        let root = document.ast.root_node();
+
+        let mut scopes = Self {
+            scopes: vec![Scope {
+                node: root,
+                parent: None,
+                bindings: HashSet::new(),
+                parameters: HashSet::new(),
+            }],
+            occurrences: Vec::new(),
+            by_node: HashMap::new(),
+            super_assignments: Vec::new(),
+        };
+
+        scopes.collect(root, ROOT, &document.contents);
+
+        for (name, scope) in std::mem::take(&mut scopes.super_assignments) {
+            let target = scopes.scopes[scope]
+                .parent
+                .and_then(|parent| scopes.binding_scope(&name, parent))
+                .unwrap_or(ROOT);
+            scopes.scopes[target].bindings.insert(name);
+        }
+
+        scopes
+    }
+
+    fn collect(&mut self, node: Node<'tree>, scope: usize, contents: &Rope) {
+        match node.node_type() {
+            NodeType::FunctionDefinition => {
+                self.scopes.push(Scope {
+                    node,
+                    parent: Some(scope),
+                    bindings: HashSet::new(),
+                    parameters: HashSet::new(),
+                });
+                let scope = self.scopes.len() - 1;
+                self.collect_children(node, scope, contents);
+            },
+            NodeType::Parameter => {
+                if let Some(name) = node.child_by_field_name("name") {
+                    if let Some(name) = self.bind(name, scope, OccurrenceKind::Binding, contents) {
+                        self.scopes[scope].parameters.insert(name);
+                    }
+                }
+                if let Some(default) = node.child_by_field_name("default") {
+                    self.collect(default, scope, contents);
+                }
+            },
+            NodeType::ForStatement => {
+                if let Some(sequence) = node.child_by_field_name("sequence") {
+                    self.collect(sequence, scope, contents);
+                }
+                if let Some(variable) = node.child_by_field_name("variable") {
+                    self.bind(variable, scope, OccurrenceKind::Binding, contents);
+                }
+                if let Some(body) = node.child_by_field_name("body") {
+                    self.collect(body, scope, contents);
+                }
+            },
+            NodeType::BinaryOperator(op) => match op {
+                BinaryOperatorType::LeftAssignment | BinaryOperatorType::EqualsAssignment => self
+                    .collect_assignment(
+                        node,
+                        "rhs",
+                        "lhs",
+                        OccurrenceKind::Binding,
+                        scope,
+                        contents,
+                    ),
+                BinaryOperatorType::RightAssignment => self.collect_assignment(
+                    node,
+                    "lhs",
+                    "rhs",
+                    OccurrenceKind::Binding,
+                    scope,
+                    contents,
+                ),
+                BinaryOperatorType::LeftSuperAssignment => self.collect_assignment(
+                    node,
+                    "rhs",
+                    "lhs",
+                    OccurrenceKind::SuperBinding,
+                    scope,
+                    contents,
+                ),
+                BinaryOperatorType::RightSuperAssignment => self.collect_assignment(
+                    node,
+                    "lhs",
+                    "rhs",
+                    OccurrenceKind::SuperBinding,
+                    scope,
+                    contents,
+                ),
+                _ => self.collect_children(node, scope, contents),
+            },
+            NodeType::Argument => {
+                // The argument name refers to a parameter of the callee
+                if let Some(value) = node.child_by_field_name("value") {
+                    self.collect(value, scope, contents);
+                }
+            },
+            NodeType::ExtractOperator(_) => {
+                // The right-hand side is a name, not a variable
+                if let Some(lhs) = node.child_by_field_name("lhs") {
+                    self.collect(lhs, scope, contents);
+                }
+            },
+            NodeType::NamespaceOperator(_) => {
+                // Refers to a package, not to the scopes of the document
+            },
+            NodeType::Identifier => {
+                self.push(node, scope, OccurrenceKind::Reference, contents);
+            },
+            _ => self.collect_children(node, scope, contents),
+        }
+    }
+
+    fn collect_children(&mut self, node: Node<'tree>, scope: usize, contents: &Rope) {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.collect(child, scope, contents);
+        }
+    }
+
+    fn collect_assignment(
+        &mut self,
+        node: Node<'tree>,
+        value: &str,
+        target: &str,
+        kind: OccurrenceKind,
+        scope: usize,
+        contents: &Rope,
+    ) {
+        if let Some(value) = node.child_by_field_name(value) {
+            self.collect(value, scope, contents);
+        }
+        if let Some(target) = node.child_by_field_name(target) {
+            self.bind(target, scope, kind, contents);
+        }
+    }
+
+    /// Binds the variable `node` if it's an identifier, and collects it
+    /// otherwise, e.g. for `names(x) <- value`. Returns the bound name.
+    fn bind(
+        &mut self,
+        node: Node<'tree>,
+        scope: usize,
+        kind: OccurrenceKind,
+        contents: &Rope,
+    ) -> Option<String> {
+        if !node.is_identifier() {
+            self.collect(node, scope, contents);
+            return None;
+        }
+
+        let name = self.push(node, scope, kind, contents)?;
+        match kind {
+            OccurrenceKind::SuperBinding => self.super_assignments.push((name.clone(), scope)),
+            _ => {
+                self.scopes[scope].bindings.insert(name.clone());
+            },
+        }
+        Some(name)
+    }
+
+    fn push(
+        &mut self,
+        node: Node<'tree>,
+        scope: usize,
+        kind: OccurrenceKind,
+        contents: &Rope,
+    ) -> Option<String> {
+        let name = node_text(&node, contents)?;
+        self.by_node.insert(node.id(), self.occurrences.len());
+        self.occurrences.push(Occurrence {
+            node,
+            name: name.clone(),
+            scope,
+            kind,
+        });
+        Some(name)
+    }
+
+    /// Returns the closest scope binding `name`, starting from `scope`
+    fn binding_scope(&self, name: &str, scope: usize) -> Option<usize> {
+        let mut scope = scope;
+        loop {
+            if self.scopes[scope].bindings.contains(name) {
+                return Some(scope);
+            }
+            scope = self.scopes[scope].parent?;
+        }
+    }
+
+    /// Returns the scope that `name` refers to when evaluated in `scope`, or
+    /// `None` for the global scope
+    pub(crate) fn resolve(&self, name: &str, scope: usize) -> Option<usize> {
+        self.binding_scope(name, scope)
+            .filter(|scope| *scope != ROOT)
+    }
+
+    /// Returns the scope that `name` refers to at `occurrence`, or `None` for
+    /// the global scope. Super-assignments look `name` up from the enclosing
+    /// scope, as R does.
+    pub(crate) fn resolve_at(&self, name: &str, occurrence: &Occurrence) -> Option<usize> {
+        let scope = match occurrence.kind {
+            OccurrenceKind::SuperBinding => self.scopes[occurrence.scope].parent?,
+            _ => occurrence.scope,
+        };
+        self.resolve(name, scope)
+    }
+
+    /// Whether `name` is bound at the top level of the document
+    pub(crate) fn is_global_binding(&self, name: &str) -> bool {
+        self.scopes[ROOT].bindings.contains(name)
+    }
+
+    /// Whether `name` is bound in `scope`, or at top level for `None`
+    pub(crate) fn is_bound(&self, name: &str, scope: Option<usize>) -> bool {
+        self.scopes[scope.unwrap_or(ROOT)].bindings.contains(name)
+    }
+
+    /// Whether `name` is a parameter of the function creating `scope`
+    pub(crate) fn is_parameter(&self, name: &str, scope: usize) -> bool {
+        self.scopes[scope].parameters.contains(name)
+    }
+
+    /// Whether `scope` is `target` or is nested in it. Every scope is nested
+    /// in the global scope.
+    pub(crate) fn is_within(&self, scope: usize, target: Option<usize>) -> bool {
+        let Some(target) = target else {
+            return true;
+        };
+
+        let mut scope = scope;
+        loop {
+            if scope == target {
+                return true;
+            }
+            scope = match self.scopes[scope].parent {
+                Some(parent) => parent,
+                None => return false,
+            };
+        }
+    }
+
+    /// Returns the scope created by `node`, a function definition or the
+    /// program
+    pub(crate) fn scope_of(&self, node: &Node) -> Option<usize> {
+        self.scopes.iter().position(|scope| scope.node == *node)
+    }
+
+    /// The occurrences of variables in evaluation order
+    pub(crate) fn occurrences(&self) -> &[Occurrence<'tree>] {
+        &self.occurrences
+    }
+
+    /// Returns the occurrence of a variable at `node`, if any
+    pub(crate) fn occurrence(&self, node: &Node) -> Option<&Occurrence<'tree>> {
+        let index = self.by_node.get(&node.id())?;
+        Some(&self.occurrences[*index])
+    }
+
+    pub(crate) fn occurrence_at(&self, point: Point) -> Option<&Occurrence<'tree>> {
+        // The position may be at the end of the identifier, for instance
+        // after a double-click selection
+        self.occurrences
+            .iter()
+            .find(|occurrence| {
+                occurrence.node.start_position() <= point && point < occurrence.node.end_position()
+            })
+            .or_else(|| {
+                self.occurrences
+                    .iter()
+                    .find(|occurrence| occurrence.node.end_position() == point)
+            })
+    }
+
+    /// The occurrences referring to the binding of `name` in `target`, in
+    /// document order
+    pub(crate) fn references(&self, name: &str, target: Option<usize>) -> Vec<&Occurrence<'tree>> {
+        let mut references: Vec<&Occurrence> = self
+            .occurrences
+            .iter()
+            .filter(|occurrence| {
+                occurrence.name == name && self.resolve_at(name, occurrence) == target
+            })
+            .collect();
+        references.sort_by_key(|occurrence| occurrence.node.start_byte());
+        references
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::scopes::OccurrenceKind;
+    use crate::lsp::scopes::Scopes;
+
+    #[test]
+    fn test_scopes_evaluation_order() {
+        let document = Document::new("x <- x + 1\nfor (i in i) i\n", None);
+        let scopes = Scopes::new(&document);
+
+        let occurrences: Vec<(usize, usize, OccurrenceKind)> = scopes
+            .occurrences()
+            .iter()
+            .map(|occurrence| {
+                let start = occurrence.node.start_position();
+                (start.row, start.column, occurrence.kind)
+            })
+            .collect();
+
+        assert_eq!(occurrences, vec![
+            (0, 5, OccurrenceKind::Reference),
+            (0, 0, OccurrenceKind::Binding),
+            (1, 10, OccurrenceKind::Reference),
+            (1, 5, OccurrenceKind::Binding),
+            (1, 13, OccurrenceKind::Reference),
+        ]);
+    }
+
+    #[test]
+    fn test_scopes_super_assignment() {
+        let text =
+            "f <- function() {\n  x <- 1\n  function() x <<- 2\n}\ng <- function() y <<- 1\n";
+        let document = Document::new(text, None);
+        let scopes = Scopes::new(&document);
+
+        // Binds in the enclosing function that already has a binding
+        let x = scopes.occurrence_at(Point::new(2, 13)).unwrap();
+        assert_eq!(x.kind, OccurrenceKind::SuperBinding);
+        assert_eq!(scopes.resolve_at("x", x), Some(1));
+
+        // Or at top level
+        let y = scopes.occurrence_at(Point::new(4, 16)).unwrap();
+        assert_eq!(scopes.resolve_at("y", y), None);
+        assert!(scopes.is_global_binding("y"));
+    }
+
+    #[test]
+    fn test_scopes_super_assignment_skips_local_binding() {
+        let text = "x <- 0\nf <- function() {\n  x <- 1\n  x <<- 2\n}\n";
+        let document = Document::new(text, None);
+        let scopes = Scopes::new(&document);
+
+        let rows = |target| -> Vec<usize> {
+            scopes
+                .references("x", target)
+                .into_iter()
+                .map(|occurrence| occurrence.node.start_position().row)
+                .collect()
+        };
+
+        // The super-assignment binds the global `x` even though `f` has a
+        // local `x`
+        let x = scopes.occurrence_at(Point::new(3, 2)).unwrap();
+        assert_eq!(scopes.resolve_at("x", x), None);
+        assert_eq!(rows(None), vec![0, 3]);
+        assert_eq!(rows(Some(1)), vec![2]);
+    }
+}
@@ -0,0 +1,588 @@
+//
+// semantic_tokens.rs
+//
+// Copyright (C) 2024 Posit Software, PBC. All rights reserved.
+//
+//
+
+use ropey::Rope;
+use tower_lsp::lsp_types::SemanticToken;
+use tower_lsp::lsp_types::SemanticTokenModifier;
+use tower_lsp::lsp_types::SemanticTokenType;
+use tower_lsp::lsp_types::SemanticTokens;
+use tower_lsp::lsp_types::SemanticTokensEdit;
+use tower_lsp::lsp_types::SemanticTokensLegend;
+use tree_sitter::Node;
+use tree_sitter::Point;
+
+use crate::lsp::diagnostics::DiagnosticContext;
+use crate::lsp::documents::Document;
+use crate::lsp::encoding::convert_point_to_position;
+use crate::lsp::scopes::Scopes;
+use crate::lsp::state::WorldState;
+use crate::treesitter::node_text;
+use crate::treesitter::BinaryOperatorType;
+use crate::treesitter::NodeType;
+use crate::treesitter::NodeTypeExt;
+use crate::treesitter::UnaryOperatorType;
+
+/// Token types, in the order of the legend sent to the client
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TokenType {
+    Namespace = 0,
+    Function = 1,
+    Parameter = 2,
+    Variable = 3,
+    Property = 4,
+}
+
+/// Token modifiers, as bits in the order of the legend sent to the client
+mod modifier {
+    pub const DECLARATION: u32 = 1 << 0;
+    pub const LOCAL: u32 = 1 << 1;
+    pub const GLOBAL: u32 = 1 << 2;
+    pub const CALL: u32 = 1 << 3;
+    pub const NAMESPACED: u32 = 1 << 4;
+    pub const FORMULA: u32 = 1 << 5;
+    pub const NSE: u32 = 1 << 6;
+}
+
+/// Functions quoting their arguments, whose symbols are not evaluated in the
+/// usual way
+static NSE_FUNCTIONS: &[&str] = &[
+    "alist",
+    "bquote",
+    "expression",
+    "library",
+    "quote",
+    "require",
+    "requireNamespace",
+    "subset",
+    "substitute",
+    "transform",
+    "with",
+    "within",
+];
+
+pub(crate) fn semantic_tokens_legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: vec![
+            SemanticTokenType::NAMESPACE,
+            SemanticTokenType::FUNCTION,
+            SemanticTokenType::PARAMETER,
+            SemanticTokenType::VARIABLE,
+            SemanticTokenType::PROPERTY,
+        ],
+        token_modifiers: vec![
+            SemanticTokenModifier::DECLARATION,
+            SemanticTokenModifier::new("local"),
+            SemanticTokenModifier::new("global"),
+            SemanticTokenModifier::new("call"),
+            SemanticTokenModifier::new("namespaced"),
+            SemanticTokenModifier::new("formula"),
+            SemanticTokenModifier::new("nse"),
+        ],
+    }
+}
+
+struct Token {
+    start: Point,
+    end: Point,
+    token_type: TokenType,
+    modifiers: u32,
+}
+
+#[derive(Clone, Copy, Default)]
+struct TokensContext {
+    /// Whether we're inside of a formula
+    in_formula: bool,
+
+    /// Whether we're within the arguments of a quoting function
+    in_nse: bool,
+}
+
+struct TokensBuilder<'a> {
+    contents: &'a Rope,
+
+    /// The scopes of the document, resolving its variables
+    scopes: Scopes<'a>,
+
+    /// The symbols of the workspace and the session, for the variables that
+    /// are not bound in the document
+    globals: DiagnosticContext<'a>,
+
+    tokens: Vec<Token>,
+}
+
+pub(crate) fn semantic_tokens(
+    document: &Document,
+    state: &WorldState,
+) -> anyhow::Result<Vec<SemanticToken>> {
+    let mut builder = TokensBuilder {
+        contents: &document.contents,
+        scopes: Scopes::new(document),
+        globals: DiagnosticContext::new(&document.contents, state),
+        tokens: Vec::new(),
+    };
+
+    builder.recurse(document.ast.root_node(), &mut TokensContext::default());
+
+    Ok(builder.encode())
+}
+
+/// Computes the edits turning `previous` tokens into `current` ones. Edits
+/// are expressed in terms of the flattened integer array of the protocol,
+/// where each token takes up five integers.
+pub(crate) fn semantic_tokens_edits(
+    previous: &[SemanticToken],
+    current: &[SemanticToken],
+) -> Vec<SemanticTokensEdit> {
+    let prefix = previous
+        .iter()
+        .zip(current.iter())
+        .take_while(|(lhs, rhs)| lhs == rhs)
+        .count();
+
+    let suffix = previous[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(lhs, rhs)| lhs == rhs)
+        .count();
+
+    let deleted = previous.len() - prefix - suffix;
+    let inserted = &current[prefix..current.len() - suffix];
+
+    if deleted == 0 && inserted.is_empty() {
+        return Vec::new();
+    }
+
+    vec![SemanticTokensEdit {
+        start: (prefix * 5) as u32,
+        delete_count: (deleted * 5) as u32,
+        data: Some(inserted.to_vec()),
+    }]
+}
+
+/// Result ids are increasing counters, one sequence per document
+pub(crate) fn semantic_tokens_result_id(previous: Option<&SemanticTokens>) -> String {
+    let previous = previous
+        .and_then(|tokens| tokens.result_id.as_ref())
+        .and_then(|id| id.parse::<u64>().ok());
+
+    match previous {
+        Some(id) => (id + 1).to_string(),
+        None => String::from("0"),
+    }
+}
+
+impl<'a> TokensBuilder<'a> {
+    fn recurse(&mut self, node: Node<'a>, context: &mut TokensContext) {
+        match node.node_type() {
+            NodeType::FunctionDefinition => self.recurse_function(node, context),
+            NodeType::ForStatement => self.recurse_for(node, context),
+            NodeType::Call => self.recurse_call(node, context),
+            NodeType::Argument => self.recurse_argument(node, context),
+            NodeType::NamespaceOperator(_) => self.recurse_namespace(node, false),
+            NodeType::ExtractOperator(_) => self.recurse_extract(node, context),
+            NodeType::Identifier => self.push_reference(node, context, TokenType::Variable, 0),
+            NodeType::BinaryOperator(op) => match op {
+                BinaryOperatorType::Tilde => self.recurse_formula(node, context),
+                BinaryOperatorType::LeftAssignment |
+                BinaryOperatorType::EqualsAssignment |
+                BinaryOperatorType::LeftSuperAssignment => {
+                    self.recurse_assignment(node, "lhs", "rhs", context)
+                },
+                BinaryOperatorType::RightAssignment | BinaryOperatorType::RightSuperAssignment => {
+                    self.recurse_assignment(node, "rhs", "lhs", context)
+                },
+                _ => self.recurse_children(node, context),
+            },
+            NodeType::UnaryOperator(UnaryOperatorType::Tilde) => {
+                self.recurse_formula(node, context)
+            },
+            _ => self.recurse_children(node, context),
+        }
+    }
+
+    fn recurse_children(&mut self, node: Node<'a>, context: &mut TokensContext) {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            self.recurse(child, context);
+        }
+    }
+
+    fn recurse_function(&mut self, node: Node<'a>, context: &mut TokensContext) {
+        if let Some(parameters) = node.child_by_field_name("parameters") {
+            let mut cursor = parameters.walk();
+            for parameter in parameters.children_by_field_name("parameter", &mut cursor) {
+                if let Some(name) = parameter.child_by_field_name("name") {
+                    self.push(
+                        name,
+                        TokenType::Parameter,
+                        modifier::DECLARATION | modifier::LOCAL,
+                    );
+                }
+                if let Some(default) = parameter.child_by_field_name("default") {
+                    self.recurse(default, context);
+                }
+            }
+        }
+
+        if let Some(body) = node.child_by_field_name("body") {
+            self.recurse(body, context);
+        }
+    }
+
+    fn recurse_for(&mut self, node: Node<'a>, context: &mut TokensContext) {
+        if let Some(sequence) = node.child_by_field_name("sequence") {
+            self.recurse(sequence, context);
+        }
+
+        if let Some(variable) = node.child_by_field_name("variable") {
+            self.push_declaration(variable, TokenType::Variable, context);
+        }
+
+        if let Some(body) = node.child_by_field_name("body") {
+            self.recurse(body, context);
+        }
+    }
+
+    fn recurse_formula(&mut self, node: Node<'a>, context: &mut TokensContext) {
+        let mut context = *context;
+        context.in_formula = true;
+        self.recurse_children(node, &mut context);
+    }
+
+    fn recurse_assignment(
+        &mut self,
+        node: Node<'a>,
+        target: &str,
+        value: &str,
+        context: &mut TokensContext,
+    ) {
+        let target = node.child_by_field_name(target);
+        let value = node.child_by_field_name(value);
+
+        if let Some(target) = target {
+            if target.is_identifier() {
+                let is_function = value.is_some_and(|value| value.is_function_definition());
+                let token_type = match is_function {
+                    true => TokenType::Function,
+                    false => TokenType::Variable,
+                };
+                self.push_declaration(target, token_type, context);
+            } else {
+                self.recurse(target, context);
+            }
+        }
+
+        if let Some(value) = value {
+            self.recurse(value, context);
+        }
+    }
+
+    fn recurse_call(&mut self, node: Node<'a>, context: &mut TokensContext) {
+        let Some(callee) = node.child_by_field_name("function") else {
+            return self.recurse_children(node, context);
+        };
+
+        // The name of the function, when we can tell it syntactically
+        let mut fun = None;
+
+        if callee.is_identifier() {
+            fun = node_text(&callee, self.contents);
+            self.push_reference(callee, context, TokenType::Function, modifier::CALL);
+        } else if matches!(callee.node_type(), NodeType::NamespaceOperator(_)) {
+            fun = callee
+                .child_by_field_name("rhs")
+                .and_then(|rhs| node_text(&rhs, self.contents));
+            self.recurse_namespace(callee, true);
+        } else {
+            self.recurse(callee, context);
+        }
+
+        let Some(arguments) = node.child_by_field_name("arguments") else {
+            return;
+        };
+
+        let in_nse = fun.is_some_and(|fun| NSE_FUNCTIONS.contains(&fun.as_str()));
+        if !in_nse {
+            return self.recurse(arguments, context);
+        }
+
+        let mut context = *context;
+        context.in_nse = true;
+        self.recurse(arguments, &mut context);
+    }
+
+    fn recurse_argument(&mut self, node: Node<'a>, context: &mut TokensContext) {
+        // Argument names are not symbols, only recurse into the value
+        if let Some(value) = node.child_by_field_name("value") {
+            self.recurse(value, context);
+        }
+    }
+
+    /// Tokenises `pkg::name`. When the operator is the callee of a call, the
+    /// right-hand side is a function.
+    fn recurse_namespace(&mut self, node: Node<'a>, is_call: bool) {
+        if let Some(lhs) = node.child_by_field_name("lhs") {
+            if lhs.is_identifier() {
+                self.push(lhs, TokenType::Namespace, 0);
+            }
+        }
+
+        if let Some(rhs) = node.child_by_field_name("rhs") {
+            if rhs.is_identifier() {
+                if is_call {
+                    let modifiers = modifier::CALL | modifier::NAMESPACED;
+                    self.push(rhs, TokenType::Function, modifiers);
+                } else {
+                    self.push(rhs, TokenType::Variable, modifier::NAMESPACED);
+                }
+            }
+        }
+    }
+
+    fn recurse_extract(&mut self, node: Node<'a>, context: &mut TokensContext) {
+        if let Some(lhs) = node.child_by_field_name("lhs") {
+            self.recurse(lhs, context);
+        }
+
+        if let Some(rhs) = node.child_by_field_name("rhs") {
+            if rhs.is_identifier() {
+                self.push(rhs, TokenType::Property, self.context_modifiers(context));
+            }
+        }
+    }
+
+    fn push_declaration(
+        &mut self,
+        node: Node<'a>,
+        token_type: TokenType,
+        context: &mut TokensContext,
+    ) {
+        if !node.is_identifier() {
+            return self.recurse(node, context);
+        }
+
+        let Some(occurrence) = self.scopes.occurrence(&node) else {
+            return;
+        };
+
+        // Super-assignments bind in an enclosing scope, other assignments in
+        // the current scope
+        let scope = match self.scopes.resolve_at(&occurrence.name, occurrence) {
+            Some(_) => modifier::LOCAL,
+            None => modifier::GLOBAL,
+        };
+
+        let modifiers = modifier::DECLARATION | scope | self.context_modifiers(context);
+        self.push(node, token_type, modifiers);
+    }
+
+    /// Pushes an identifier referring to a binding, resolved through the
+    /// scopes of the document, then the workspace and the session
+    fn push_reference(
+        &mut self,
+        node: Node<'a>,
+        context: &mut TokensContext,
+        token_type: TokenType,
+        modifiers: u32,
+    ) {
+        let Some(occurrence) = self.scopes.occurrence(&node) else {
+            return;
+        };
+        let name = occurrence.name.as_str();
+
+        let mut token_type = token_type;
+        let mut modifiers = modifiers | self.context_modifiers(context);
+
+        if let Some(scope) = self.scopes.resolve_at(name, occurrence) {
+            modifiers |= modifier::LOCAL;
+            if token_type == TokenType::Variable && self.scopes.is_parameter(name, scope) {
+                token_type = TokenType::Parameter;
+            }
+        } else if self.scopes.is_global_binding(name) || self.globals.has_definition(name) {
+            modifiers |= modifier::GLOBAL;
+        }
+
+        self.push(node, token_type, modifiers);
+    }
+
+    fn context_modifiers(&self, context: &TokensContext) -> u32 {
+        let mut modifiers = 0;
+        if context.in_formula {
+            modifiers |= modifier::FORMULA;
+        }
+        if context.in_nse {
+            modifiers |= modifier::NSE;
+        }
+        modifiers
+    }
+
+    fn push(&mut self, node: Node<'a>, token_type: TokenType, modifiers: u32) {
+        self.tokens.push(Token {
+            start: node.start_position(),
+            end: node.end_position(),
+            token_type,
+            modifiers,
+        });
+    }
+
+    /// Encodes tokens relative to each other, as required by the protocol
+    fn encode(mut self) -> Vec<SemanticToken> {
+        self.tokens.sort_by_key(|token| token.start);
+
+        let mut data = Vec::with_capacity(self.tokens.len());
+        let mut line = 0;
+        let mut character = 0;
+
+        for token in self.tokens.iter() {
+            // Tokens can't span multiple lines
+            if token.start.row != token.end.row {
+                continue;
+            }
+
+            let start = convert_point_to_position(self.contents, token.start);
+            let end = convert_point_to_position(self.contents, token.end);
+
+            let delta_line = start.line - line;
+            let delta_start = match delta_line {
+                0 => start.character - character,
+                _ => start.character,
+            };
+
+            data.push(SemanticToken {
+                delta_line,
+                delta_start,
+                length: end.character - start.character,
+                token_type: token.token_type as u32,
+                token_modifiers_bitset: token.modifiers,
+            });
+
+            line = start.line;
+            character = start.character;
+        }
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::SemanticToken;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::semantic_tokens::modifier;
+    use crate::lsp::semantic_tokens::semantic_tokens;
+    use crate::lsp::semantic_tokens::semantic_tokens_edits;
+    use crate::lsp::semantic_tokens::TokenType;
+    use crate::lsp::state::WorldState;
+
+    /// Decodes tokens to absolute `(line, character, length, type, modifiers)`
+    fn tokens(text: &str) -> Vec<(u32, u32, u32, u32, u32)> {
+        let document = Document::new(text, None);
+        let tokens = semantic_tokens(&document, &WorldState::default()).unwrap();
+
+        let mut line = 0;
+        let mut character = 0;
+
+        tokens
+            .into_iter()
+            .map(|token| {
+                if token.delta_line > 0 {
+                    character = 0;
+                }
+                line += token.delta_line;
+                character += token.delta_start;
+                (
+                    line,
+                    character,
+                    token.length,
+                    token.token_type,
+                    token.token_modifiers_bitset,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_semantic_tokens_scopes() {
+        let text = "x <- 1\nf <- function(a) {\n  y <- a + x\n  y\n}\n";
+        let decl = modifier::DECLARATION;
+
+        assert_eq!(tokens(text), vec![
+            (0, 0, 1, TokenType::Variable as u32, decl | modifier::GLOBAL),
+            (1, 0, 1, TokenType::Function as u32, decl | modifier::GLOBAL),
+            (
+                1,
+                14,
+                1,
+                TokenType::Parameter as u32,
+                decl | modifier::LOCAL
+            ),
+            (2, 2, 1, TokenType::Variable as u32, decl | modifier::LOCAL),
+            (2, 7, 1, TokenType::Parameter as u32, modifier::LOCAL),
+            (2, 11, 1, TokenType::Variable as u32, modifier::GLOBAL),
+            (3, 2, 1, TokenType::Variable as u32, modifier::LOCAL),
+        ]);
+    }
+
+    #[test]
+    fn test_semantic_tokens_calls() {
+        let text = "dplyr::filter(df, x > 1)\nfoo(bar = baz)\n";
+
+        assert_eq!(tokens(text), vec![
+            (0, 0, 5, TokenType::Namespace as u32, 0),
+            (
+                0,
+                7,
+                6,
+                TokenType::Function as u32,
+                modifier::CALL | modifier::NAMESPACED,
+            ),
+            (0, 14, 2, TokenType::Variable as u32, 0),
+            (0, 18, 1, TokenType::Variable as u32, 0),
+            (1, 0, 3, TokenType::Function as u32, modifier::CALL),
+            (1, 10, 3, TokenType::Variable as u32, 0),
+        ]);
+    }
+
+    #[test]
+    fn test_semantic_tokens_nse() {
+        let text = "lm(y ~ x)\nquote(z)\ndf$col\n";
+
+        assert_eq!(tokens(text), vec![
+            (0, 0, 2, TokenType::Function as u32, modifier::CALL),
+            (0, 3, 1, TokenType::Variable as u32, modifier::FORMULA),
+            (0, 7, 1, TokenType::Variable as u32, modifier::FORMULA),
+            (1, 0, 5, TokenType::Function as u32, modifier::CALL),
+            (1, 6, 1, TokenType::Variable as u32, modifier::NSE),
+            (2, 0, 2, TokenType::Variable as u32, 0),
+            (2, 3, 3, TokenType::Property as u32, 0),
+        ]);
+    }
+
+    #[test]
+    fn test_semantic_tokens_edits() {
+        let token = |delta_line| SemanticToken {
+            delta_line,
+            delta_start: 0,
+            length: 1,
+            token_type: 0,
+            token_modifiers_bitset: 0,
+        };
+
+        let previous = vec![token(0), token(1), token(2)];
+        let current = vec![token(0), token(3), token(4), token(2)];
+
+        let edits = semantic_tokens_edits(&previous, &current);
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].start, 5);
+        assert_eq!(edits[0].delete_count, 5);
+        assert_eq!(edits[0].data, Some(vec![token(3), token(4)]));
+
+        assert!(semantic_tokens_edits(&previous, &previous).is_empty());
+    }
+}
@@ -1,11 +1,15 @@
 use std::collections::HashMap;
 use std::path::Path;
+use std::path::PathBuf;
 
 use anyhow::anyhow;
 use url::Url;
 
 use crate::lsp::config::LspConfig;
 use crate::lsp::documents::Document;
+use crate::lsp::indexer;
+use crate::lsp::package::Package;
+use crate::lsp::rmarkdown;
 
 #[derive(Clone, Default, Debug)]
 /// The world state, i.e. all the inputs necessary for analysing or refactoring
@@ -50,6 +54,25 @@ pub(crate) struct WorldState {
 #[derive(Clone, Default, Debug)]
 pub(crate) struct Workspace {
     pub folders: Vec<Url>,
+
+    /// The R packages found at the root of the watched folders
+    pub packages: Vec<Package>,
+}
+
+impl Workspace {
+    /// Finds the package that the document at `uri` belongs to, if any
+    pub(crate) fn package(&self, uri: &Url) -> Option<&Package> {
+        let path = uri.to_file_path().ok()?;
+        self.packages.iter().find(|package| package.contains(&path))
+    }
+
+    /// The R files in the watched folders
+    pub(crate) fn r_files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.folders
+            .iter()
+            .filter_map(|folder| folder.to_file_path().ok())
+            .flat_map(|folder| indexer::r_files(&folder))
+    }
 }
 
 impl WorldState {
@@ -80,7 +103,12 @@ where
 {
     let mut fallback = || {
         let contents = std::fs::read_to_string(path)?;
-        let document = Document::new(contents.as_str(), None);
+        let document = match Url::from_file_path(path) {
+            Ok(uri) if rmarkdown::is_host_document(&uri) => {
+                Document::new_host(contents.as_str(), None)
+            },
+            _ => Document::new(contents.as_str(), None),
+        };
         return callback(&document);
     };
 
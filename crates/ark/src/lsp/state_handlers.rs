@@ -5,26 +5,39 @@
 //
 //
 
+use std::collections::HashSet;
 use std::path::Path;
 
 use anyhow::anyhow;
 use serde_json::Value;
 use struct_field_names_as_array::FieldNamesAsArray;
+use tower_lsp::lsp_types::CallHierarchyServerCapability;
+use tower_lsp::lsp_types::CodeActionKind;
+use tower_lsp::lsp_types::CodeActionOptions;
+use tower_lsp::lsp_types::CodeActionProviderCapability;
 use tower_lsp::lsp_types::CompletionOptions;
 use tower_lsp::lsp_types::ConfigurationItem;
 use tower_lsp::lsp_types::DidChangeConfigurationParams;
 use tower_lsp::lsp_types::DidChangeTextDocumentParams;
+use tower_lsp::lsp_types::DidChangeWatchedFilesParams;
+use tower_lsp::lsp_types::DidChangeWorkspaceFoldersParams;
 use tower_lsp::lsp_types::DidCloseTextDocumentParams;
 use tower_lsp::lsp_types::DidOpenTextDocumentParams;
+use tower_lsp::lsp_types::DidSaveTextDocumentParams;
 use tower_lsp::lsp_types::DocumentOnTypeFormattingOptions;
 use tower_lsp::lsp_types::ExecuteCommandOptions;
+use tower_lsp::lsp_types::FoldingRangeProviderCapability;
 use tower_lsp::lsp_types::FormattingOptions;
 use tower_lsp::lsp_types::HoverProviderCapability;
 use tower_lsp::lsp_types::ImplementationProviderCapability;
 use tower_lsp::lsp_types::InitializeParams;
 use tower_lsp::lsp_types::InitializeResult;
 use tower_lsp::lsp_types::OneOf;
+use tower_lsp::lsp_types::RenameOptions;
 use tower_lsp::lsp_types::SelectionRangeProviderCapability;
+use tower_lsp::lsp_types::SemanticTokensFullOptions;
+use tower_lsp::lsp_types::SemanticTokensOptions;
+use tower_lsp::lsp_types::SemanticTokensServerCapabilities;
 use tower_lsp::lsp_types::ServerCapabilities;
 use tower_lsp::lsp_types::ServerInfo;
 use tower_lsp::lsp_types::SignatureHelpOptions;
@@ -42,13 +55,19 @@ use crate::lsp::config::indent_style_from_lsp;
 use crate::lsp::config::DocumentConfig;
 use crate::lsp::config::VscDiagnosticsConfig;
 use crate::lsp::config::VscDocumentConfig;
+use crate::lsp::config::VscInlayHintsConfig;
 use crate::lsp::diagnostics::DiagnosticsConfig;
 use crate::lsp::documents::Document;
 use crate::lsp::encoding::get_position_encoding_kind;
 use crate::lsp::indexer;
+use crate::lsp::inlay_hints::InlayHintsConfig;
 use crate::lsp::main_loop::LspState;
+use crate::lsp::package::Package;
+use crate::lsp::rmarkdown;
+use crate::lsp::semantic_tokens::semantic_tokens_legend;
 use crate::lsp::state::workspace_uris;
 use crate::lsp::state::WorldState;
+use crate::r_task;
 
 // Handlers that mutate the world state
 
@@ -82,6 +101,10 @@ pub(crate) fn initialize(
         {
             lsp_state.needs_registration.did_change_configuration = true;
         }
+        if matches!(ws_caps.did_change_watched_files, Some(caps) if matches!(caps.dynamic_registration, Some(true)))
+        {
+            lsp_state.needs_registration.did_change_watched_files = true;
+        }
     }
 
     // Initialize the workspace folders
@@ -89,6 +112,9 @@ pub(crate) fn initialize(
     if let Some(workspace_folders) = params.workspace_folders {
         for folder in workspace_folders.iter() {
             state.workspace.folders.push(folder.uri.clone());
+            if let Some(package) = load_package(&folder.uri) {
+                state.workspace.packages.push(package);
+            }
             if let Ok(path) = folder.uri.to_file_path() {
                 if let Some(path) = path.to_str() {
                     folders.push(path.to_string());
@@ -114,6 +140,16 @@ pub(crate) fn initialize(
                 TextDocumentSyncKind::INCREMENTAL,
             )),
             selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+            folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            semantic_tokens_provider: Some(
+                SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: semantic_tokens_legend(),
+                    full: Some(SemanticTokensFullOptions::Delta { delta: Some(true) }),
+                    range: None,
+                    ..Default::default()
+                }),
+            ),
             hover_provider: Some(HoverProviderCapability::from(true)),
             completion_provider: Some(CompletionOptions {
                 resolve_provider: Some(true),
@@ -133,6 +169,21 @@ pub(crate) fn initialize(
             type_definition_provider: None,
             implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
             references_provider: Some(OneOf::Left(true)),
+            document_highlight_provider: Some(OneOf::Left(true)),
+            call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                code_action_kinds: Some(vec![
+                    CodeActionKind::QUICKFIX,
+                    CodeActionKind::REFACTOR_EXTRACT,
+                    CodeActionKind::REFACTOR_REWRITE,
+                ]),
+                work_done_progress_options: Default::default(),
+                resolve_provider: None,
+            })),
+            rename_provider: Some(OneOf::Right(RenameOptions {
+                prepare_provider: Some(true),
+                work_done_progress_options: Default::default(),
+            })),
             document_symbol_provider: Some(OneOf::Left(true)),
             workspace_symbol_provider: Some(OneOf::Left(true)),
             execute_command_provider: Some(ExecuteCommandOptions {
@@ -150,6 +201,8 @@ pub(crate) fn initialize(
                 first_trigger_character: String::from("\n"),
                 more_trigger_character: None,
             }),
+            document_formatting_provider: Some(OneOf::Left(true)),
+            document_range_formatting_provider: Some(OneOf::Left(true)),
             ..ServerCapabilities::default()
         },
     })
@@ -169,7 +222,11 @@ pub(crate) fn did_open(
     let mut parser = Parser::new();
     parser.set_language(&language).unwrap();
 
-    let document = Document::new_with_parser(contents, &mut parser, Some(version));
+    let document = if rmarkdown::is_host_document(&uri) {
+        Document::new_host_with_parser(contents, &mut parser, Some(version))
+    } else {
+        Document::new_with_parser(contents, &mut parser, Some(version))
+    };
 
     lsp_state.parsers.insert(uri.clone(), parser);
     state.documents.insert(uri.clone(), document.clone());
@@ -226,6 +283,8 @@ pub(crate) fn did_close(
         .remove(&uri)
         .ok_or(anyhow!("Failed to remove parser for URI: {uri}"))?;
 
+    lsp_state.semantic_tokens.remove(&uri);
+
     lsp::log_info!("did_close(): closed document with URI: '{uri}'.");
 
     Ok(())
@@ -292,6 +351,16 @@ async fn update_config(
         .collect();
     items.append(&mut diagnostics_items);
 
+    let inlay_hints_keys = VscInlayHintsConfig::FIELD_NAMES_AS_ARRAY;
+    let mut inlay_hints_items: Vec<ConfigurationItem> = inlay_hints_keys
+        .iter()
+        .map(|key| ConfigurationItem {
+            scope_uri: None,
+            section: Some(VscInlayHintsConfig::section_from_key(key).into()),
+        })
+        .collect();
+    items.append(&mut inlay_hints_items);
+
     // For document configs we collect all pairs of URIs and config keys of
     // interest in a flat vector
     let document_keys = VscDocumentConfig::FIELD_NAMES_AS_ARRAY;
@@ -312,7 +381,8 @@ async fn update_config(
     // by chunk
     let n_document_items = document_keys.len();
     let n_diagnostics_items = diagnostics_keys.len();
-    let n_items = n_diagnostics_items + (n_document_items * uris.len());
+    let n_inlay_hints_items = inlay_hints_keys.len();
+    let n_items = n_diagnostics_items + n_inlay_hints_items + (n_document_items * uris.len());
 
     if configs.len() != n_items {
         return Err(anyhow!(
@@ -347,6 +417,13 @@ async fn update_config(
         lsp::spawn_diagnostics_refresh_all(state.clone());
     }
 
+    // --- Inlay hints
+    let items: Vec<Value> = configs.by_ref().take(n_inlay_hints_items).collect();
+
+    let config = VscInlayHintsConfig::from_values(items)?;
+    let config: InlayHintsConfig = config.into();
+    state.config.inlay_hints = config;
+
     // --- Documents
     // For each document, deserialise the vector of JSON values into a typed config
     for uri in uris.into_iter() {
@@ -371,6 +448,65 @@ async fn update_config(
     Ok(())
 }
 
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn did_change_workspace_folders(
+    params: DidChangeWorkspaceFoldersParams,
+    state: &mut WorldState,
+) -> anyhow::Result<()> {
+    for folder in params.event.removed.iter() {
+        state.workspace.folders.retain(|uri| *uri != folder.uri);
+        if let Ok(path) = folder.uri.to_file_path() {
+            state
+                .workspace
+                .packages
+                .retain(|package| package.path != path);
+        }
+    }
+
+    let mut folders: Vec<String> = Vec::new();
+    for folder in params.event.added.iter() {
+        state.workspace.folders.push(folder.uri.clone());
+        if let Some(package) = load_package(&folder.uri) {
+            state.workspace.packages.push(package);
+        }
+        if let Ok(path) = folder.uri.to_file_path() {
+            if let Some(path) = path.to_str() {
+                folders.push(path.to_string());
+            }
+        }
+    }
+
+    // TODO: Remove the files of the removed folders from the index.
+    lsp::spawn_blocking(|| {
+        indexer::start(folders);
+        Ok(None)
+    });
+
+    lsp::spawn_diagnostics_refresh_all(state.clone());
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn did_change_watched_files(
+    params: DidChangeWatchedFilesParams,
+    state: &mut WorldState,
+) -> anyhow::Result<()> {
+    reload_packages(params.changes.iter().map(|change| &change.uri), state);
+    Ok(())
+}
+
+#[tracing::instrument(level = "info", skip_all)]
+pub(crate) fn did_save(
+    params: DidSaveTextDocumentParams,
+    state: &mut WorldState,
+) -> anyhow::Result<()> {
+    // Clients that can't watch files still let us know about edits of the
+    // package metadata made in the editor
+    reload_packages(std::iter::once(&params.text_document.uri), state);
+    Ok(())
+}
+
 #[tracing::instrument(level = "info", skip_all)]
 pub(crate) fn did_change_console_inputs(
     inputs: ConsoleInputs,
@@ -388,6 +524,75 @@ pub(crate) fn did_change_console_inputs(
     Ok(())
 }
 
+/// Loads the R package at the root of a workspace folder, if there is one.
+/// The exports of its imports are resolved from the installed packages.
+fn load_package(folder: &Url) -> Option<Package> {
+    let path = folder.to_file_path().ok()?;
+
+    let mut package = match Package::load(&path) {
+        Ok(package) => package?,
+        Err(err) => {
+            lsp::log_error!("Can't load package at {}: {err:?}", path.display());
+            return None;
+        },
+    };
+
+    if let Err(err) = r_task(|| package.resolve_imports()) {
+        lsp::log_error!(
+            "Can't resolve the imports of package '{}': {err:?}",
+            package.description.name
+        );
+    }
+
+    Some(package)
+}
+
+/// Reloads the packages of the workspace folders whose DESCRIPTION or
+/// NAMESPACE file is among `uris`, then refreshes diagnostics since they
+/// depend on the imports of the package.
+fn reload_packages<'a>(uris: impl Iterator<Item = &'a Url>, state: &mut WorldState) {
+    let roots: HashSet<_> = uris
+        .filter_map(|uri| uri.to_file_path().ok())
+        .filter(|path| {
+            matches!(
+                path.file_name().and_then(|name| name.to_str()),
+                Some("DESCRIPTION" | "NAMESPACE")
+            )
+        })
+        .filter_map(|path| path.parent().map(|root| root.to_path_buf()))
+        .collect();
+
+    let folders: Vec<Url> = state
+        .workspace
+        .folders
+        .iter()
+        .filter(|folder| {
+            folder
+                .to_file_path()
+                .is_ok_and(|path| roots.contains(&path))
+        })
+        .cloned()
+        .collect();
+
+    if folders.is_empty() {
+        return;
+    }
+
+    for folder in folders.iter() {
+        if let Ok(path) = folder.to_file_path() {
+            state
+                .workspace
+                .packages
+                .retain(|package| package.path != path);
+        }
+        if let Some(package) = load_package(folder) {
+            state.workspace.packages.push(package);
+        }
+    }
+
+    lsp::spawn_diagnostics_refresh_all(state.clone());
+}
+
 // FIXME: The initial indexer is currently racing against our state notification
 // handlers. The indexer is synchronised through a mutex but we might end up in
 // a weird state. Eventually the index should be moved to WorldState and created
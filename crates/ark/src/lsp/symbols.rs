@@ -16,6 +16,7 @@ use stdext::unwrap::IntoResult;
 use tower_lsp::lsp_types::DocumentSymbol;
 use tower_lsp::lsp_types::DocumentSymbolParams;
 use tower_lsp::lsp_types::Location;
+use tower_lsp::lsp_types::Position;
 use tower_lsp::lsp_types::Range;
 use tower_lsp::lsp_types::SymbolInformation;
 use tower_lsp::lsp_types::SymbolKind;
@@ -26,6 +27,7 @@ use tree_sitter::Node;
 use crate::lsp::encoding::convert_point_to_position;
 use crate::lsp::indexer;
 use crate::lsp::indexer::IndexEntryData;
+use crate::lsp::rmarkdown::HostDocument;
 use crate::lsp::state::WorldState;
 use crate::lsp::traits::rope::RopeExt;
 use crate::lsp::traits::string::StringExt;
@@ -57,6 +59,20 @@ pub fn symbols(params: &WorkspaceSymbolParams) -> anyhow::Result<Vec<SymbolInfor
                 });
             },
 
+            IndexEntryData::Variable { name } => {
+                info.push(SymbolInformation {
+                    name: name.to_string(),
+                    kind: SymbolKind::VARIABLE,
+                    location: Location {
+                        uri: Url::from_file_path(path).unwrap(),
+                        range: entry.range,
+                    },
+                    tags: None,
+                    deprecated: None,
+                    container_name: None,
+                });
+            },
+
             IndexEntryData::Section { level: _, title } => {
                 info.push(SymbolInformation {
                     name: title.to_string(),
@@ -107,8 +123,114 @@ pub(crate) fn document_symbols(
     // index from the root
     index_node(&node, &contents, &mut root, &mut symbols)?;
 
-    // return the children we found
-    Ok(root.children.unwrap_or_default())
+    let symbols = root.children.unwrap_or_default();
+
+    // host documents are outlined by their headings and chunks
+    match &document.host {
+        Some(host) => Ok(host_symbols(host, symbols)),
+        None => Ok(symbols),
+    }
+}
+
+/// Outlines an R Markdown or Quarto document. Labelled chunks nest under the
+/// section they belong to, and the R symbols under their chunk or section.
+fn host_symbols(host: &HostDocument, symbols: Vec<DocumentSymbol>) -> Vec<DocumentSymbol> {
+    let contents = &host.contents;
+    let last_row = contents.len_lines().saturating_sub(1);
+
+    // Symbols paired with their depth. Headings take their level, from 1 to 6.
+    const CHUNK_DEPTH: usize = 7;
+    const SYMBOL_DEPTH: usize = 8;
+
+    let mut entries: Vec<(usize, DocumentSymbol)> = Vec::new();
+
+    for (i, heading) in host.headings.iter().enumerate() {
+        // A section extends up to the next heading of the same or a higher level
+        let end = host.headings[i + 1..]
+            .iter()
+            .find(|other| other.level <= heading.level)
+            .map(|other| other.row - 1)
+            .unwrap_or(last_row);
+
+        let symbol = outline_symbol(
+            &heading.title,
+            SymbolKind::MODULE,
+            heading.row,
+            end,
+            contents,
+        );
+        entries.push((heading.level, symbol));
+    }
+
+    for chunk in host.chunks.iter() {
+        let Some(label) = &chunk.label else {
+            continue;
+        };
+
+        let end = chunk.end.min(last_row);
+        let symbol = outline_symbol(label, SymbolKind::NAMESPACE, chunk.start, end, contents);
+        entries.push((CHUNK_DEPTH, symbol));
+    }
+
+    entries.extend(symbols.into_iter().map(|symbol| (SYMBOL_DEPTH, symbol)));
+    entries.sort_by_key(|(depth, symbol)| (symbol.range.start.line, *depth));
+
+    // Nest each entry in the closest enclosing entry of a lower depth
+    let mut outline = Vec::new();
+    let mut stack: Vec<(usize, DocumentSymbol)> = Vec::new();
+
+    let close = |stack: &mut Vec<(usize, DocumentSymbol)>, outline: &mut Vec<DocumentSymbol>| {
+        let (_, symbol) = stack.pop().unwrap();
+        match stack.last_mut() {
+            Some((_, parent)) => parent.children.get_or_insert_with(Vec::new).push(symbol),
+            None => outline.push(symbol),
+        }
+    };
+
+    for (depth, symbol) in entries {
+        while let Some((parent_depth, parent)) = stack.last() {
+            if *parent_depth < depth && parent.range.end.line >= symbol.range.start.line {
+                break;
+            }
+            close(&mut stack, &mut outline);
+        }
+        stack.push((depth, symbol));
+    }
+
+    while !stack.is_empty() {
+        close(&mut stack, &mut outline);
+    }
+
+    outline
+}
+
+fn outline_symbol(
+    name: &str,
+    kind: SymbolKind,
+    start: usize,
+    end: usize,
+    contents: &Rope,
+) -> DocumentSymbol {
+    let line = contents.line(start).to_string();
+    let line = line.trim_end_matches(['\n', '\r']);
+    let header_end = Position::new(start as u32, line.encode_utf16().count() as u32);
+
+    let line = contents.line(end).to_string();
+    let line = line.trim_end_matches(['\n', '\r']);
+    let end = Position::new(end as u32, line.encode_utf16().count() as u32);
+
+    let start = Position::new(start as u32, 0);
+
+    DocumentSymbol {
+        name: name.to_string(),
+        kind,
+        detail: None,
+        children: Some(Vec::new()),
+        deprecated: None,
+        tags: None,
+        range: Range::new(start, end),
+        selection_range: Range::new(start, header_end),
+    }
 }
 
 fn is_indexable(node: &Node) -> bool {
@@ -255,3 +377,72 @@ fn index_assignment_with_function(
 
     Ok(true)
 }
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::DocumentSymbol;
+    use tower_lsp::lsp_types::DocumentSymbolParams;
+    use tower_lsp::lsp_types::SymbolKind;
+    use tower_lsp::lsp_types::TextDocumentIdentifier;
+    use tower_lsp::lsp_types::Url;
+
+    use crate::lsp::documents::Document;
+    use crate::lsp::state::WorldState;
+    use crate::lsp::symbols::document_symbols;
+
+    fn names(symbols: &[DocumentSymbol]) -> Vec<(String, SymbolKind)> {
+        symbols
+            .iter()
+            .map(|symbol| (symbol.name.clone(), symbol.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_document_symbols_host_document() {
+        let text = "# Setup
+
+```{r setup}
+x <- 1
+```
+
+## Helpers
+
+```{r}
+f <- function() x
+```
+";
+        let document = Document::new_host(text, None);
+
+        let uri = Url::parse("file:///report.Rmd").unwrap();
+        let mut state = WorldState::default();
+        state.documents.insert(uri.clone(), document);
+
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let symbols = document_symbols(&state, &params).unwrap();
+
+        assert_eq!(names(&symbols), vec![(
+            String::from("Setup"),
+            SymbolKind::MODULE
+        )]);
+
+        let setup = symbols[0].children.as_ref().unwrap();
+        assert_eq!(names(setup), vec![
+            (String::from("setup"), SymbolKind::NAMESPACE),
+            (String::from("Helpers"), SymbolKind::MODULE),
+        ]);
+
+        let chunk = setup[0].children.as_ref().unwrap();
+        assert_eq!(names(chunk), vec![(String::from("x"), SymbolKind::OBJECT)]);
+
+        let helpers = setup[1].children.as_ref().unwrap();
+        assert_eq!(names(helpers), vec![(
+            String::from("f"),
+            SymbolKind::FUNCTION
+        )]);
+        assert_eq!(helpers[0].range.start.line, 9);
+    }
+}
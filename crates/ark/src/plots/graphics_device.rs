@@ -23,26 +23,34 @@
 /// https://github.com/rstudio/rstudio/blob/main/src/cpp/r/session/graphics/RGraphicsDevice.cpp
 ///
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
-use std::io::Read;
+use std::collections::VecDeque;
+use std::time::Duration;
 
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::event::CommManagerEvent;
+use amalthea::comm::plot_comm::LocateParams;
+use amalthea::comm::plot_comm::LocatorParams;
 use amalthea::comm::plot_comm::PlotBackendReply;
 use amalthea::comm::plot_comm::PlotBackendRequest;
+use amalthea::comm::plot_comm::PlotDevice;
 use amalthea::comm::plot_comm::PlotFrontendEvent;
+use amalthea::comm::plot_comm::PlotRecord;
 use amalthea::comm::plot_comm::PlotResult;
+use amalthea::comm::plot_comm::PlotSource;
 use amalthea::comm::plot_comm::RenderFormat;
+use amalthea::comm::plot_history_comm::PlotHistoryBackendReply;
+use amalthea::comm::plot_history_comm::PlotHistoryBackendRequest;
 use amalthea::socket::comm::CommInitiator;
 use amalthea::socket::comm::CommSocket;
 use amalthea::socket::iopub::IOPubMessage;
 use amalthea::wire::display_data::DisplayData;
 use amalthea::wire::update_display_data::TransientValue;
 use amalthea::wire::update_display_data::UpdateDisplayData;
+use anyhow::anyhow;
 use anyhow::bail;
 use base64::engine::general_purpose;
 use base64::Engine;
+use chrono::Utc;
 use crossbeam::channel::Select;
 use crossbeam::channel::Sender;
 use harp::exec::RFunction;
@@ -51,18 +59,33 @@ use harp::object::RObject;
 use libr::pDevDesc;
 use libr::pGEcontext;
 use libr::R_NilValue;
+use libr::Rboolean;
+use libr::Rboolean_FALSE;
+use libr::Rboolean_TRUE;
 use libr::Rf_ScalarLogical;
 use libr::SEXP;
 use once_cell::sync::Lazy;
+use serde::Deserialize;
 use serde_json::json;
 use stdext::result::ResultOrLog;
 use stdext::unwrap;
 use uuid::Uuid;
 
+use crate::interface::RMain;
+use crate::interface::SessionMode;
+use crate::modules::ARK_ENVS;
 use crate::r_task;
+use crate::signals::interrupts_pending;
 
 const POSITRON_PLOT_CHANNEL_ID: &str = "positron.plot";
 
+// The maximum number of rendered plots kept in the render cache.
+const RENDER_CACHE_CAPACITY: usize = 32;
+
+// How long to wait for plot comm messages at a time while `locator()` waits
+// for a click, before checking for interrupts.
+const LOCATOR_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 macro_rules! trace {
     ($($tts:tt)*) => {{
         let message = format!($($tts)*);
@@ -80,6 +103,98 @@ struct DeviceCallbacks {
     pub newPage: Option<unsafe extern "C" fn(pGEcontext, pDevDesc)>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct RenderCacheKey {
+    id: String,
+    width: i64,
+    height: i64,
+    // The bits of the `f64` pixel ratio, since floats aren't hashable
+    pixel_ratio: u64,
+    format: String,
+}
+
+impl RenderCacheKey {
+    fn new(id: &str, width: i64, height: i64, pixel_ratio: f64, format: &RenderFormat) -> Self {
+        Self {
+            id: id.to_string(),
+            width,
+            height,
+            pixel_ratio: pixel_ratio.to_bits(),
+            format: format.to_string(),
+        }
+    }
+}
+
+/// A cache of rendered plots, so that e.g. resizing the Plots pane back and
+/// forth doesn't replay the plot in R each time. The oldest renders are
+/// evicted first once the cache is full.
+#[derive(Default)]
+struct RenderCache {
+    renders: HashMap<RenderCacheKey, String>,
+    order: VecDeque<RenderCacheKey>,
+}
+
+impl RenderCache {
+    fn get(&self, key: &RenderCacheKey) -> Option<&String> {
+        self.renders.get(key)
+    }
+
+    fn insert(&mut self, key: RenderCacheKey, data: String) {
+        if self.renders.insert(key.clone(), data).is_some() {
+            return;
+        }
+
+        self.order.push_back(key);
+        if self.order.len() > RENDER_CACHE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.renders.remove(&oldest);
+            }
+        }
+    }
+
+    /// Remove all renders of a plot, e.g. because the plot has changed.
+    fn invalidate(&mut self, id: &str) {
+        self.renders.retain(|key, _| key.id != id);
+        self.order.retain(|key| key.id != id);
+    }
+}
+
+/// Where a plot comes from, as recorded when the plot page is created.
+#[derive(Debug, Default, Clone, Deserialize)]
+struct PlotProvenance {
+    source: Option<PlotSource>,
+    device: Option<PlotDevice>,
+}
+
+/// The state of a `locator()` call waiting for a click on the current plot.
+#[derive(Debug)]
+enum LocatorState {
+    Waiting,
+    Clicked(LocateParams),
+    Cancelled,
+}
+
+/// The extent of the device surface, in device units.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct DeviceBounds {
+    left: f64,
+    right: f64,
+    bottom: f64,
+    top: f64,
+}
+
+impl DeviceBounds {
+    /// Map a click on a plot rendered at `width` x `height` pixels to device
+    /// units. The frontend renders the plot with the same layout as the
+    /// device while locating, so the relative position of the click is the
+    /// same on the device.
+    fn device_position(&self, click: &LocateParams) -> (f64, f64) {
+        let x = self.left + (click.x / click.width as f64) * (self.right - self.left);
+        let y = self.top + (click.y / click.height as f64) * (self.bottom - self.top);
+        (x, y)
+    }
+}
+
 #[derive(Default)]
 struct DeviceContext {
     // Tracks whether the graphics device has changes.
@@ -108,8 +223,32 @@ struct DeviceContext {
     // for communicating their rendered results to the frontend.
     pub _channels: HashMap<String, CommSocket>,
 
+    // The comm opened by the frontend to manage the plot history, if any.
+    pub _history_channel: Option<CommSocket>,
+
     // The device callbacks, which are patched into the device.
     pub _callbacks: DeviceCallbacks,
+
+    // The rendered plots, keyed by plot ID and render parameters.
+    pub _render_cache: RenderCache,
+
+    // The plot history, i.e. the plots that have been created and not
+    // deleted, oldest first.
+    pub _records: Vec<PlotRecord>,
+
+    // The comms of plots deleted by a request. They are closed once the
+    // request has been replied to.
+    pub _closing: Vec<CommSocket>,
+
+    // The state of the ongoing `locator()` call, if any.
+    pub _locator: Option<LocatorState>,
+
+    // The provenance of the next plot page, recorded by the plot hooks
+    // before the page is created.
+    pub _pending_provenance: Option<PlotProvenance>,
+
+    // The provenance of the current plot page.
+    pub _provenance: PlotProvenance,
 }
 
 impl DeviceContext {
@@ -127,6 +266,7 @@ impl DeviceContext {
         let id = Uuid::new_v4().to_string();
         self._id = Some(id.clone());
         self._new_page = true;
+        self._provenance = self._pending_provenance.take().unwrap_or_default();
     }
 
     pub fn on_did_execute_request(
@@ -134,12 +274,20 @@ impl DeviceContext {
         comm_manager_tx: Sender<CommManagerEvent>,
         iopub_tx: Sender<IOPubMessage>,
         dynamic_plots: bool,
+        code: &str,
+        execution_count: u32,
     ) {
         // After R code has completed execution, we use this to check if any graphics
         // need to be created
         if self._changes {
             self._changes = false;
-            self.process_changes(comm_manager_tx, iopub_tx, dynamic_plots);
+            self.process_changes(
+                comm_manager_tx,
+                iopub_tx,
+                dynamic_plots,
+                code,
+                execution_count,
+            );
         }
     }
 
@@ -154,10 +302,35 @@ impl DeviceContext {
             return;
         }
 
+        self.process_message(None);
+    }
+
+    pub fn on_plot_history_open(&mut self, socket: CommSocket) {
+        // Only the latest frontend manages the history
+        if let Some(socket) = self._history_channel.replace(socket) {
+            socket
+                .outgoing_tx
+                .send(CommMsg::Close)
+                .or_log_error("Failed to close plot history comm.");
+        }
+    }
+
+    /// Handle the next message received on a plot comm or on the plot
+    /// history comm, if any. Waits for up to `timeout` for a message, or
+    /// returns immediately if `None`.
+    fn process_message(&mut self, timeout: Option<Duration>) {
         // Collect existing channels into a vector of tuples.
         // Necessary for handling Select in a clean way.
-        let channels = self._channels.clone();
-        let channels = channels.iter().collect::<Vec<_>>();
+        // The plot history comm has no plot ID.
+        let mut channels = self
+            ._channels
+            .clone()
+            .into_iter()
+            .map(|(id, channel)| (Some(id), channel))
+            .collect::<Vec<_>>();
+        if let Some(channel) = &self._history_channel {
+            channels.push((None, channel.clone()));
+        }
 
         // Check for incoming plot render requests.
         let mut select = Select::new();
@@ -165,22 +338,52 @@ impl DeviceContext {
             select.recv(&channel.incoming_rx);
         }
 
-        let selection = unwrap!(select.try_select(), Err(_error) => {
+        let selection = match timeout {
+            Some(timeout) => select.select_timeout(timeout).ok(),
+            None => select.try_select().ok(),
+        };
+        let selection = unwrap!(selection, None => {
             // We don't log errors here, since it's most likely that none
             // of the channels have any messages available.
             return;
         });
 
-        let plot_id = unsafe { channels.get_unchecked(selection.index()).0 };
-        let socket = unsafe { channels.get_unchecked(selection.index()).1 };
+        let (plot_id, socket) = unsafe { channels.get_unchecked(selection.index()) };
         let message = unwrap!(selection.recv(&socket.incoming_rx), Err(error) => {
             log::error!("{}", error);
             return;
         });
 
-        // Get the RPC request.
-        if socket.handle_request(message, |req| self.handle_rpc(req, plot_id)) {
-            return;
+        match plot_id {
+            Some(plot_id) => {
+                // The frontend closed the plot, e.g. because it was removed
+                // from the Plots pane
+                if let CommMsg::Close = message {
+                    self.remove_plot(plot_id);
+                    return;
+                }
+
+                // Get the RPC request.
+                socket.handle_request(message, |req| self.handle_rpc(req, plot_id));
+            },
+            None => {
+                // The plots themselves outlive the history comm
+                if let CommMsg::Close = message {
+                    self._history_channel = None;
+                    return;
+                }
+
+                socket.handle_request(message, |req| self.handle_history_rpc(req));
+            },
+        }
+
+        // Close the comms of the plots deleted by the request, now that it
+        // has been replied to
+        for socket in self._closing.drain(..) {
+            socket
+                .outgoing_tx
+                .send(CommMsg::Close)
+                .or_log_error("Failed to close plot comm.");
         }
     }
 
@@ -205,9 +408,213 @@ impl DeviceContext {
                     mime_type: mime_type.to_string(),
                 }))
             },
+            PlotBackendRequest::GetRecord => {
+                let record = self._records.iter().find(|record| &record.id == plot_id);
+                let record = unwrap!(record, None => {
+                    bail!("Can't find the record of plot with id {plot_id}.");
+                });
+                Ok(PlotBackendReply::GetRecordReply(record.clone()))
+            },
+            PlotBackendRequest::DeletePlot => {
+                if let Some(socket) = self.remove_plot(plot_id) {
+                    self._closing.push(socket);
+                }
+                Ok(PlotBackendReply::DeletePlotReply())
+            },
+            PlotBackendRequest::Export(params) => {
+                let data = self.render_plot(
+                    &plot_id,
+                    params.width,
+                    params.height,
+                    params.pixel_ratio,
+                    &params.format,
+                )?;
+                let data = general_purpose::STANDARD_NO_PAD.decode(data)?;
+
+                if let Err(error) = std::fs::write(&params.path, data) {
+                    bail!("Failed to export plot to '{}': {error}", params.path);
+                }
+                Ok(PlotBackendReply::ExportReply())
+            },
+            PlotBackendRequest::Locate(params) => {
+                if self._locator.is_none() {
+                    bail!("Not waiting for a click on the plot.");
+                }
+                if self._id.as_ref() != Some(plot_id) {
+                    bail!("Can only locate points on the current plot.");
+                }
+                if params.width <= 0 || params.height <= 0 {
+                    bail!(
+                        "Invalid plot size {}x{} for locating a point.",
+                        params.width,
+                        params.height
+                    );
+                }
+                self._locator = Some(LocatorState::Clicked(params));
+                Ok(PlotBackendReply::LocateReply())
+            },
+            PlotBackendRequest::CancelLocate => {
+                if self._locator.is_some() {
+                    self._locator = Some(LocatorState::Cancelled);
+                }
+                Ok(PlotBackendReply::CancelLocateReply())
+            },
         }
     }
 
+    fn handle_history_rpc(
+        &mut self,
+        message: PlotHistoryBackendRequest,
+    ) -> anyhow::Result<PlotHistoryBackendReply> {
+        match message {
+            PlotHistoryBackendRequest::ListPlots => Ok(PlotHistoryBackendReply::ListPlotsReply(
+                self._records.clone(),
+            )),
+            PlotHistoryBackendRequest::ClearPlots => {
+                let ids: Vec<String> = self
+                    ._records
+                    .iter()
+                    .map(|record| record.id.clone())
+                    .collect();
+                for id in ids.iter() {
+                    if let Some(socket) = self.remove_plot(id) {
+                        self._closing.push(socket);
+                    }
+                }
+                Ok(PlotHistoryBackendReply::ClearPlotsReply())
+            },
+        }
+    }
+
+    /// Wait for a click on the current plot in the Plots pane, for
+    /// `locator()` and `identify()`.
+    ///
+    /// Returns the position of the click in device units, or `None` if
+    /// locating was cancelled or interrupted, or if the frontend can't
+    /// receive clicks.
+    pub fn locator(&mut self, bounds: DeviceBounds) -> Option<(f64, f64)> {
+        let main = RMain::get();
+
+        // Only the Plots pane can send clicks
+        let dynamic_plots = main.get_kernel().lock().unwrap().ui_connected() &&
+            main.session_mode == SessionMode::Console;
+        if !dynamic_plots {
+            log::warn!("Locating points is only supported in the Plots pane.");
+            return None;
+        }
+
+        // Make sure the frontend shows the current state of the plot before
+        // it's clicked on
+        if self._changes {
+            self._changes = false;
+            let (code, execution_count) = main.get_active_request().unwrap_or(("", 0));
+            self.process_changes(
+                main.get_comm_manager_tx().clone(),
+                main.get_iopub_tx().clone(),
+                dynamic_plots,
+                code,
+                execution_count,
+            );
+        }
+
+        let id = self._id.clone()?;
+        let socket = unwrap!(self._channels.get(&id), None => {
+            log::error!("Can't find socket to locate points on plot with id: {id}.");
+            return None;
+        });
+        let socket = socket.clone();
+
+        // The frontend renders the plot at the size of the device while
+        // locating, so that clicks can be mapped back to device units
+        let size = r_task(|| -> anyhow::Result<Vec<i32>> {
+            Ok(RFunction::from(".ps.graphics.deviceSize")
+                .call()?
+                .try_into()?)
+        });
+        let size = unwrap!(size, Err(error) => {
+            log::error!("Failed to get the size of the graphics device: {error}.");
+            return None;
+        });
+        let &[width, height] = &size[..] else {
+            log::error!("Unexpected graphics device size: {size:?}.");
+            return None;
+        };
+
+        let event = PlotFrontendEvent::Locator(LocatorParams {
+            width: width as i64,
+            height: height as i64,
+        });
+        let value = serde_json::to_value(event).unwrap();
+        socket
+            .outgoing_tx
+            .send(CommMsg::Data(value))
+            .or_log_error("Failed to send locator message for id {id}.");
+
+        // Handle plot comm messages, e.g. render requests, until the plot is
+        // clicked on
+        self._locator = Some(LocatorState::Waiting);
+
+        let position = loop {
+            match self._locator {
+                Some(LocatorState::Clicked(ref click)) => {
+                    break Some(bounds.device_position(click))
+                },
+                Some(LocatorState::Cancelled) | None => break None,
+                Some(LocatorState::Waiting) => {},
+            }
+
+            // The interrupt is handled by R once we return
+            if interrupts_pending() {
+                break None;
+            }
+
+            // The plot was deleted
+            if !self._channels.contains_key(&id) {
+                break None;
+            }
+
+            self.process_message(Some(LOCATOR_POLL_INTERVAL));
+        };
+
+        self._locator = None;
+
+        let value = serde_json::to_value(PlotFrontendEvent::LocatorDone).unwrap();
+        socket
+            .outgoing_tx
+            .send(CommMsg::Data(value))
+            .or_log_error("Failed to send locator message for id {id}.");
+
+        position
+    }
+
+    /// Remove a plot from the plot history and delete its snapshot.
+    ///
+    /// Returns the comm of the plot, if any, which the caller is responsible
+    /// for closing.
+    fn remove_plot(&mut self, id: &str) -> Option<CommSocket> {
+        self._records.retain(|record| record.id != id);
+        self._render_cache.invalidate(id);
+
+        // If the current page is still drawn on, it's shown as a new plot.
+        // It gets a new ID so that the deleted plot's ID isn't reused for a
+        // different comm and record.
+        if self._id.as_deref() == Some(id) {
+            self._id = Some(Uuid::new_v4().to_string());
+            self._new_page = true;
+        }
+
+        let result = r_task(|| {
+            RFunction::from(".ps.graphics.removeSnapshot")
+                .param("id", id)
+                .call()
+        });
+        if let Err(error) = result {
+            log::error!("Failed to remove snapshot of plot {id}: {error}");
+        }
+
+        self._channels.remove(id)
+    }
+
     fn get_mime_type(format: &RenderFormat) -> String {
         match format {
             RenderFormat::Png => "image/png".to_string(),
@@ -222,6 +629,8 @@ impl DeviceContext {
         comm_manager_tx: Sender<CommManagerEvent>,
         iopub_tx: Sender<IOPubMessage>,
         dynamic_plots: bool,
+        code: &str,
+        execution_count: u32,
     ) {
         let id = unwrap!(self._id.clone(), None => {
             log::error!("Unexpected uninitialized `id`.");
@@ -230,9 +639,19 @@ impl DeviceContext {
 
         if self._new_page {
             self._new_page = false;
-            self.process_new_plot(id.as_str(), comm_manager_tx, iopub_tx, dynamic_plots);
+            self._records.push(PlotRecord {
+                id: id.clone(),
+                code: code.to_string(),
+                execution_count: execution_count as i64,
+                created: Utc::now().timestamp_millis(),
+                source: self._provenance.source.clone(),
+                device: self._provenance.device.clone(),
+            });
+            self.process_new_plot(id.as_str(), comm_manager_tx, iopub_tx, dynamic_plots, code);
         } else {
-            self.process_update_plot(id.as_str(), iopub_tx, dynamic_plots);
+            // Previous renders of this plot are now out of date
+            self._render_cache.invalidate(id.as_str());
+            self.process_update_plot(id.as_str(), iopub_tx, dynamic_plots, code);
         }
     }
 
@@ -242,11 +661,12 @@ impl DeviceContext {
         comm_manager_tx: Sender<CommManagerEvent>,
         iopub_tx: Sender<IOPubMessage>,
         dynamic_plots: bool,
+        code: &str,
     ) {
         if dynamic_plots {
             self.process_new_plot_positron(id, comm_manager_tx);
         } else {
-            self.process_new_plot_jupyter_protocol(id, iopub_tx);
+            self.process_new_plot_jupyter_protocol(id, iopub_tx, code);
         }
     }
 
@@ -258,7 +678,15 @@ impl DeviceContext {
             POSITRON_PLOT_CHANNEL_ID.to_string(),
         );
 
-        let event = CommManagerEvent::Opened(socket.clone(), serde_json::Value::Null);
+        // The plot's record is sent along, so that the frontend knows where
+        // the plot comes from
+        let record = self._records.iter().find(|record| record.id == id);
+        let data = match record {
+            Some(record) => serde_json::to_value(record).unwrap(),
+            None => serde_json::Value::Null,
+        };
+
+        let event = CommManagerEvent::Opened(socket.clone(), data);
         if let Err(error) = comm_manager_tx.send(event) {
             log::error!("{}", error);
         }
@@ -267,14 +695,30 @@ impl DeviceContext {
         self._channels.insert(id.to_string(), socket.clone());
     }
 
-    fn process_new_plot_jupyter_protocol(&mut self, id: &str, iopub_tx: Sender<IOPubMessage>) {
-        let data = unwrap!(self.create_display_data_plot(id), Err(error) => {
+    fn process_new_plot_jupyter_protocol(
+        &mut self,
+        id: &str,
+        iopub_tx: Sender<IOPubMessage>,
+        code: &str,
+    ) {
+        // Plots displayed through the Jupyter protocol can't be updated or
+        // rendered again once a new page is started, so the previous ones are
+        // dropped from the history along with their snapshots
+        let previous: Vec<String> = self
+            ._records
+            .iter()
+            .filter(|record| record.id != id && !self._channels.contains_key(&record.id))
+            .map(|record| record.id.clone())
+            .collect();
+        for id in previous.iter() {
+            self.remove_plot(id);
+        }
+
+        let (data, metadata) = unwrap!(self.create_display_data_plot(id, code), Err(error) => {
             log::error!("Failed to create plot due to: {error}.");
             return;
         });
 
-        let metadata = json!({});
-
         // For `DisplayData`, the `transient` slot is a simple `Value`,
         // but we can use the `TransientValue` required by `UpdateDisplayData`
         // to structure this object since we pass through a `display_id` in
@@ -301,11 +745,12 @@ impl DeviceContext {
         id: &str,
         iopub_tx: Sender<IOPubMessage>,
         positron_connected: bool,
+        code: &str,
     ) {
         if positron_connected {
             self.process_update_plot_positron(id);
         } else {
-            self.process_update_plot_jupyter_protocol(id, iopub_tx);
+            self.process_update_plot_jupyter_protocol(id, iopub_tx, code);
         }
     }
 
@@ -328,14 +773,17 @@ impl DeviceContext {
             .or_log_error("Failed to send update message for id {id}.");
     }
 
-    fn process_update_plot_jupyter_protocol(&mut self, id: &str, iopub_tx: Sender<IOPubMessage>) {
-        let data = unwrap!(self.create_display_data_plot(id), Err(error) => {
+    fn process_update_plot_jupyter_protocol(
+        &mut self,
+        id: &str,
+        iopub_tx: Sender<IOPubMessage>,
+        code: &str,
+    ) {
+        let (data, metadata) = unwrap!(self.create_display_data_plot(id, code), Err(error) => {
             log::error!("Failed to create plot due to: {error}.");
             return;
         });
 
-        let metadata = json!({});
-
         let transient = TransientValue {
             display_id: id.to_string(),
             data: None,
@@ -352,21 +800,59 @@ impl DeviceContext {
             .or_log_warning(&format!("Could not publish update display data on IOPub."));
     }
 
-    fn create_display_data_plot(&mut self, id: &str) -> Result<serde_json::Value, anyhow::Error> {
-        // TODO: Take these from R global options? Like `ark.plot.width`?
-        let width = 800;
-        let height = 600;
-        let pixel_ratio = 1.0;
-        let format = RenderFormat::Png;
-
-        let data = unwrap!(self.render_plot(id, width, height, pixel_ratio, &format), Err(error) => {
-            bail!("Failed to render plot with id {id} due to: {error}.");
-        });
-
-        let mut map = serde_json::Map::new();
-        map.insert("image/png".to_string(), serde_json::to_value(data).unwrap());
+    /// Render the plot in each of the formats requested by the `ark.plot.*`
+    /// options and the cell's overrides.
+    ///
+    /// Returns the `data` and `metadata` of the `display_data` message.
+    fn create_display_data_plot(
+        &mut self,
+        id: &str,
+        code: &str,
+    ) -> anyhow::Result<(serde_json::Value, serde_json::Value)> {
+        let mut options = r_task(|| JupyterPlotOptions::from_r_options())?;
+        options.apply_cell_overrides(code);
+
+        let mut data = serde_json::Map::new();
+        let mut metadata = serde_json::Map::new();
+
+        for format in options.formats.iter() {
+            let rendered = self.render_plot(
+                id,
+                options.width,
+                options.height,
+                options.pixel_ratio(),
+                format,
+            );
+            let rendered = unwrap!(rendered, Err(error) => {
+                bail!("Failed to render plot with id {id} due to: {error}.");
+            });
+
+            let mime_type = Self::get_mime_type(format);
+
+            let value = match format {
+                // SVG is sent as text rather than base64
+                RenderFormat::Svg => {
+                    let svg = general_purpose::STANDARD_NO_PAD.decode(rendered)?;
+                    String::from_utf8(svg)?
+                },
+                _ => rendered,
+            };
+
+            // High resolution images are displayed at their requested size
+            if matches!(format, RenderFormat::Png | RenderFormat::Jpeg) {
+                metadata.insert(
+                    mime_type.clone(),
+                    json!({ "width": options.width, "height": options.height }),
+                );
+            }
+
+            data.insert(mime_type, serde_json::Value::String(value));
+        }
 
-        Ok(serde_json::Value::Object(map))
+        Ok((
+            serde_json::Value::Object(data),
+            serde_json::Value::Object(metadata),
+        ))
     }
 
     fn render_plot(
@@ -377,40 +863,156 @@ impl DeviceContext {
         pixel_ratio: f64,
         format: &RenderFormat,
     ) -> anyhow::Result<String> {
-        // Render the plot to file.
-        // TODO: Is it possible to do this without writing to file; e.g. could
-        // we instead write to a connection or something else?
+        let key = RenderCacheKey::new(plot_id, width, height, pixel_ratio, format);
+        if let Some(data) = self._render_cache.get(&key) {
+            return Ok(data.clone());
+        }
+
+        // Render the plot to an in-memory image
         self._rendering = true;
-        let image_path = r_task(|| unsafe {
-            RFunction::from(".ps.graphics.renderPlot")
+        let image = r_task(|| -> anyhow::Result<Vec<u8>> {
+            Ok(RFunction::from(".ps.graphics.renderPlot")
                 .param("id", plot_id)
                 .param("width", RObject::try_from(width)?)
                 .param("height", RObject::try_from(height)?)
                 .param("dpr", pixel_ratio)
                 .param("format", format.to_string())
                 .call()?
-                .to::<String>()
+                .try_into()?)
         });
         self._rendering = false;
 
-        let image_path = unwrap!(image_path, Err(error) => {
+        let image = unwrap!(image, Err(error) => {
             bail!("Failed to render plot with id {plot_id} due to: {error}.");
         });
 
-        // Read contents into bytes.
-        let conn = File::open(image_path)?;
-        let mut reader = BufReader::new(conn);
-
-        let mut buffer = vec![];
-        reader.read_to_end(&mut buffer)?;
-
         // what an odd interface
-        let data = general_purpose::STANDARD_NO_PAD.encode(buffer);
+        let data = general_purpose::STANDARD_NO_PAD.encode(image);
 
+        self._render_cache.insert(key, data.clone());
         Ok(data)
     }
 }
 
+/// The size, resolution and formats of plots emitted as Jupyter
+/// `display_data`, e.g. in notebooks.
+///
+/// These are set with the `ark.plot.width`, `ark.plot.height` (in pixels),
+/// `ark.plot.res` (in DPI) and `ark.plot.formats` R options. They can be
+/// overridden for a single cell with comments like `#| ark.plot.width: 400`.
+#[derive(Debug, Clone, PartialEq)]
+struct JupyterPlotOptions {
+    width: i64,
+    height: i64,
+    res: f64,
+    // The resolution of plots with a pixel ratio of 1
+    default_res: f64,
+    formats: Vec<RenderFormat>,
+}
+
+impl JupyterPlotOptions {
+    /// Read the options from R. Must be called on the R thread.
+    fn from_r_options() -> anyhow::Result<Self> {
+        let options: HashMap<String, RObject> = RFunction::from("jupyterPlotOptions")
+            .call_in(ARK_ENVS.positron_ns)?
+            .try_into()?;
+
+        let get = |name: &str| -> anyhow::Result<RObject> {
+            match options.get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(anyhow!("Missing plot option `{name}`")),
+            }
+        };
+
+        let width: f64 = get("width")?.try_into()?;
+        let height: f64 = get("height")?.try_into()?;
+        let formats: Vec<String> = get("formats")?.try_into()?;
+
+        Ok(Self {
+            width: width as i64,
+            height: height as i64,
+            res: get("res")?.try_into()?,
+            default_res: get("default_res")?.try_into()?,
+            formats: parse_plot_formats(&formats),
+        })
+    }
+
+    /// Apply the `#| ark.plot.<name>: <value>` comments of a cell.
+    fn apply_cell_overrides(&mut self, code: &str) {
+        for line in code.lines() {
+            let Some(option) = line.trim_start().strip_prefix("#|") else {
+                continue;
+            };
+            let Some((name, value)) = option.split_once(':') else {
+                continue;
+            };
+            let name = name.trim();
+            let value = value.trim();
+
+            let number = || -> Option<f64> {
+                let number = value.parse::<f64>().ok().filter(|number| *number > 0.0);
+                if number.is_none() {
+                    log::warn!("Ignoring invalid value `{value}` for plot option `{name}`.");
+                }
+                number
+            };
+
+            match name {
+                "ark.plot.width" => {
+                    if let Some(width) = number() {
+                        self.width = width as i64;
+                    }
+                },
+                "ark.plot.height" => {
+                    if let Some(height) = number() {
+                        self.height = height as i64;
+                    }
+                },
+                "ark.plot.res" => {
+                    if let Some(res) = number() {
+                        self.res = res;
+                    }
+                },
+                "ark.plot.formats" => {
+                    // Accept both `png, svg` and `[png, svg]`
+                    let formats: Vec<String> = value
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .split(',')
+                        .map(|format| format.trim().trim_matches(['"', '\'']).to_string())
+                        .collect();
+                    self.formats = parse_plot_formats(&formats);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn pixel_ratio(&self) -> f64 {
+        self.res / self.default_res
+    }
+}
+
+// Unknown formats are skipped. Falls back to PNG if no formats are valid.
+fn parse_plot_formats(formats: &[String]) -> Vec<RenderFormat> {
+    let mut out = Vec::<RenderFormat>::new();
+
+    for format in formats {
+        let value = serde_json::Value::String(format.to_lowercase());
+        match serde_json::from_value::<RenderFormat>(value) {
+            Ok(format) if !out.contains(&format) => out.push(format),
+            Ok(_) => {},
+            Err(_) => log::warn!("Ignoring unknown plot format `{format}`."),
+        }
+    }
+
+    if out.is_empty() {
+        out.push(RenderFormat::Png);
+    }
+
+    out
+}
+
 static mut DEVICE_CONTEXT: Lazy<DeviceContext> = Lazy::new(|| DeviceContext::default());
 
 // TODO: This macro needs to be updated every time we introduce support
@@ -447,12 +1049,24 @@ pub unsafe fn on_process_events() {
     DEVICE_CONTEXT.on_process_events();
 }
 
+pub unsafe fn on_plot_history_open(socket: CommSocket) {
+    DEVICE_CONTEXT.on_plot_history_open(socket);
+}
+
 pub unsafe fn on_did_execute_request(
     comm_manager_tx: Sender<CommManagerEvent>,
     iopub_tx: Sender<IOPubMessage>,
     dynamic_plots: bool,
+    code: &str,
+    execution_count: u32,
 ) {
-    DEVICE_CONTEXT.on_did_execute_request(comm_manager_tx, iopub_tx, dynamic_plots);
+    DEVICE_CONTEXT.on_did_execute_request(
+        comm_manager_tx,
+        iopub_tx,
+        dynamic_plots,
+        code,
+        execution_count,
+    );
 }
 
 // NOTE: May be called when rendering a plot to file, since this is done by
@@ -511,6 +1125,46 @@ unsafe extern "C" fn gd_new_page(dd: pGEcontext, dev: pDevDesc) {
     DEVICE_CONTEXT.new_page(dd, dev);
 }
 
+/// The extent of `dev`, which isn't necessarily the current device.
+unsafe fn device_bounds(dev: pDevDesc) -> DeviceBounds {
+    // `pDevDesc` is opaque, see the versioned structs of `libr`
+    macro_rules! bounds {
+        ($versioned:ty) => {{
+            let dev = dev as $versioned;
+            DeviceBounds {
+                left: (*dev).left,
+                right: (*dev).right,
+                bottom: (*dev).bottom,
+                top: (*dev).top,
+            }
+        }};
+    }
+
+    match libr::R_GE_getVersion() {
+        13 => bounds!(libr::pDevDescVersion13),
+        14 => bounds!(libr::pDevDescVersion14),
+        15 => bounds!(libr::pDevDescVersion15),
+        16 => bounds!(libr::pDevDescVersion16),
+        version => panic!(
+            "R graphics engine version {} is not supported by this version of Positron.",
+            version
+        ),
+    }
+}
+
+unsafe extern "C" fn gd_locator(x: *mut f64, y: *mut f64, dev: pDevDesc) -> Rboolean {
+    trace!("gd_locator");
+
+    match DEVICE_CONTEXT.locator(device_bounds(dev)) {
+        Some((x_dev, y_dev)) => {
+            *x = x_dev;
+            *y = y_dev;
+            Rboolean_TRUE
+        },
+        None => Rboolean_FALSE,
+    }
+}
+
 unsafe fn ps_graphics_device_impl() -> anyhow::Result<SEXP> {
     // TODO: Don't allow creation of more than one graphics device.
     // TODO: Allow customization of the graphics device here?
@@ -559,6 +1213,11 @@ unsafe fn ps_graphics_device_impl() -> anyhow::Result<SEXP> {
 
         callbacks.newPage = (*device).newPage;
         (*device).newPage = Some(gd_new_page);
+
+        // Clicks come from the Plots pane rather than the underlying device.
+        // `haveLocator` is 2 for "yes".
+        (*device).locator = Some(gd_locator);
+        (*device).haveLocator = 2;
     });
 
     Ok(R_NilValue)
@@ -574,6 +1233,13 @@ unsafe extern "C" fn ps_graphics_device() -> anyhow::Result<SEXP> {
 
 #[harp::register]
 unsafe extern "C" fn ps_graphics_event(_name: SEXP) -> anyhow::Result<SEXP> {
+    // Called from the plot hooks, before the new page is created. The call
+    // stack is still the one of the code drawing the plot.
+    match record_plot_provenance() {
+        Ok(provenance) => DEVICE_CONTEXT._pending_provenance = Some(provenance),
+        Err(error) => log::error!("Failed to record plot provenance: {error}"),
+    }
+
     let id = unwrap!(DEVICE_CONTEXT._id.clone(), None => {
         return Ok(Rf_ScalarLogical(0));
     });
@@ -589,3 +1255,217 @@ unsafe extern "C" fn ps_graphics_event(_name: SEXP) -> anyhow::Result<SEXP> {
 
     Ok(Rf_ScalarLogical(1))
 }
+
+fn record_plot_provenance() -> anyhow::Result<PlotProvenance> {
+    let provenance = RFunction::from(".ps.graphics.plotProvenance").call()?;
+    let provenance = serde_json::Value::try_from(provenance)?;
+    Ok(serde_json::from_value(provenance)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use harp::environment::R_ENVS;
+    use harp::eval::r_parse_eval0;
+    use harp::utils::r_is_null;
+
+    use super::*;
+    use crate::test::r_test;
+
+    fn key(id: &str, width: i64) -> RenderCacheKey {
+        RenderCacheKey::new(id, width, 400, 2.0, &RenderFormat::Png)
+    }
+
+    #[test]
+    fn test_render_cache() {
+        let mut cache = RenderCache::default();
+        cache.insert(key("a", 100), String::from("a100"));
+        cache.insert(key("a", 200), String::from("a200"));
+        cache.insert(key("b", 100), String::from("b100"));
+
+        assert_eq!(cache.get(&key("a", 100)), Some(&String::from("a100")));
+        assert_eq!(cache.get(&key("a", 300)), None);
+
+        // Other render parameters are part of the key
+        let svg = RenderCacheKey::new("a", 100, 400, 2.0, &RenderFormat::Svg);
+        assert_eq!(cache.get(&svg), None);
+        let low_dpi = RenderCacheKey::new("a", 100, 400, 1.0, &RenderFormat::Png);
+        assert_eq!(cache.get(&low_dpi), None);
+
+        // Invalidation only affects renders of the given plot
+        cache.invalidate("a");
+        assert_eq!(cache.get(&key("a", 100)), None);
+        assert_eq!(cache.get(&key("a", 200)), None);
+        assert_eq!(cache.get(&key("b", 100)), Some(&String::from("b100")));
+    }
+
+    fn jupyter_plot_options() -> JupyterPlotOptions {
+        JupyterPlotOptions {
+            width: 800,
+            height: 600,
+            res: 72.0,
+            default_res: 72.0,
+            formats: vec![RenderFormat::Png],
+        }
+    }
+
+    #[test]
+    fn test_jupyter_plot_options_cell_overrides() {
+        let mut options = jupyter_plot_options();
+        options.apply_cell_overrides(
+            "#| ark.plot.width: 400\n#|ark.plot.res:144\n#| ark.plot.formats: [png, \"svg\", pdf]\nplot(1:10)",
+        );
+        assert_eq!(options.width, 400);
+        assert_eq!(options.height, 600);
+        assert_eq!(options.pixel_ratio(), 2.0);
+        assert_eq!(options.formats, vec![
+            RenderFormat::Png,
+            RenderFormat::Svg,
+            RenderFormat::Pdf
+        ]);
+
+        // Invalid values and other options are ignored
+        let mut options = jupyter_plot_options();
+        options.apply_cell_overrides(
+            "#| ark.plot.height: tall\n#| ark.plot.width: -1\n#| echo: false\n# ark.plot.res: 144",
+        );
+        assert_eq!(options, jupyter_plot_options());
+    }
+
+    #[test]
+    fn test_parse_plot_formats() {
+        let formats = |formats: &[&str]| {
+            let formats: Vec<String> = formats.iter().map(|format| format.to_string()).collect();
+            parse_plot_formats(&formats)
+        };
+
+        assert_eq!(formats(&["SVG", "jpeg"]), vec![
+            RenderFormat::Svg,
+            RenderFormat::Jpeg
+        ]);
+
+        // Duplicates and unknown formats are skipped
+        assert_eq!(formats(&["svg", "gif", "svg"]), vec![RenderFormat::Svg]);
+
+        // Falls back to PNG
+        assert_eq!(formats(&["gif"]), vec![RenderFormat::Png]);
+        assert_eq!(formats(&[]), vec![RenderFormat::Png]);
+    }
+
+    #[test]
+    fn test_device_position() {
+        let click = |x: f64, y: f64| LocateParams {
+            x,
+            y,
+            width: 200,
+            height: 100,
+        };
+
+        // Raster devices have their origin at the top left
+        let bounds = DeviceBounds {
+            left: 0.0,
+            right: 480.0,
+            bottom: 240.0,
+            top: 0.0,
+        };
+        assert_eq!(bounds.device_position(&click(0.0, 0.0)), (0.0, 0.0));
+        assert_eq!(bounds.device_position(&click(50.0, 25.0)), (120.0, 60.0));
+        assert_eq!(bounds.device_position(&click(200.0, 100.0)), (480.0, 240.0));
+
+        // Other devices have their origin at the bottom left
+        let bounds = DeviceBounds {
+            left: 0.0,
+            right: 480.0,
+            bottom: 0.0,
+            top: 240.0,
+        };
+        assert_eq!(bounds.device_position(&click(50.0, 25.0)), (120.0, 180.0));
+    }
+
+    #[test]
+    fn test_render_cache_eviction() {
+        let mut cache = RenderCache::default();
+        for width in 0..(RENDER_CACHE_CAPACITY as i64 + 1) {
+            cache.insert(key("a", width), width.to_string());
+        }
+
+        // The oldest render is evicted first
+        assert_eq!(cache.get(&key("a", 0)), None);
+        assert_eq!(cache.get(&key("a", 1)), Some(&String::from("1")));
+        assert_eq!(cache.renders.len(), RENDER_CACHE_CAPACITY);
+    }
+
+    fn render_test_plot(function: &str, format: &str) -> Option<Vec<u8>> {
+        let plot = r_parse_eval0(
+            "local({
+                grDevices::pdf(NULL)
+                on.exit(grDevices::dev.off())
+                plot(1:10)
+                grDevices::recordPlot()
+            })",
+            R_ENVS.global,
+        )
+        .unwrap();
+
+        let image = RFunction::from(function)
+            .add(plot)
+            .add(format)
+            .add(400.0)
+            .add(300.0)
+            .add(72.0)
+            .call_in(ARK_ENVS.positron_ns)
+            .unwrap();
+
+        if r_is_null(image.sexp) {
+            return None;
+        }
+        Some(image.try_into().unwrap())
+    }
+
+    #[test]
+    fn test_render_in_memory() {
+        r_test(|| {
+            // PDF devices only write to files
+            assert_eq!(render_test_plot("renderInMemory", "pdf"), None);
+
+            // Other formats are rendered in memory when the rendering
+            // packages are installed
+            let has_packages = |packages: &str| -> bool {
+                r_parse_eval0(
+                    &format!("all(vapply({packages}, .ps.is_installed, logical(1)))"),
+                    ARK_ENVS.positron_ns,
+                )
+                .and_then(|value| value.try_into())
+                .unwrap()
+            };
+            if has_packages("c('ragg', 'png')") {
+                let png = render_test_plot("renderInMemory", "png").unwrap();
+                assert_eq!(&png[1..4], b"PNG");
+            }
+            if has_packages("'svglite'") {
+                let svg = render_test_plot("renderInMemory", "svg").unwrap();
+                assert!(String::from_utf8(svg).unwrap().contains("<svg"));
+            }
+        })
+    }
+
+    #[test]
+    fn test_render_with_file() {
+        r_test(|| {
+            let files = || -> i32 {
+                r_parse_eval0("length(list.files(tempdir()))", R_ENVS.global)
+                    .and_then(|value| value.try_into())
+                    .unwrap()
+            };
+            let before = files();
+
+            let pdf = render_test_plot("renderWithFile", "pdf").unwrap();
+            assert_eq!(&pdf[..4], b"%PDF");
+
+            let png = render_test_plot("renderWithFile", "png").unwrap();
+            assert_eq!(&png[1..4], b"PNG");
+
+            // The temporary files are removed
+            assert_eq!(files(), before);
+        })
+    }
+}
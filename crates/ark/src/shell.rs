@@ -230,6 +230,11 @@ impl ShellHandler for Shell {
             ExecuteResponse::ReplyException(err) => Err(err),
         };
 
+        let execution_count = match &result {
+            Ok(reply) => reply.execution_count,
+            Err(err) => err.execution_count,
+        };
+
         let mut kernel = self.kernel.lock().unwrap();
 
         // Check for pending graphics updates
@@ -240,6 +245,8 @@ impl ShellHandler for Shell {
                 self.comm_manager_tx.clone(),
                 self.iopub_tx.clone(),
                 kernel.ui_connected() && self.session_mode == SessionMode::Console,
+                &req_clone.code,
+                execution_count,
             )
         };
 
@@ -283,6 +290,7 @@ impl ShellHandler for Shell {
                 self.kernel_request_tx.clone(),
             ),
             Comm::Help => handle_comm_open_help(comm),
+            Comm::PlotHistory => handle_comm_open_plot_history(comm),
             _ => Ok(false),
         }
     }
@@ -345,6 +353,13 @@ fn handle_comm_open_help(comm: CommSocket) -> Result<bool, Exception> {
     })
 }
 
+fn handle_comm_open_plot_history(comm: CommSocket) -> Result<bool, Exception> {
+    // The plot history belongs to the graphics device, which handles the
+    // comm's requests alongside those of the plot comms
+    r_task(|| unsafe { graphics_device::on_plot_history_open(comm) });
+    Ok(true)
+}
+
 // Kernel is shared with the main R thread
 fn listen(kernel_mutex: Arc<Mutex<Kernel>>, kernel_request_rx: Receiver<KernelRequest>) {
     loop {
@@ -4,8 +4,16 @@
 // Copyright (C) 2024 Posit Software, PBC. All rights reserved.
 //
 //
+use amalthea::comm::base_comm::JsonRpcError;
 use amalthea::comm::comm_channel::CommMsg;
 use amalthea::comm::data_explorer_comm::ArraySelection;
+use amalthea::comm::data_explorer_comm::ColumnDisplayType;
+use amalthea::comm::data_explorer_comm::ColumnFilter;
+use amalthea::comm::data_explorer_comm::ColumnFilterParams;
+use amalthea::comm::data_explorer_comm::ColumnFilterType;
+use amalthea::comm::data_explorer_comm::ColumnFrequencyTableParams;
+use amalthea::comm::data_explorer_comm::ColumnHistogramParams;
+use amalthea::comm::data_explorer_comm::ColumnProfileParams;
 use amalthea::comm::data_explorer_comm::ColumnProfileRequest;
 use amalthea::comm::data_explorer_comm::ColumnProfileSpec;
 use amalthea::comm::data_explorer_comm::ColumnProfileType;
@@ -23,7 +31,9 @@ use amalthea::comm::data_explorer_comm::ExportFormat;
 use amalthea::comm::data_explorer_comm::ExportedData;
 use amalthea::comm::data_explorer_comm::FilterComparison;
 use amalthea::comm::data_explorer_comm::FilterComparisonOp;
+use amalthea::comm::data_explorer_comm::FilterMatchDataTypes;
 use amalthea::comm::data_explorer_comm::FilterResult;
+use amalthea::comm::data_explorer_comm::FilterSetMembership;
 use amalthea::comm::data_explorer_comm::FilterTextSearch;
 use amalthea::comm::data_explorer_comm::FormatOptions;
 use amalthea::comm::data_explorer_comm::GetColumnProfilesParams;
@@ -34,12 +44,16 @@ use amalthea::comm::data_explorer_comm::RowFilter;
 use amalthea::comm::data_explorer_comm::RowFilterCondition;
 use amalthea::comm::data_explorer_comm::RowFilterParams;
 use amalthea::comm::data_explorer_comm::RowFilterType;
+use amalthea::comm::data_explorer_comm::SearchSchemaParams;
 use amalthea::comm::data_explorer_comm::Selection;
+use amalthea::comm::data_explorer_comm::SetCellValueParams;
+use amalthea::comm::data_explorer_comm::SetColumnFiltersParams;
 use amalthea::comm::data_explorer_comm::SetRowFiltersParams;
 use amalthea::comm::data_explorer_comm::SetSortColumnsParams;
 use amalthea::comm::data_explorer_comm::SummaryStatsBoolean;
 use amalthea::comm::data_explorer_comm::SummaryStatsNumber;
 use amalthea::comm::data_explorer_comm::SummaryStatsString;
+use amalthea::comm::data_explorer_comm::SupportStatus;
 use amalthea::comm::data_explorer_comm::TableSelection;
 use amalthea::comm::data_explorer_comm::TableSelectionKind;
 use amalthea::comm::data_explorer_comm::TextSearchType;
@@ -146,6 +160,42 @@ fn socket_rpc(
     socket_rpc_request::<DataExplorerBackendRequest, DataExplorerBackendReply>(&socket.socket, req)
 }
 
+/// Helper method for sending a request that modifies the data, like a cell
+/// edit, and receiving a reply. The data explorer notifies the frontend of
+/// the change before replying.
+///
+/// Returns the update event and the reply.
+fn socket_rpc_with_update(
+    socket: &DataExplorerSocket,
+    req: DataExplorerBackendRequest,
+) -> (DataExplorerFrontendEvent, DataExplorerBackendReply) {
+    let json = serde_json::to_value(req).unwrap();
+    socket
+        .socket
+        .incoming_tx
+        .send(CommMsg::Rpc(String::from("update"), json))
+        .unwrap();
+
+    let recv = || {
+        socket
+            .socket
+            .outgoing_rx
+            .recv_timeout(std::time::Duration::from_secs(1))
+            .unwrap()
+    };
+
+    let event = match recv() {
+        CommMsg::Data(value) => serde_json::from_value(value).unwrap(),
+        msg => panic!("Expected an update event, got {msg:?}"),
+    };
+    let reply = match recv() {
+        CommMsg::Rpc(_id, value) => serde_json::from_value(value).unwrap(),
+        msg => panic!("Unexpected Comm Message {msg:?}"),
+    };
+
+    (event, reply)
+}
+
 fn default_format_options() -> FormatOptions {
     FormatOptions {
         large_num_digits: 2,
@@ -737,6 +787,295 @@ fn test_summary_stats() {
     })
 }
 
+#[test]
+fn test_histogram() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.frame(x = c(1, 2, 2, 3, 3, 3, 4, 4, 4, 4, NA), y = c(rep('a', 5), rep('b', 6)))",
+            None,
+        )
+        .unwrap();
+
+        let histogram_req = || {
+            DataExplorerBackendRequest::GetColumnProfiles(GetColumnProfilesParams {
+                profiles: vec![ColumnProfileRequest {
+                    column_index: 0,
+                    profiles: vec![ColumnProfileSpec {
+                        profile_type: ColumnProfileType::Histogram,
+                        params: Some(ColumnProfileParams::Histogram(ColumnHistogramParams {
+                            num_bins: 3,
+                            quantiles: Some(vec![0.5]),
+                        })),
+                    }],
+                }],
+                format_options: default_format_options(),
+            })
+        };
+
+        assert_match!(socket_rpc(&socket, histogram_req()),
+           DataExplorerBackendReply::GetColumnProfilesReply(data) => {
+               assert_eq!(data.len(), 1);
+               let histogram = data[0].histogram.clone().unwrap();
+               assert_eq!(histogram.bin_edges, vec!["1.00", "2.00", "3.00", "4.00"]);
+               assert_eq!(histogram.bin_counts, vec![1, 2, 7]);
+               assert_eq!(histogram.quantiles[0].value, "3.00".to_string());
+           }
+        );
+
+        // Filter the rows so that only the `b` rows remain. The histogram
+        // should only take those rows into account.
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            _ => panic!("Unexpected reply"),
+        };
+
+        let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+            filters: vec![RowFilter {
+                column_schema: schema.columns[1].clone(),
+                filter_type: RowFilterType::Compare,
+                filter_id: "5B1E1F0C-7C4E-4B11-9C0B-2AF4B6C3E1D7".to_string(),
+                condition: RowFilterCondition::And,
+                is_valid: None,
+                params: Some(RowFilterParams::Comparison(FilterComparison {
+                    op: FilterComparisonOp::Eq,
+                    value: "b".to_string(),
+                })),
+                error_message: None,
+            }],
+        });
+        socket_rpc(&socket, req);
+
+        assert_match!(socket_rpc(&socket, histogram_req()),
+           DataExplorerBackendReply::GetColumnProfilesReply(data) => {
+               let histogram = data[0].histogram.clone().unwrap();
+               assert_eq!(histogram.bin_edges, vec!["3.00", "3.33", "3.67", "4.00"]);
+               assert_eq!(histogram.bin_counts, vec![1, 0, 4]);
+           }
+        );
+    })
+}
+
+#[test]
+fn test_frequency_table() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.frame(
+                x = factor(c('a', 'b', 'b', 'c', 'c', 'c'), levels = c('a', 'b', 'c', 'd')),
+                y = c(TRUE, TRUE, FALSE, FALSE, FALSE, NA)
+            )",
+            None,
+        )
+        .unwrap();
+
+        let frequency_table_req = |column_index| {
+            DataExplorerBackendRequest::GetColumnProfiles(GetColumnProfilesParams {
+                profiles: vec![ColumnProfileRequest {
+                    column_index,
+                    profiles: vec![ColumnProfileSpec {
+                        profile_type: ColumnProfileType::FrequencyTable,
+                        params: Some(ColumnProfileParams::FrequencyTable(
+                            ColumnFrequencyTableParams { limit: 2 },
+                        )),
+                    }],
+                }],
+                format_options: default_format_options(),
+            })
+        };
+
+        assert_match!(socket_rpc(&socket, frequency_table_req(0)),
+           DataExplorerBackendReply::GetColumnProfilesReply(data) => {
+               let table = data[0].frequency_table.clone().unwrap();
+               assert_eq!(table.values, vec!["c", "b"]);
+               assert_eq!(table.counts, vec![3, 2]);
+               assert_eq!(table.other_count, Some(1));
+           }
+        );
+
+        assert_match!(socket_rpc(&socket, frequency_table_req(1)),
+           DataExplorerBackendReply::GetColumnProfilesReply(data) => {
+               let table = data[0].frequency_table.clone().unwrap();
+               assert_eq!(table.values, vec!["FALSE", "TRUE"]);
+               assert_eq!(table.counts, vec![3, 2]);
+               assert_eq!(table.other_count, Some(0));
+           }
+        );
+
+        // Only keep the rows where `y` is true. The counts should reflect the
+        // filtered rows, and unused levels are reported with a zero count.
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            _ => panic!("Unexpected reply"),
+        };
+
+        let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+            filters: vec![RowFilter {
+                column_schema: schema.columns[1].clone(),
+                filter_type: RowFilterType::IsTrue,
+                filter_id: "9A3C5B8E-2D41-4F6A-8B7C-1E0D9F2A3B4C".to_string(),
+                condition: RowFilterCondition::And,
+                is_valid: None,
+                params: None,
+                error_message: None,
+            }],
+        });
+        socket_rpc(&socket, req);
+
+        assert_match!(socket_rpc(&socket, frequency_table_req(0)),
+           DataExplorerBackendReply::GetColumnProfilesReply(data) => {
+               let table = data[0].frequency_table.clone().unwrap();
+               assert_eq!(table.values, vec!["a", "b"]);
+               assert_eq!(table.counts, vec![1, 1]);
+               assert_eq!(table.other_count, Some(0));
+           }
+        );
+    })
+}
+
+#[test]
+fn test_search_schema() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.frame(id = 1:2, gene_a = c(1, 2), gene_b = c(3, 4), gene_c = c('x', 'y'), gene_d = c(5, 6))",
+            None,
+        )
+        .unwrap();
+
+        let search_req = |filters: Vec<ColumnFilter>, start_index, max_results| {
+            DataExplorerBackendRequest::SearchSchema(SearchSchemaParams {
+                filters,
+                start_index,
+                max_results,
+            })
+        };
+
+        let gene_filter = ColumnFilter {
+            filter_type: ColumnFilterType::TextSearch,
+            params: ColumnFilterParams::TextSearch(FilterTextSearch {
+                search_type: TextSearchType::StartsWith,
+                term: "GENE".to_string(),
+                case_sensitive: false,
+            }),
+        };
+        let number_filter = ColumnFilter {
+            filter_type: ColumnFilterType::MatchDataTypes,
+            params: ColumnFilterParams::MatchDataTypes(FilterMatchDataTypes {
+                display_types: vec![ColumnDisplayType::Number],
+            }),
+        };
+
+        // Without filters, all the columns are returned
+        assert_match!(socket_rpc(&socket, search_req(vec![], 0, 10)),
+            DataExplorerBackendReply::SearchSchemaReply(result) => {
+                assert_eq!(result.total_num_matches, 5);
+                assert_eq!(result.matches.columns.len(), 5);
+            }
+        );
+
+        // Filters are combined, and results are paged
+        let filters = vec![gene_filter.clone(), number_filter.clone()];
+        assert_match!(socket_rpc(&socket, search_req(filters.clone(), 0, 2)),
+            DataExplorerBackendReply::SearchSchemaReply(result) => {
+                assert_eq!(result.total_num_matches, 3);
+                let names: Vec<String> = result.matches.columns.iter().map(|c| c.column_name.clone()).collect();
+                assert_eq!(names, vec!["gene_a", "gene_b"]);
+            }
+        );
+        assert_match!(socket_rpc(&socket, search_req(filters, 2, 2)),
+            DataExplorerBackendReply::SearchSchemaReply(result) => {
+                assert_eq!(result.total_num_matches, 3);
+                assert_eq!(result.matches.columns.len(), 1);
+                assert_eq!(result.matches.columns[0].column_name, "gene_d");
+                assert_eq!(result.matches.columns[0].column_index, 4);
+            }
+        );
+    })
+}
+
+#[test]
+fn test_column_filters() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.frame(id = 1:2, gene_a = c(1, 2), gene_b = c('x', 'y'), gene_c = c(3, 4))",
+            None,
+        )
+        .unwrap();
+
+        let number_filter = ColumnFilter {
+            filter_type: ColumnFilterType::MatchDataTypes,
+            params: ColumnFilterParams::MatchDataTypes(FilterMatchDataTypes {
+                display_types: vec![ColumnDisplayType::Number],
+            }),
+        };
+        let gene_filter = ColumnFilter {
+            filter_type: ColumnFilterType::TextSearch,
+            params: ColumnFilterParams::TextSearch(FilterTextSearch {
+                search_type: TextSearchType::StartsWith,
+                term: "gene".to_string(),
+                case_sensitive: false,
+            }),
+        };
+
+        let req = DataExplorerBackendRequest::SetColumnFilters(SetColumnFiltersParams {
+            filters: vec![number_filter, gene_filter],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetColumnFiltersReply() => {}
+        );
+
+        // The table shape only counts the visible columns
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.table_shape.num_columns, 2);
+                assert_eq!(state.table_unfiltered_shape.num_columns, 4);
+                assert_eq!(state.column_filters.len(), 2);
+            }
+        );
+
+        // Column indices are relative to the visible columns
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1, 2],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetSchemaReply(schema) => {
+                let names: Vec<String> = schema.columns.iter().map(|c| c.column_name.clone()).collect();
+                assert_eq!(names, vec!["gene_a", "gene_c"]);
+                assert_eq!(schema.columns[1].column_index, 3);
+            }
+        );
+
+        let req = get_data_values_request(0, 2, vec![1], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns.len(), 1);
+                assert_eq!(data.columns[0][0], ColumnValue::FormattedValue("3.00".to_string()));
+                assert_eq!(data.columns[0][1], ColumnValue::FormattedValue("4.00".to_string()));
+            }
+        );
+
+        // Clearing the filters makes all the columns visible again
+        let req = DataExplorerBackendRequest::SetColumnFilters(SetColumnFiltersParams {
+            filters: vec![],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetColumnFiltersReply() => {}
+        );
+
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.table_shape.num_columns, 4);
+            }
+        );
+    })
+}
+
 #[test]
 fn test_search_filters() {
     r_test(|| {
@@ -1017,6 +1356,668 @@ fn test_search_filters() {
     });
 }
 
+#[test]
+fn test_set_membership_filters() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            r#"data.frame(
+                chr = c("a", "b", "c", NA, "a"),
+                fct = factor(c("x", "y", "z", "x", NA)),
+                num = c(1, 2.5, NA, 4, 1),
+                lgl = c(TRUE, FALSE, NA, TRUE, TRUE)
+            )"#,
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1, 2, 3],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+
+        let set_membership_filter =
+            |column_index: usize, values: Vec<&str>, inclusive: bool| RowFilter {
+                column_schema: schema.columns[column_index].clone(),
+                filter_type: RowFilterType::SetMembership,
+                filter_id: "0D8F2C5B-3B3E-4A4B-9E59-5D1B6B3D8A21".to_string(),
+                condition: RowFilterCondition::And,
+                is_valid: None,
+                params: Some(RowFilterParams::SetMembership(FilterSetMembership {
+                    values: values.into_iter().map(String::from).collect(),
+                    inclusive,
+                })),
+                error_message: None,
+            };
+
+        let check = |filter: RowFilter, expected_num_rows: i64| {
+            let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+                filters: vec![filter],
+            });
+            assert_match!(socket_rpc(&socket, req),
+                DataExplorerBackendReply::SetRowFiltersReply(
+                    FilterResult { selected_num_rows: num_rows, had_errors: Some(false) }
+                ) => {
+                    assert_eq!(num_rows, expected_num_rows);
+                }
+            );
+        };
+
+        // Missing values are excluded by inclusive filters...
+        check(set_membership_filter(0, vec!["a", "c"], true), 3);
+        check(set_membership_filter(1, vec!["x", "z"], true), 3);
+        check(set_membership_filter(2, vec!["1", "2.5"], true), 3);
+        check(set_membership_filter(3, vec!["TRUE"], true), 3);
+
+        // ...and kept by exclusive ones
+        check(set_membership_filter(0, vec!["a", "c"], false), 2);
+        check(set_membership_filter(1, vec!["x", "z"], false), 2);
+        check(set_membership_filter(2, vec!["1", "2.5"], false), 2);
+        check(set_membership_filter(3, vec!["TRUE"], false), 2);
+
+        // An empty set matches nothing
+        check(set_membership_filter(0, vec![], true), 0);
+
+        // Values that can't be converted to the column type are reported as errors
+        let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+            filters: vec![set_membership_filter(2, vec!["one"], true)],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetRowFiltersReply(
+                FilterResult { selected_num_rows: 5, had_errors: Some(true) }
+            ) => {}
+        );
+    })
+}
+
+#[test]
+fn test_row_filter_conditions() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.frame(x = c(1, 2, 3, 4, 5, NA), y = c(TRUE, FALSE, TRUE, FALSE, TRUE, TRUE))",
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+
+        let compare_filter = |op: FilterComparisonOp, value: &str, condition| RowFilter {
+            column_schema: schema.columns[0].clone(),
+            filter_type: RowFilterType::Compare,
+            filter_id: "5A0BC52F-9C1B-4C0B-A1C4-3F9A4E1B9D11".to_string(),
+            condition,
+            is_valid: None,
+            params: Some(RowFilterParams::Comparison(FilterComparison {
+                op,
+                value: value.to_string(),
+            })),
+            error_message: None,
+        };
+        let true_filter = |condition| RowFilter {
+            column_schema: schema.columns[1].clone(),
+            filter_type: RowFilterType::IsTrue,
+            filter_id: "9E3D0C1A-7E2B-4B7E-8C0A-1D2E3F4A5B6C".to_string(),
+            condition,
+            is_valid: None,
+            params: None,
+            error_message: None,
+        };
+
+        let set_row_filters = |filters: Vec<RowFilter>| {
+            let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams { filters });
+            match socket_rpc(&socket, req) {
+                DataExplorerBackendReply::SetRowFiltersReply(result) => result,
+                reply => panic!("Unexpected reply: {:?}", reply),
+            }
+        };
+
+        // x < 2 | x > 4
+        let result = set_row_filters(vec![
+            compare_filter(FilterComparisonOp::Lt, "2", RowFilterCondition::And),
+            compare_filter(FilterComparisonOp::Gt, "4", RowFilterCondition::Or),
+        ]);
+        assert_eq!(result.selected_num_rows, 2);
+        assert_eq!(result.had_errors, Some(false));
+
+        // The condition of the first filter is ignored
+        let result = set_row_filters(vec![
+            compare_filter(FilterComparisonOp::Lt, "2", RowFilterCondition::Or),
+            compare_filter(FilterComparisonOp::Gt, "4", RowFilterCondition::Or),
+        ]);
+        assert_eq!(result.selected_num_rows, 2);
+
+        // Filters are combined from left to right: (x < 2 | x > 4) & y
+        let result = set_row_filters(vec![
+            compare_filter(FilterComparisonOp::Lt, "2", RowFilterCondition::And),
+            compare_filter(FilterComparisonOp::Gt, "4", RowFilterCondition::Or),
+            true_filter(RowFilterCondition::And),
+        ]);
+        assert_eq!(result.selected_num_rows, 2);
+
+        // Rows with missing values don't match: x > 4 | y
+        let result = set_row_filters(vec![
+            compare_filter(FilterComparisonOp::Gt, "4", RowFilterCondition::And),
+            true_filter(RowFilterCondition::Or),
+        ]);
+        assert_eq!(result.selected_num_rows, 4);
+
+        // Filters that fail are skipped and reported, while the others are
+        // still combined
+        let failing_filter = RowFilter {
+            filter_type: RowFilterType::SetMembership,
+            params: Some(RowFilterParams::SetMembership(FilterSetMembership {
+                values: vec!["one".to_string()],
+                inclusive: true,
+            })),
+            ..compare_filter(FilterComparisonOp::Eq, "1", RowFilterCondition::Or)
+        };
+        let result = set_row_filters(vec![
+            compare_filter(FilterComparisonOp::Lt, "2", RowFilterCondition::And),
+            failing_filter,
+            compare_filter(FilterComparisonOp::Gt, "4", RowFilterCondition::Or),
+        ]);
+        assert_eq!(result.selected_num_rows, 2);
+        assert_eq!(result.had_errors, Some(true));
+
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.supported_features.set_row_filters.supports_conditions, SupportStatus::Supported);
+                assert_eq!(state.row_filters[0].is_valid, Some(true));
+                assert_eq!(state.row_filters[1].is_valid, Some(false));
+                assert_eq!(state.row_filters[2].is_valid, Some(true));
+            }
+        );
+    })
+}
+
+#[test]
+fn test_nested_columns() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            r#"local({
+                x <- data.frame(id = 1:3)
+                x$list <- list(1:2, "a", NULL)
+                x$nested <- data.frame(a = c(1, NA, 3), b = c("u", NA, "w"))
+                x
+            })"#,
+            None,
+        )
+        .unwrap();
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1, 2],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetSchemaReply(schema) => {
+                assert_eq!(schema.columns[0].children, None);
+                assert_eq!(schema.columns[1].type_display, ColumnDisplayType::Array);
+                assert_eq!(schema.columns[2].type_display, ColumnDisplayType::Struct);
+
+                // Nested data frame columns are described by their children,
+                // indexed after the columns of the table
+                let children = schema.columns[2].children.clone().unwrap();
+                let names: Vec<String> = children.iter().map(|c| c.column_name.clone()).collect();
+                assert_eq!(names, vec!["a", "b"]);
+                assert_eq!(children[0].column_index, 3);
+                assert_eq!(children[1].column_index, 4);
+                assert_eq!(children[1].type_display, ColumnDisplayType::String);
+            }
+        );
+
+        // The values of the children can be fetched on their own
+        let req = get_data_values_request(0, 3, vec![3, 4], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0][0], ColumnValue::FormattedValue("1.00".to_string()));
+                assert_eq!(data.columns[0][1], ColumnValue::SpecialValueCode(1));
+                assert_eq!(data.columns[1][2], ColumnValue::FormattedValue("w".to_string()));
+            }
+        );
+
+        // Also when columns are filtered out, since children are indexed
+        // relative to the unfiltered schema
+        let req = DataExplorerBackendRequest::SetColumnFilters(SetColumnFiltersParams {
+            filters: vec![ColumnFilter {
+                filter_type: ColumnFilterType::TextSearch,
+                params: ColumnFilterParams::TextSearch(FilterTextSearch {
+                    search_type: TextSearchType::Contains,
+                    term: "nested".to_string(),
+                    case_sensitive: false,
+                }),
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetColumnFiltersReply() => {}
+        );
+
+        let req = get_data_values_request(0, 3, vec![4], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0][0], ColumnValue::FormattedValue("u".to_string()));
+            }
+        );
+
+        // List columns and nested data frames are summarised
+        let req = get_data_values_request(0, 3, vec![1, 2], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0][0], ColumnValue::FormattedValue("<integer [2]>".to_string()));
+                assert_eq!(data.columns[0][2], ColumnValue::SpecialValueCode(0));
+                assert_eq!(data.columns[1][0], ColumnValue::FormattedValue("{a: 1.00, b: u}".to_string()));
+                assert_eq!(data.columns[1][1], ColumnValue::FormattedValue("{a: NA, b: NA}".to_string()));
+            }
+        );
+
+        // A row of a nested data frame is missing when all its fields are
+        let req = DataExplorerBackendRequest::GetColumnProfiles(GetColumnProfilesParams {
+            profiles: vec![ColumnProfileRequest {
+                column_index: 2,
+                profiles: vec![ColumnProfileSpec {
+                    profile_type: ColumnProfileType::NullCount,
+                    params: None,
+                }],
+            }],
+            format_options: default_format_options(),
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetColumnProfilesReply(data) => {
+                assert_eq!(data[0].null_count, Some(1));
+            }
+        );
+    })
+}
+
+#[test]
+fn test_data_table_keys() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "data.table::data.table(x = c(3, 1, 2), y = c('a', 'b', 'a'), key = c('y', 'x'))",
+            None,
+        );
+
+        // Skip test if data.table is not installed
+        let Ok(socket) = socket else {
+            return;
+        };
+
+        // The keys are exposed as the default sort
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.sort_keys, vec![
+                    ColumnSortKey { column_index: 1, ascending: true },
+                    ColumnSortKey { column_index: 0, ascending: true },
+                ]);
+            }
+        );
+
+        // The rows are already ordered by the keys
+        let req = get_data_values_request(0, 3, vec![0], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0], vec![
+                    ColumnValue::FormattedValue("2.00".to_string()),
+                    ColumnValue::FormattedValue("3.00".to_string()),
+                    ColumnValue::FormattedValue("1.00".to_string()),
+                ]);
+            }
+        );
+    })
+}
+
+#[test]
+fn test_lazy_table_backend() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "dbplyr::memdb_frame(x = c(3, 1, 4, 1, 5), y = c('a', 'b', 'c', 'd', 'e'))",
+            None,
+        );
+
+        // Skip test if dbplyr or RSQLite are not installed
+        let Ok(socket) = socket else {
+            return;
+        };
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+        assert_eq!(schema.columns[0].column_name, "x");
+        assert_eq!(schema.columns[0].type_display, ColumnDisplayType::Number);
+        assert_eq!(schema.columns[1].type_display, ColumnDisplayType::String);
+
+        // Rows are counted by the database
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.table_shape.num_rows, 5);
+                assert_eq!(state.supported_features.export_data_selection.support_status, SupportStatus::Unsupported);
+            }
+        );
+
+        // Sorts and filters are computed by the database
+        let req = DataExplorerBackendRequest::SetSortColumns(SetSortColumnsParams {
+            sort_keys: vec![ColumnSortKey {
+                column_index: 0,
+                ascending: false,
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req), DataExplorerBackendReply::SetSortColumnsReply() => {});
+
+        let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+            filters: vec![RowFilter {
+                column_schema: schema.columns[0].clone(),
+                filter_type: RowFilterType::Compare,
+                filter_id: "2B0D3A6E-5F4C-4E0B-9B1A-7C6D5E4F3A2B".to_string(),
+                condition: RowFilterCondition::And,
+                is_valid: None,
+                params: Some(RowFilterParams::Comparison(FilterComparison {
+                    op: FilterComparisonOp::Gt,
+                    value: "1".to_string(),
+                })),
+                error_message: None,
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetRowFiltersReply(
+                FilterResult { selected_num_rows: 3, had_errors: Some(false) }
+            ) => {}
+        );
+
+        // Only the requested page is fetched
+        let req = get_data_values_request(1, 2, vec![1], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0], vec![
+                    ColumnValue::FormattedValue("c".to_string()),
+                    ColumnValue::FormattedValue("a".to_string()),
+                ]);
+            }
+        );
+    })
+}
+
+#[test]
+fn test_lazy_table_untranslatable_filter() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "dbplyr::memdb_frame(x = c(3, 1, 4, 1, 5), y = c('a', 'b', 'c', 'd', 'e'))",
+            None,
+        );
+
+        // Skip test if dbplyr or RSQLite are not installed
+        let Ok(socket) = socket else {
+            return;
+        };
+
+        let req = DataExplorerBackendRequest::GetSchema(GetSchemaParams {
+            column_indices: vec![0, 1],
+        });
+        let schema = match socket_rpc(&socket, req) {
+            DataExplorerBackendReply::GetSchemaReply(schema) => schema,
+            reply => panic!("Unexpected reply: {:?}", reply),
+        };
+
+        // SQLite can't translate the `grepl()` call of search filters, which
+        // is then reported as invalid while the other filters still apply
+        let req = DataExplorerBackendRequest::SetRowFilters(SetRowFiltersParams {
+            filters: vec![
+                RowFilter {
+                    column_schema: schema.columns[0].clone(),
+                    filter_type: RowFilterType::Compare,
+                    filter_id: "6C1F2B7A-3D4E-4F5A-8B9C-0D1E2F3A4B5C".to_string(),
+                    condition: RowFilterCondition::And,
+                    is_valid: None,
+                    params: Some(RowFilterParams::Comparison(FilterComparison {
+                        op: FilterComparisonOp::Gt,
+                        value: "1".to_string(),
+                    })),
+                    error_message: None,
+                },
+                RowFilter {
+                    column_schema: schema.columns[1].clone(),
+                    filter_type: RowFilterType::Search,
+                    filter_id: "7D2A3C8B-4E5F-4A6B-9C0D-1E2F3A4B5C6D".to_string(),
+                    condition: RowFilterCondition::And,
+                    is_valid: None,
+                    params: Some(RowFilterParams::TextSearch(FilterTextSearch {
+                        search_type: TextSearchType::Contains,
+                        term: "a".to_string(),
+                        case_sensitive: false,
+                    })),
+                    error_message: None,
+                },
+            ],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetRowFiltersReply(
+                FilterResult { selected_num_rows: 3, had_errors: Some(true) }
+            ) => {}
+        );
+
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.row_filters[0].is_valid, Some(true));
+                assert_eq!(state.row_filters[1].is_valid, Some(false));
+                assert!(state.row_filters[1].error_message.is_some());
+            }
+        );
+
+        // The rows can still be fetched
+        let req = get_data_values_request(0, 3, vec![1], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0], vec![
+                    ColumnValue::FormattedValue("a".to_string()),
+                    ColumnValue::FormattedValue("c".to_string()),
+                    ColumnValue::FormattedValue("e".to_string()),
+                ]);
+            }
+        );
+    })
+}
+
+#[test]
+fn test_arrow_table_backend() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "arrow::arrow_table(x = c(3, 1, 4, 1, 5), y = c('a', 'b', 'c', 'd', 'e'))",
+            None,
+        );
+
+        // Skip test if arrow is not installed
+        let Ok(socket) = socket else {
+            return;
+        };
+
+        let req = DataExplorerBackendRequest::GetState;
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.table_shape.num_rows, 5);
+            }
+        );
+
+        // Sorts are computed by Acero
+        let req = DataExplorerBackendRequest::SetSortColumns(SetSortColumnsParams {
+            sort_keys: vec![ColumnSortKey {
+                column_index: 0,
+                ascending: false,
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req), DataExplorerBackendReply::SetSortColumnsReply() => {});
+
+        // Only the requested page is brought into R
+        let req = get_data_values_request(1, 2, vec![1], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0], vec![
+                    ColumnValue::FormattedValue("c".to_string()),
+                    ColumnValue::FormattedValue("a".to_string()),
+                ]);
+            }
+        );
+    })
+}
+
+#[test]
+fn test_cell_edits() {
+    r_test(|| {
+        let socket = open_data_explorer_from_expression(
+            "x <- data.frame(num = c(1, 2, 3), int = 1:3, fct = factor(c('lo', 'hi', 'lo')))",
+            Some("x"),
+        )
+        .unwrap();
+
+        let set_cell_value = |row_index, column_index, value: &str| {
+            DataExplorerBackendRequest::SetCellValue(SetCellValueParams {
+                row_index,
+                column_index,
+                value: value.to_string(),
+            })
+        };
+
+        let eval = |code: &str| r_parse_eval0(code, R_ENVS.global).unwrap();
+
+        // Editing is supported for tables bound to a variable
+        assert_match!(socket_rpc(&socket, DataExplorerBackendRequest::GetState),
+            DataExplorerBackendReply::GetStateReply(state) => {
+                assert_eq!(state.supported_features.set_cell_value.unwrap().support_status, SupportStatus::Supported);
+            }
+        );
+
+        // Sort the rows in descending order, so that the first row of the
+        // view is the last row of the table
+        let req = DataExplorerBackendRequest::SetSortColumns(SetSortColumnsParams {
+            sort_keys: vec![ColumnSortKey {
+                column_index: 0,
+                ascending: false,
+            }],
+        });
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::SetSortColumnsReply() => {}
+        );
+
+        // The edit is written back to the binding and the frontend is
+        // notified
+        let (event, reply) = socket_rpc_with_update(&socket, set_cell_value(0, 0, "10"));
+        assert_match!(event, DataExplorerFrontendEvent::DataUpdate);
+        assert_match!(reply, DataExplorerBackendReply::SetCellValueReply() => {});
+        assert_eq!(f64::try_from(eval("x$num[[3]]")).unwrap(), 10.0);
+
+        // The data reflects the edit
+        let req = get_data_values_request(0, 3, vec![0], default_format_options());
+        assert_match!(socket_rpc(&socket, req),
+            DataExplorerBackendReply::GetDataValuesReply(data) => {
+                assert_eq!(data.columns[0][0], ColumnValue::FormattedValue("10.00".to_string()));
+            }
+        );
+
+        // Values are coerced to the type of the column
+        let (_, reply) = socket_rpc_with_update(&socket, set_cell_value(0, 1, "5"));
+        assert_match!(reply, DataExplorerBackendReply::SetCellValueReply() => {});
+        assert_eq!(i32::try_from(eval("x$int[[3]]")).unwrap(), 5);
+
+        let (_, reply) = socket_rpc_with_update(&socket, set_cell_value(2, 2, "hi"));
+        assert_match!(reply, DataExplorerBackendReply::SetCellValueReply() => {});
+        assert_eq!(
+            String::try_from(eval("as.character(x$fct[[1]])")).unwrap(),
+            "hi"
+        );
+
+        // Values that can't be coerced are rejected and the table is left
+        // untouched
+        for (column_index, value) in [(1, "2.5"), (1, "five"), (2, "mid")] {
+            let reply = socket_rpc_request::<DataExplorerBackendRequest, JsonRpcError>(
+                &socket.socket,
+                set_cell_value(0, column_index, value),
+            );
+            assert!(!reply.error.message.is_empty());
+        }
+        assert_eq!(i32::try_from(eval("x$int[[3]]")).unwrap(), 5);
+
+        // Edits are undone in reverse order
+        let (event, reply) =
+            socket_rpc_with_update(&socket, DataExplorerBackendRequest::UndoCellEdit);
+        assert_match!(event, DataExplorerFrontendEvent::DataUpdate);
+        assert_match!(reply, DataExplorerBackendReply::UndoCellEditReply() => {});
+        assert_eq!(
+            String::try_from(eval("as.character(x$fct[[1]])")).unwrap(),
+            "lo"
+        );
+        assert_eq!(i32::try_from(eval("x$int[[3]]")).unwrap(), 5);
+
+        let (_, reply) = socket_rpc_with_update(&socket, DataExplorerBackendRequest::UndoCellEdit);
+        assert_match!(reply, DataExplorerBackendReply::UndoCellEditReply() => {});
+        assert_eq!(i32::try_from(eval("x$int[[3]]")).unwrap(), 3);
+
+        // Edits can no longer be undone once the variable is modified
+        // elsewhere
+        eval("x$num[[1]] <- 0");
+        EVENTS.console_prompt.emit(());
+        assert_match!(socket.socket.outgoing_rx.recv_timeout(std::time::Duration::from_secs(1)).unwrap(),
+            CommMsg::Data(value) => {
+                assert_match!(serde_json::from_value::<DataExplorerFrontendEvent>(value).unwrap(),
+                    DataExplorerFrontendEvent::DataUpdate
+                );
+        });
+
+        let reply = socket_rpc_request::<DataExplorerBackendRequest, JsonRpcError>(
+            &socket.socket,
+            DataExplorerBackendRequest::UndoCellEdit,
+        );
+        assert!(reply.error.message.contains("no cell edits to undo"));
+        assert_eq!(f64::try_from(eval("x$num[[3]]")).unwrap(), 10.0);
+    });
+}
+
+#[test]
+fn test_cell_edits_undo_limit() {
+    r_test(|| {
+        let socket =
+            open_data_explorer_from_expression("x <- data.frame(num = 0)", Some("x")).unwrap();
+
+        // Only the last 20 edits are kept
+        for i in 1..=21 {
+            let req = DataExplorerBackendRequest::SetCellValue(SetCellValueParams {
+                row_index: 0,
+                column_index: 0,
+                value: i.to_string(),
+            });
+            let (_, reply) = socket_rpc_with_update(&socket, req);
+            assert_match!(reply, DataExplorerBackendReply::SetCellValueReply() => {});
+        }
+
+        for _ in 0..20 {
+            let (_, reply) =
+                socket_rpc_with_update(&socket, DataExplorerBackendRequest::UndoCellEdit);
+            assert_match!(reply, DataExplorerBackendReply::UndoCellEditReply() => {});
+        }
+
+        let reply = socket_rpc_request::<DataExplorerBackendRequest, JsonRpcError>(
+            &socket.socket,
+            DataExplorerBackendRequest::UndoCellEdit,
+        );
+        assert!(reply.error.message.contains("no cell edits to undo"));
+
+        // The first edit can no longer be undone
+        let num = r_parse_eval0("x$num", R_ENVS.global).unwrap();
+        assert_eq!(f64::try_from(num).unwrap(), 1.0);
+    });
+}
+
 #[test]
 fn test_live_updates() {
     r_test(|| {
@@ -603,6 +603,18 @@ impl From<&Vec<i64>> for RObject {
     }
 }
 
+impl From<&Vec<f64>> for RObject {
+    fn from(values: &Vec<f64>) -> Self {
+        unsafe {
+            let vector = RObject::from(Rf_allocVector(REALSXP, values.len() as isize));
+            for idx in 0..values.len() {
+                SET_REAL_ELT(vector.sexp, idx as isize, values[idx]);
+            }
+            return vector;
+        }
+    }
+}
+
 // Convert a String -> String HashMap into named character vector.
 impl From<HashMap<String, String>> for RObject {
     fn from(value: HashMap<String, String>) -> Self {
@@ -877,6 +889,22 @@ impl TryFrom<RObject> for Vec<i32> {
     }
 }
 
+impl TryFrom<RObject> for Vec<u8> {
+    type Error = crate::error::Error;
+    fn try_from(value: RObject) -> Result<Self, Self::Error> {
+        unsafe {
+            r_assert_type(value.sexp, &[RAWSXP, NILSXP])?;
+            if r_is_null(value.sexp) {
+                return Ok(Vec::new());
+            }
+
+            let n = Rf_xlength(value.sexp) as usize;
+            let bytes = std::slice::from_raw_parts(RAW(value.sexp), n);
+            return Ok(bytes.to_vec());
+        }
+    }
+}
+
 impl TryFrom<RObject> for Vec<RObject> {
     type Error = crate::error::Error;
     fn try_from(value: RObject) -> Result<Self, Self::Error> {
@@ -1605,6 +1633,26 @@ mod tests {
         }
     }
 
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_tryfrom_RObject_Vec_u8() {
+        r_test! {
+            let v = r_parse_eval0("as.raw(c(0, 1, 255))", R_ENVS.global).unwrap();
+            assert_match!(
+                Vec::<u8>::try_from(v),
+                Ok(x) => {
+                    assert_eq!(x, vec![0u8, 1, 255]);
+                }
+            );
+
+            let v = r_parse_eval0("raw()", R_ENVS.global).unwrap();
+            assert_eq!(Vec::<u8>::try_from(v).unwrap(), Vec::<u8>::new());
+
+            let v = r_parse_eval0("1:3", R_ENVS.global).unwrap();
+            assert!(Vec::<u8>::try_from(v).is_err());
+        }
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn test_tryfrom_RObject_Vec_RObject() {
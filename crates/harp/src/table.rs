@@ -4,6 +4,7 @@ use crate::exec::RFunction;
 use crate::exec::RFunctionExt;
 use crate::object::r_length;
 use crate::object::RObject;
+use crate::utils::r_inherits;
 use crate::utils::r_is_data_frame;
 use crate::utils::r_is_matrix;
 use crate::utils::r_typeof;
@@ -20,6 +21,9 @@ pub struct TableInfo {
     pub kind: TableKind,
     pub dims: TableDim,
     pub col_names: ColumnNames,
+    /// Names of the columns the rows are known to be sorted by, in order of
+    /// precedence. For instance the keys of a data.table.
+    pub sort_keys: Vec<String>,
 }
 
 // TODO: Might want to encode as types with methods so that we can make
@@ -68,15 +72,30 @@ pub fn df_info(x: SEXP) -> anyhow::Result<TableInfo> {
     unsafe {
         let dims = df_dim(x);
         let col_names = ColumnNames::new(Rf_getAttrib(x, R_NamesSymbol));
+        let sort_keys = df_sort_keys(x)?;
 
         Ok(TableInfo {
             kind: TableKind::Dataframe,
             dims,
             col_names,
+            sort_keys,
         })
     }
 }
 
+/// Returns the keys of a data.table, which is always physically sorted by
+/// them. Other data frames don't record how they are sorted.
+fn df_sort_keys(x: SEXP) -> anyhow::Result<Vec<String>> {
+    if !r_inherits(x, "data.table") {
+        return Ok(vec![]);
+    }
+
+    match RObject::view(x).attr("sorted") {
+        Some(keys) if r_typeof(keys.sexp) == STRSXP => Ok(keys.try_into()?),
+        _ => Ok(vec![]),
+    }
+}
+
 pub fn mat_info(x: SEXP) -> anyhow::Result<TableInfo> {
     let dims = mat_dim(x);
 
@@ -87,6 +106,7 @@ pub fn mat_info(x: SEXP) -> anyhow::Result<TableInfo> {
         kind: TableKind::Matrix,
         dims,
         col_names,
+        sort_keys: vec![],
     })
 }
 